@@ -10,9 +10,9 @@ use reqwest_impersonate::browser::ChromeVersion;
 
 #[tokio::main]
 async fn main() -> Result<(), reqwest_impersonate::Error> {
-    // Build a client to mimic Chrome 104
+    // Build a client to mimic Chrome 131
     let client = reqwest_impersonate::Client::builder()
-        .chrome_builder(ChromeVersion::V108)
+        .chrome_builder(ChromeVersion::V131)
         .build()
         .unwrap();
 
@@ -0,0 +1,413 @@
+//! HTTP Alternative Services
+//!
+//! A server can advertise, with the `Alt-Svc` response header, that its
+//! origin is also reachable at another endpoint, usually over HTTP/3. Like
+//! Chrome, a `Client` with an Alt-Svc [`Cache`] sends later requests to an
+//! advertised `h3` alternative, and falls back to the origin if it can't be
+//! reached.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use percent_encoding::percent_decode_str;
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::header::HeaderValue;
+
+/// The max-age of an alternative without an `ma` parameter.
+const DEFAULT_MAX_AGE: u64 = 24 * 60 * 60;
+
+/// How long an alternative which could not be reached is skipped.
+#[cfg(feature = "http3")]
+const BROKEN_FOR: Duration = Duration::from_secs(5 * 60);
+
+/// A store of the alternative services origins advertised.
+///
+/// Alternatives are kept until their max-age runs out, or until their origin
+/// sends `Alt-Svc: clear` or a new list of alternatives.
+///
+/// A `Cache` can be serialized, e.g. with `serde_json`, to be loaded again
+/// along with the cookies of a session. Alternatives found to be broken are
+/// not part of it.
+#[derive(Default)]
+pub struct Cache(RwLock<Inner>);
+
+/// An endpoint an origin is also reachable at.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Alternative {
+    protocol: String,
+    host: String,
+    port: u16,
+    /// In seconds since the Unix epoch.
+    expires: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    origins: HashMap<String, Vec<Alternative>>,
+    broken: HashMap<(String, Alternative), Instant>,
+}
+
+/// Where a request is sent instead of its origin.
+#[cfg(feature = "http3")]
+#[derive(Clone, Debug)]
+pub(crate) struct Route {
+    pub(crate) origin: String,
+    pub(crate) alternative: Alternative,
+}
+
+/// The serialized form of a `Cache`.
+#[derive(Deserialize, Serialize)]
+struct CacheDef {
+    origins: HashMap<String, Vec<Alternative>>,
+}
+
+// ===== impl Cache =====
+
+impl Cache {
+    /// Creates an empty cache.
+    pub fn new() -> Cache {
+        Cache::default()
+    }
+
+    /// Records the `Alt-Svc` header value `alt_svc`, received from `url`.
+    ///
+    /// Only `https` origins can advertise alternatives; values from other
+    /// URLs, and malformed ones, are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use reqwest_impersonate::{alt_svc::Cache, Url};
+    ///
+    /// let url = "https://example.com".parse::<Url>().unwrap();
+    /// let cache = Cache::new();
+    /// cache.add_alt_svc_str(r#"h3=":443"; ma=3600"#, &url);
+    ///
+    /// assert_eq!(cache.alternatives(&url)[0].protocol(), "h3");
+    /// ```
+    pub fn add_alt_svc_str(&self, alt_svc: &str, url: &url::Url) {
+        let origin = match origin(url) {
+            Some(origin) => origin,
+            None => return,
+        };
+        let alternatives = match parse(alt_svc, SystemTime::now()) {
+            Some(alternatives) => alternatives,
+            None => return,
+        };
+
+        let mut inner = self.0.write().unwrap();
+        if alternatives.is_empty() {
+            inner.origins.remove(&origin);
+        } else {
+            inner.origins.insert(origin, alternatives);
+        }
+    }
+
+    /// Returns the alternatives of the origin of `url` which have not
+    /// expired yet, in order of preference.
+    pub fn alternatives(&self, url: &url::Url) -> Vec<Alternative> {
+        match origin(url) {
+            Some(origin) => self.unexpired(&origin),
+            None => Vec::new(),
+        }
+    }
+
+    /// Forgets every alternative, e.g. after a network change.
+    pub fn clear(&self) {
+        let mut inner = self.0.write().unwrap();
+        inner.origins.clear();
+        inner.broken.clear();
+    }
+
+    fn unexpired(&self, origin: &str) -> Vec<Alternative> {
+        let now = unix_time(SystemTime::now());
+        self.0
+            .read()
+            .unwrap()
+            .origins
+            .get(origin)
+            .into_iter()
+            .flatten()
+            .filter(|alt| alt.expires > now)
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn set_alt_svc(&self, alt_svc: &HeaderValue, url: &url::Url) {
+        if let Ok(alt_svc) = alt_svc.to_str() {
+            self.add_alt_svc_str(alt_svc, url);
+        }
+    }
+
+    /// Returns the alternative a request to `url` should be sent to instead,
+    /// if any.
+    #[cfg(feature = "http3")]
+    pub(crate) fn route(&self, uri: &http::Uri) -> Option<Route> {
+        if uri.scheme_str() != Some("https") {
+            return None;
+        }
+        let origin = format!("https://{}:{}", uri.host()?, uri.port_u16().unwrap_or(443));
+        let alternatives = self.unexpired(&origin);
+        let now = Instant::now();
+        let inner = self.0.read().unwrap();
+        let alternative = alternatives
+            .into_iter()
+            .filter(|alt| alt.protocol == "h3")
+            .find(|alt| {
+                inner
+                    .broken
+                    .get(&(origin.clone(), alt.clone()))
+                    .map_or(true, |until| *until <= now)
+            })?;
+        Some(Route {
+            origin,
+            alternative,
+        })
+    }
+
+    /// Skips the alternative of `route` for a while, as it could not be
+    /// reached.
+    #[cfg(feature = "http3")]
+    pub(crate) fn mark_broken(&self, route: Route) {
+        let mut inner = self.0.write().unwrap();
+        let now = Instant::now();
+        inner.broken.retain(|_, until| *until > now);
+        inner
+            .broken
+            .insert((route.origin, route.alternative), now + BROKEN_FOR);
+    }
+}
+
+impl fmt::Debug for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("origins", &self.0.read().unwrap().origins)
+            .finish()
+    }
+}
+
+impl Serialize for Cache {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let now = unix_time(SystemTime::now());
+        let origins = self
+            .0
+            .read()
+            .unwrap()
+            .origins
+            .iter()
+            .map(|(origin, alts)| {
+                let alts = alts.iter().filter(|alt| alt.expires > now).cloned();
+                (origin.clone(), alts.collect::<Vec<_>>())
+            })
+            .filter(|(_, alts)| !alts.is_empty())
+            .collect();
+        CacheDef { origins }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cache {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Cache, D::Error> {
+        let def = CacheDef::deserialize(deserializer)?;
+        Ok(Cache(RwLock::new(Inner {
+            origins: def.origins,
+            broken: HashMap::new(),
+        })))
+    }
+}
+
+// ===== impl Alternative =====
+
+impl Alternative {
+    /// The ALPN protocol ID of the alternative, e.g. `h3`.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// The host of the alternative; empty for the host of the origin.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The port of the alternative.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// When the alternative expires.
+    pub fn expires(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.expires)
+    }
+}
+
+fn origin(url: &url::Url) -> Option<String> {
+    if url.scheme() != "https" {
+        return None;
+    }
+    Some(format!(
+        "https://{}:{}",
+        url.host_str()?,
+        url.port_or_known_default()?
+    ))
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Parses an `Alt-Svc` header value, as described in RFC 7838.
+///
+/// Returns an empty list for `clear`, and `None` if the value is malformed.
+fn parse(value: &str, now: SystemTime) -> Option<Vec<Alternative>> {
+    if value.trim() == "clear" {
+        return Some(Vec::new());
+    }
+
+    let mut alternatives = Vec::new();
+    for entry in split_quoted(value, ',') {
+        let mut params = split_quoted(entry, ';');
+        let (protocol, authority) = params.next()?.split_once('=')?;
+        let protocol = percent_decode_str(protocol.trim()).decode_utf8().ok()?;
+        let authority = unquote(authority.trim())?;
+        let (host, port) = authority.rsplit_once(':')?;
+        let port = port.parse().ok()?;
+
+        let mut max_age = DEFAULT_MAX_AGE;
+        for param in params {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("ma") {
+                    max_age = unquote(value.trim())?.parse().ok()?;
+                }
+            }
+        }
+
+        alternatives.push(Alternative {
+            protocol: protocol.into_owned(),
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_owned(),
+            port,
+            expires: unix_time(now).saturating_add(max_age),
+        });
+    }
+    Some(alternatives)
+}
+
+/// Splits `value` on `sep`, except within quoted strings.
+fn split_quoted(value: &str, sep: char) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+    let mut escaped = false;
+    value
+        .split(move |c| {
+            if escaped {
+                escaped = false;
+            } else if quoted && c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                quoted = !quoted;
+            } else if c == sep && !quoted {
+                return true;
+            }
+            false
+        })
+        .filter(|part| !part.trim().is_empty())
+}
+
+fn unquote(value: &str) -> Option<&str> {
+    match value.strip_prefix('"') {
+        Some(quoted) => quoted.strip_suffix('"'),
+        None => Some(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn parse_alternatives() {
+        let alts = parse(
+            r#"h3=":443"; ma=2592000; persist=1, h2="alt.example.com:8443", h3-29=":443""#,
+            at(1000),
+        )
+        .unwrap();
+
+        assert_eq!(alts.len(), 3);
+        assert_eq!(alts[0].protocol(), "h3");
+        assert_eq!(alts[0].host(), "");
+        assert_eq!(alts[0].port(), 443);
+        assert_eq!(alts[0].expires(), at(1000 + 2592000));
+        assert_eq!(alts[1].protocol(), "h2");
+        assert_eq!(alts[1].host(), "alt.example.com");
+        assert_eq!(alts[1].port(), 8443);
+        assert_eq!(alts[1].expires(), at(1000 + DEFAULT_MAX_AGE));
+        assert_eq!(alts[2].protocol(), "h3-29");
+    }
+
+    #[test]
+    fn parse_clear() {
+        assert_eq!(parse("clear", at(0)), Some(Vec::new()));
+    }
+
+    #[test]
+    fn parse_percent_encoded_protocol() {
+        let alts = parse(r#"w%3Dx%3Ay=":443""#, at(0)).unwrap();
+        assert_eq!(alts[0].protocol(), "w=x:y");
+    }
+
+    #[test]
+    fn parse_malformed() {
+        assert_eq!(parse("h3", at(0)), None);
+        assert_eq!(parse(r#"h3=":port""#, at(0)), None);
+        assert_eq!(parse(r#"h3=":443"; ma=soon"#, at(0)), None);
+    }
+
+    #[test]
+    fn clear_removes_origin() {
+        let url = "https://example.com".parse::<url::Url>().unwrap();
+        let cache = Cache::new();
+        cache.add_alt_svc_str(r#"h3=":443""#, &url);
+        assert_eq!(cache.alternatives(&url).len(), 1);
+
+        cache.add_alt_svc_str("clear", &url);
+        assert!(cache.alternatives(&url).is_empty());
+    }
+
+    #[test]
+    fn ignores_http_origins() {
+        let url = "http://example.com".parse::<url::Url>().unwrap();
+        let cache = Cache::new();
+        cache.add_alt_svc_str(r#"h3=":443""#, &url);
+        assert!(cache.alternatives(&url).is_empty());
+    }
+
+    #[test]
+    fn expired_alternatives_are_skipped() {
+        let url = "https://example.com".parse::<url::Url>().unwrap();
+        let cache = Cache::new();
+        cache.add_alt_svc_str(r#"h3=":443"; ma=0"#, &url);
+        assert!(cache.alternatives(&url).is_empty());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn serde_round_trip() {
+        let url = "https://example.com".parse::<url::Url>().unwrap();
+        let cache = Cache::new();
+        cache.add_alt_svc_str(r#"h3=":443"; ma=3600"#, &url);
+
+        let json = serde_json::to_string(&cache).unwrap();
+        let loaded: Cache = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.alternatives(&url), cache.alternatives(&url));
+    }
+}
@@ -108,13 +108,19 @@ impl Body {
         }
     }
 
-    pub(crate) fn response(body: hyper::Body, timeout: Option<Pin<Box<Sleep>>>) -> Body {
-        Body {
-            inner: Inner::Streaming {
-                body: Box::pin(WrapHyper(body)),
-                timeout,
-            },
+    /// Applies the total timeout of a request to its response body, unless
+    /// it is buffered already.
+    pub(crate) fn with_timeout(mut self, timeout: Option<Pin<Box<Sleep>>>) -> Body {
+        if let Inner::Streaming {
+            timeout: ref mut slot,
+            ..
+        } = self.inner
+        {
+            if timeout.is_some() {
+                *slot = timeout;
+            }
         }
+        self
     }
 
     pub(crate) fn empty() -> Body {
@@ -6,12 +6,13 @@ use std::{fmt, str};
 
 use bytes::Bytes;
 use http::header::{
-    Entry, HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH,
-    CONTENT_TYPE, LOCATION, PROXY_AUTHORIZATION, RANGE, REFERER, TRANSFER_ENCODING, USER_AGENT,
+    Entry, HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, ALT_SVC, CONTENT_ENCODING,
+    CONTENT_LENGTH, CONTENT_TYPE, LOCATION, PROXY_AUTHORIZATION, RANGE, REFERER, TRANSFER_ENCODING,
+    USER_AGENT,
 };
 use http::uri::Scheme;
-use http::Uri;
-use hyper::client::{HttpConnector, ResponseFuture};
+use http::{Uri, Version};
+use hyper::client::HttpConnector;
 use pin_project_lite::pin_project;
 use std::future::Future;
 use std::pin::Pin;
@@ -21,16 +22,27 @@ use tokio::time::Sleep;
 use log::{debug, trace};
 
 use super::decoder::Accepts;
+#[cfg(feature = "http3")]
+use super::h3_client::{H3Client, H3Config};
 use super::request::{Request, RequestBuilder};
 use super::response::Response;
 use super::Body;
-use crate::browser::{configure_chrome, ChromeVersion};
-use crate::connect::Connector;
+use crate::alt_svc;
+use crate::browser::alps::AlpsConfig;
+#[cfg(feature = "http3")]
+use crate::browser::profile::{Http3Settings, TlsSettings};
+use crate::browser::{
+    ChromeVersion, FirefoxVersion, ImpersonateProfile, Priority, PriorityFrame, PseudoOrder,
+    SafariVersion, SettingId,
+};
+use crate::connect::{Connector, EchConfigs, HandshakeSettings, SessionCache, TlsOverrides};
 use crate::cookie;
 use crate::dns::{gai::GaiResolver, DnsResolverWithOverrides, DynResolver, Resolve};
-use crate::error;
+use crate::error::{self, BoxError};
 use crate::into_url::{expect_uri, try_uri};
+use crate::middleware::{BoxFuture, Middleware, Next};
 use crate::redirect::{self, remove_sensitive_headers};
+use crate::retry;
 use crate::tls::{self, TlsBackend};
 use crate::{IntoUrl, Method, Proxy, StatusCode, Url};
 
@@ -68,8 +80,16 @@ struct Config {
     // NOTE: When adding a new field, update `fmt::Debug for ClientBuilder`
     accepts: Accepts,
     headers: HeaderMap,
+    header_order: Vec<HeaderName>,
     certs_verification: bool,
+    root_certs: Vec<tls::Certificate>,
+    tls_built_in_root_certs: bool,
+    identity: Option<tls::Identity>,
     tls_sni: bool,
+    tls_application_settings: Option<AlpsConfig>,
+    tls_enable_ech_grease: bool,
+    tls_session_cache: bool,
+    tls_early_data: bool,
     connect_timeout: Option<Duration>,
     connection_verbose: bool,
     pool_idle_timeout: Option<Duration>,
@@ -79,6 +99,8 @@ struct Config {
     auto_sys_proxy: bool,
     redirect_policy: redirect::Policy,
     referer: bool,
+    retry_policy: retry::Policy,
+    middleware: Vec<Arc<dyn Middleware>>,
     timeout: Option<Duration>,
     min_tls_version: Option<tls::Version>,
     max_tls_version: Option<tls::Version>,
@@ -95,16 +117,27 @@ struct Config {
     http2_max_header_list_size: Option<u32>,
     http2_enable_push: Option<bool>,
     http2_header_table_size: Option<u32>,
+    http2_headers_pseudo_order: Option<[PseudoOrder; 4]>,
+    http2_settings_order: Vec<SettingId>,
+    http2_extra_settings: Vec<(u16, u32)>,
+    http2_headers_priority: Option<Priority>,
+    http2_priority_frames: Vec<PriorityFrame>,
     http2_keep_alive_interval: Option<Duration>,
     http2_keep_alive_timeout: Option<Duration>,
     http2_keep_alive_while_idle: bool,
+    #[cfg(feature = "http3")]
+    http3_tls: TlsSettings,
+    #[cfg(feature = "http3")]
+    http3: Http3Settings,
     local_address: Option<IpAddr>,
     nodelay: bool,
     #[cfg(feature = "cookies")]
     cookie_store: Option<Arc<dyn cookie::CookieStore>>,
+    alt_svc: Option<Arc<alt_svc::Cache>>,
     error: Option<crate::Error>,
     https_only: bool,
     dns_overrides: HashMap<String, Vec<SocketAddr>>,
+    ech_config_lists: HashMap<String, Vec<u8>>,
     dns_resolver: Option<Arc<dyn Resolve>>,
 }
 
@@ -127,8 +160,16 @@ impl ClientBuilder {
                 error: None,
                 accepts: Accepts::default(),
                 headers,
+                header_order: Vec::new(),
                 certs_verification: true,
+                root_certs: Vec::new(),
+                tls_built_in_root_certs: true,
+                identity: None,
                 tls_sni: true,
+                tls_application_settings: Some(AlpsConfig::default()),
+                tls_enable_ech_grease: false,
+                tls_session_cache: true,
+                tls_early_data: false,
                 connect_timeout: None,
                 connection_verbose: false,
                 pool_idle_timeout: Some(Duration::from_secs(90)),
@@ -140,6 +181,8 @@ impl ClientBuilder {
                 auto_sys_proxy: true,
                 redirect_policy: redirect::Policy::default(),
                 referer: true,
+                retry_policy: retry::Policy::default(),
+                middleware: Vec::new(),
                 timeout: None,
                 min_tls_version: None,
                 max_tls_version: None,
@@ -156,15 +199,26 @@ impl ClientBuilder {
                 http2_max_header_list_size: None,
                 http2_enable_push: None,
                 http2_header_table_size: None,
+                http2_headers_pseudo_order: None,
+                http2_settings_order: Vec::new(),
+                http2_extra_settings: Vec::new(),
+                http2_headers_priority: None,
+                http2_priority_frames: Vec::new(),
                 http2_keep_alive_interval: None,
                 http2_keep_alive_timeout: None,
                 http2_keep_alive_while_idle: false,
+                #[cfg(feature = "http3")]
+                http3_tls: TlsSettings::default(),
+                #[cfg(feature = "http3")]
+                http3: Http3Settings::default(),
                 local_address: None,
                 nodelay: true,
                 #[cfg(feature = "cookies")]
                 cookie_store: None,
+                alt_svc: None,
                 https_only: false,
                 dns_overrides: HashMap::new(),
+                ech_config_lists: HashMap::new(),
                 dns_resolver: None,
             },
         }
@@ -172,8 +226,79 @@ impl ClientBuilder {
 
     /// Sets the necessary values to mimic the specified Chrome version.
     pub fn chrome_builder(self, ver: ChromeVersion) -> ClientBuilder {
-        configure_chrome(ver, self)
+        self.impersonate(ver.profile())
+    }
+
+    /// Sets the necessary values to mimic the specified Firefox version.
+    pub fn firefox_builder(self, ver: FirefoxVersion) -> ClientBuilder {
+        self.impersonate(ver.profile())
+    }
+
+    /// Sets the necessary values to mimic the specified Safari version.
+    pub fn safari_builder(self, ver: SafariVersion) -> ClientBuilder {
+        self.impersonate(ver.profile())
+    }
+
+    /// Sets the TLS, HTTP/2, HTTP/3 and header settings to mimic the given
+    /// profile.
+    ///
+    /// This replaces the default headers and any TLS connector set before.
+    /// See [`ImpersonateProfile`] for how to tweak a shipped profile.
+    pub fn impersonate(mut self, profile: ImpersonateProfile) -> ClientBuilder {
+        if let Err(e) = profile.tls.connector_builder() {
+            self.config.error = Some(e);
+            return self;
+        }
+
+        #[cfg(feature = "gzip")]
+        {
+            self.config.accepts.gzip = profile.gzip;
+        }
+        #[cfg(feature = "brotli")]
+        {
+            self.config.accepts.brotli = profile.brotli;
+        }
+        #[cfg(feature = "deflate")]
+        {
+            self.config.accepts.deflate = profile.deflate;
+        }
+        #[cfg(feature = "http3")]
+        {
+            self.config.http3_tls = profile.tls.clone();
+            self.config.http3 = profile.http3.clone();
+        }
+
+        let headers = profile.ordered_headers();
+        let header_order = headers.keys().cloned().collect::<Vec<_>>();
+        let ImpersonateProfile { tls, http2, .. } = profile;
+        let application_settings = tls
+            .application_settings
+            .as_ref()
+            .map(|alps| alps.to_config(&http2));
+        let enable_ech_grease = tls.enable_ech_grease;
+
+        self.use_boring_tls(Arc::new(move || {
+            tls.connector_builder()
+                .expect("profile TLS settings were validated")
+        }))
+        .tls_application_settings(application_settings)
+        .tls_enable_ech_grease(enable_ech_grease)
+        .http2_initial_stream_window_size(http2.initial_stream_window_size)
+        .http2_initial_connection_window_size(http2.initial_connection_window_size)
+        .http2_max_concurrent_streams(http2.max_concurrent_streams)
+        .http2_max_header_list_size(http2.max_header_list_size)
+        .http2_header_table_size(http2.header_table_size)
+        .http2_max_frame_size(http2.max_frame_size)
+        .http2_enable_push(http2.enable_push)
+        .http2_headers_pseudo_order(http2.headers_pseudo_order)
+        .http2_settings_order(http2.settings_order)
+        .http2_extra_settings(http2.extra_settings)
+        .http2_headers_priority(http2.headers_priority)
+        .http2_priority_frames(http2.priority_frames)
+        .replace_default_headers(headers)
+        .header_order(header_order)
     }
+
     /// Returns a `Client` that uses this `ClientBuilder` configuration.
     ///
     /// # Errors
@@ -193,6 +318,8 @@ impl ClientBuilder {
         }
         let proxies = Arc::new(proxies);
 
+        #[cfg(feature = "http3")]
+        let h3_client;
         let mut connector = {
             fn user_agent(headers: &HeaderMap) -> Option<HeaderValue> {
                 headers.get(USER_AGENT).cloned()
@@ -208,9 +335,42 @@ impl ClientBuilder {
                     config.dns_overrides,
                 ));
             }
-            let http = HttpConnector::new_with_resolver(DynResolver::new(resolver));
+            let http = HttpConnector::new_with_resolver(DynResolver::new(resolver.clone()));
+            let ech = EchConfigs::new(config.ech_config_lists, resolver.clone());
+            let tls_overrides = TlsOverrides {
+                certs_verification: config.certs_verification,
+                root_certs: config.root_certs,
+                built_in_root_certs: config.tls_built_in_root_certs,
+                identity: config.identity,
+                min_tls_version: config.min_tls_version,
+                max_tls_version: config.max_tls_version,
+            };
+
+            let handshake = HandshakeSettings {
+                application_settings: config.tls_application_settings,
+                enable_ech_grease: config.tls_enable_ech_grease,
+                sni: config.tls_sni,
+            };
+
+            #[cfg(feature = "http3")]
+            {
+                h3_client = H3Client::new(H3Config {
+                    tls: config.http3_tls,
+                    settings: config.http3,
+                    tls_overrides: tls_overrides.clone(),
+                    handshake: handshake.for_h3(),
+                    pseudo_order: config.http2_headers_pseudo_order,
+                    resolver,
+                    local_address: config.local_address,
+                    connect_timeout: config.connect_timeout,
+                })
+                .map_err(crate::error::builder)?;
+            }
 
-            match config.tls {
+            let session_cache = config
+                .tls_session_cache
+                .then(|| Arc::new(SessionCache::default()));
+            let mut connector = match config.tls {
                 TlsBackend::BoringTls(tls) => Connector::new_boring_tls(
                     http,
                     tls,
@@ -218,9 +378,14 @@ impl ClientBuilder {
                     user_agent(&config.headers),
                     config.local_address,
                     config.nodelay,
-                    config.certs_verification,
-                ),
-            }
+                    tls_overrides,
+                    handshake,
+                    session_cache,
+                )
+                .map_err(crate::error::builder)?,
+            };
+            connector.set_ech(Arc::new(ech));
+            connector
         };
 
         connector.set_timeout(config.connect_timeout);
@@ -257,6 +422,33 @@ impl ClientBuilder {
         if let Some(max) = config.http2_header_table_size {
             builder.http2_header_table_size(max);
         }
+        if let Some(order) = config.http2_headers_pseudo_order {
+            builder.http2_headers_pseudo_order(Some(order.map(PseudoOrder::to_h2)));
+        }
+        if !config.http2_settings_order.is_empty() {
+            builder.http2_settings_order(
+                config
+                    .http2_settings_order
+                    .iter()
+                    .map(|id| id.to_h2())
+                    .collect(),
+            );
+        }
+        if !config.http2_extra_settings.is_empty() {
+            builder.http2_unknown_settings(config.http2_extra_settings.clone());
+        }
+        if let Some(priority) = config.http2_headers_priority {
+            builder.http2_headers_priority(Some(priority.to_h2()));
+        }
+        if !config.http2_priority_frames.is_empty() {
+            builder.http2_priority(Some(
+                config
+                    .http2_priority_frames
+                    .iter()
+                    .map(|frame| frame.to_h2())
+                    .collect(),
+            ));
+        }
         if let Some(http2_keep_alive_interval) = config.http2_keep_alive_interval {
             builder.http2_keep_alive_interval(http2_keep_alive_interval);
         }
@@ -283,6 +475,15 @@ impl ClientBuilder {
             builder.http1_allow_obsolete_multiline_headers_in_responses(true);
         }
 
+        // Requests which may be replayed get their own pool, whose
+        // connections send them as TLS early data.
+        let hyper_early = if config.tls_session_cache && config.tls_early_data {
+            let mut early_connector = connector.clone();
+            early_connector.set_early_data(true);
+            Some(builder.build(early_connector))
+        } else {
+            None
+        };
         let hyper_client = builder.build(connector);
 
         let proxies_maybe_http_auth = proxies.iter().any(|p| p.maybe_has_http_auth());
@@ -292,10 +493,17 @@ impl ClientBuilder {
                 accepts: config.accepts,
                 #[cfg(feature = "cookies")]
                 cookie_store: config.cookie_store,
+                alt_svc: config.alt_svc,
                 hyper: hyper_client,
+                hyper_early,
+                #[cfg(feature = "http3")]
+                h3: h3_client,
                 headers: config.headers,
+                header_order: config.header_order,
                 redirect_policy: config.redirect_policy,
                 referer: config.referer,
+                retry_policy: config.retry_policy,
+                middleware: config.middleware,
                 request_timeout: config.timeout,
                 proxies,
                 proxies_maybe_http_auth,
@@ -392,6 +600,38 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets the order headers are sent in.
+    ///
+    /// Every request's headers, both the default ones and those set on the
+    /// request, are rearranged so the listed names come first, in this
+    /// order. Headers not listed keep their relative order and are sent
+    /// after the listed ones. An empty list, the default, leaves the order
+    /// untouched.
+    ///
+    /// Header order is part of a browser's fingerprint, so
+    /// [`ClientBuilder::impersonate`] sets it from the profile. It can be
+    /// overridden for a single request with
+    /// [`RequestBuilder::header_order`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use reqwest_impersonate::header::{ACCEPT, USER_AGENT};
+    /// # fn doc() -> Result<(), reqwest_impersonate::Error> {
+    /// let client = reqwest_impersonate::Client::builder()
+    ///     .header_order([USER_AGENT, ACCEPT])
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn header_order<I>(mut self, order: I) -> ClientBuilder
+    where
+        I: IntoIterator<Item = HeaderName>,
+    {
+        self.config.header_order = order.into_iter().collect();
+        self
+    }
+
     pub(crate) fn replace_default_headers(mut self, headers: HeaderMap) -> ClientBuilder {
         self.config.headers = headers;
         self
@@ -438,6 +678,40 @@ impl ClientBuilder {
         self
     }
 
+    /// Enable an Alt-Svc cache for the client.
+    ///
+    /// Alternative services advertised in `Alt-Svc` response headers are
+    /// remembered for their max-age, and later requests to the same origin
+    /// are sent over HTTP/3 to an advertised `h3` alternative, as browsers
+    /// do. Should the alternative fail before a response arrives, the
+    /// request is sent again over TCP and the alternative is skipped for a
+    /// while.
+    ///
+    /// Requests are not upgraded without the `http3` feature enabled, nor
+    /// when a version other than HTTP/1.1 was set on them or their body
+    /// cannot be sent twice.
+    ///
+    /// By default, no Alt-Svc cache is used.
+    pub fn alt_svc(mut self, enable: bool) -> ClientBuilder {
+        if enable {
+            self.alt_svc_cache(Arc::new(alt_svc::Cache::default()))
+        } else {
+            self.config.alt_svc = None;
+            self
+        }
+    }
+
+    /// Set the Alt-Svc cache for the client.
+    ///
+    /// This allows sharing a cache between clients, or persisting it, with
+    /// the `json` feature, alongside a cookie store.
+    ///
+    /// By default, no Alt-Svc cache is used.
+    pub fn alt_svc_cache(mut self, cache: Arc<alt_svc::Cache>) -> ClientBuilder {
+        self.config.alt_svc = Some(cache);
+        self
+    }
+
     /// Enable auto gzip decompression by checking the `Content-Encoding` response header.
     ///
     /// If auto gzip decompression is turned on:
@@ -576,6 +850,47 @@ impl ClientBuilder {
         self
     }
 
+    // Retry options
+
+    /// Set a `retry::Policy` for this client.
+    ///
+    /// Default will retry a request at most twice when an HTTP/2 server
+    /// gracefully closes the connection before processing it.
+    pub fn retry(mut self, policy: retry::Policy) -> ClientBuilder {
+        self.config.retry_policy = policy;
+        self
+    }
+
+    // Middleware options
+
+    /// Add a `Middleware` wrapping every request sent by the `Client`.
+    ///
+    /// Middleware runs in the order it is added, around each redirected hop
+    /// and retry of a request, after default headers and cookies were added.
+    /// See the [`middleware`](crate::middleware) module for details.
+    pub fn middleware<M: Middleware>(mut self, middleware: M) -> ClientBuilder {
+        self.config.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Enable the HTTP cache, keeping responses in `store`.
+    ///
+    /// The cache is added to the middleware: middleware added before it also
+    /// sees the requests answered from the cache, middleware added after it
+    /// only the requests sent to servers. See the [`cache`](crate::cache)
+    /// module for details.
+    ///
+    /// By default, no cache is used.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `cache` feature to be enabled.
+    #[cfg(feature = "cache")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cache")))]
+    pub fn cache<S: crate::cache::CacheStore>(self, store: S) -> ClientBuilder {
+        self.middleware(crate::cache::Cache::new(store))
+    }
+
     // Proxy options
 
     /// Add a `Proxy` to the list of proxies the `Client` will use.
@@ -767,6 +1082,64 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets the order of the request pseudo-headers sent in HTTP2 `HEADERS` frames.
+    ///
+    /// Passing `None` keeps the default order of `:method`, `:scheme`,
+    /// `:authority` and `:path`.
+    pub fn http2_headers_pseudo_order(
+        mut self,
+        order: impl Into<Option<[PseudoOrder; 4]>>,
+    ) -> ClientBuilder {
+        self.config.http2_headers_pseudo_order = order.into();
+        self
+    }
+
+    /// Sets the order of the parameters in the initial HTTP2 `SETTINGS` frame.
+    ///
+    /// Parameters left out of the list are sent after the listed ones.
+    pub fn http2_settings_order<I>(mut self, order: I) -> ClientBuilder
+    where
+        I: IntoIterator<Item = SettingId>,
+    {
+        self.config.http2_settings_order = order.into_iter().collect();
+        self
+    }
+
+    /// Adds non-standard parameters, identifier and value, sent at the end of
+    /// the initial HTTP2 `SETTINGS` frame.
+    pub fn http2_extra_settings<I>(mut self, settings: I) -> ClientBuilder
+    where
+        I: IntoIterator<Item = (u16, u32)>,
+    {
+        self.config.http2_extra_settings = settings.into_iter().collect();
+        self
+    }
+
+    /// Sets the priority attached to the HTTP2 `HEADERS` frame of every request.
+    ///
+    /// Passing `None` sends `HEADERS` frames without priority information.
+    pub fn http2_headers_priority(mut self, priority: impl Into<Option<Priority>>) -> ClientBuilder {
+        let priority = priority.into();
+        if !priority.map_or(true, |p| p.is_valid()) {
+            self.config.error = Some(crate::error::builder("invalid HTTP2 stream priority"));
+        }
+        self.config.http2_headers_priority = priority;
+        self
+    }
+
+    /// Sets the HTTP2 `PRIORITY` frames sent when a connection is opened.
+    pub fn http2_priority_frames<I>(mut self, frames: I) -> ClientBuilder
+    where
+        I: IntoIterator<Item = PriorityFrame>,
+    {
+        let frames = frames.into_iter().collect::<Vec<_>>();
+        if !frames.iter().all(PriorityFrame::is_valid) {
+            self.config.error = Some(crate::error::builder("invalid HTTP2 stream priority"));
+        }
+        self.config.http2_priority_frames = frames;
+        self
+    }
+
     /// Sets an interval for HTTP2 Ping frames should be sent to keep a connection alive.
     ///
     /// Pass `None` to disable HTTP2 keep-alive.
@@ -840,6 +1213,44 @@ impl ClientBuilder {
         self
     }
 
+    /// Add a custom root certificate.
+    ///
+    /// This can be used to connect to a server that has a self-signed
+    /// certificate for example. The certificate is trusted on top of
+    /// whatever the TLS builder func or impersonation profile sets up.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `boring-tls(-...)` feature to be enabled.
+    pub fn add_root_certificate(mut self, cert: tls::Certificate) -> ClientBuilder {
+        self.config.root_certs.push(cert);
+        self
+    }
+
+    /// Controls the use of built-in/preloaded certificates during certificate validation.
+    ///
+    /// Defaults to `true` -- built-in system certs will be used. When
+    /// `false`, only the certificates added with
+    /// [`ClientBuilder::add_root_certificate`] are trusted.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `boring-tls(-...)` feature to be enabled.
+    pub fn tls_built_in_root_certs(mut self, tls_built_in_root_certs: bool) -> ClientBuilder {
+        self.config.tls_built_in_root_certs = tls_built_in_root_certs;
+        self
+    }
+
+    /// Sets the identity to be used for client certificate authentication.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `boring-tls(-...)` feature to be enabled.
+    pub fn identity(mut self, identity: tls::Identity) -> ClientBuilder {
+        self.config.identity = Some(identity);
+        self
+    }
+
     /// Controls the use of certificate validation.
     ///
     /// Defaults to `false`.
@@ -867,8 +1278,7 @@ impl ClientBuilder {
     ///
     /// # Optional
     ///
-    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls(-...)`
-    /// feature to be enabled.
+    /// This requires the optional `boring-tls(-...)` feature to be enabled.
     pub fn tls_sni(mut self, tls_sni: bool) -> ClientBuilder {
         self.config.tls_sni = tls_sni;
         self
@@ -876,19 +1286,12 @@ impl ClientBuilder {
 
     /// Set the minimum required TLS version for connections.
     ///
-    /// By default the TLS backend's own default is used.
-    ///
-    /// # Errors
-    ///
-    /// A value of `tls::Version::TLS_1_3` will cause an error with the
-    /// `native-tls`/`default-tls` backend. This does not mean the version
-    /// isn't supported, just that it can't be set as a minimum due to
-    /// technical limitations.
+    /// By default the version of the TLS builder func is used, e.g. the one
+    /// of the impersonated browser. Setting it overrides that version.
     ///
     /// # Optional
     ///
-    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls(-...)`
-    /// feature to be enabled.
+    /// This requires the optional `boring-tls(-...)` feature to be enabled.
     pub fn min_tls_version(mut self, version: tls::Version) -> ClientBuilder {
         self.config.min_tls_version = Some(version);
         self
@@ -896,19 +1299,12 @@ impl ClientBuilder {
 
     /// Set the maximum allowed TLS version for connections.
     ///
-    /// By default there's no maximum.
-    ///
-    /// # Errors
-    ///
-    /// A value of `tls::Version::TLS_1_3` will cause an error with the
-    /// `native-tls`/`default-tls` backend. This does not mean the version
-    /// isn't supported, just that it can't be set as a maximum due to
-    /// technical limitations.
+    /// By default the version of the TLS builder func is used, e.g. the one
+    /// of the impersonated browser. Setting it overrides that version.
     ///
     /// # Optional
     ///
-    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls(-...)`
-    /// feature to be enabled.
+    /// This requires the optional `boring-tls(-...)` feature to be enabled.
     pub fn max_tls_version(mut self, version: tls::Version) -> ClientBuilder {
         self.config.max_tls_version = Some(version);
         self
@@ -919,6 +1315,9 @@ impl ClientBuilder {
     /// Since multiple TLS backends can be optionally enabled, this option will
     /// force the `boring` backend to be used for this `Client`.
     ///
+    /// `builder_func` is called once, when the `Client` is built, and every
+    /// connection of the `Client` shares the connector it returns.
+    ///
     /// # Optional
     ///
     /// This requires the optional `boring-tls(-...)` feature to be enabled.
@@ -930,6 +1329,58 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets the TLS ALPS (application settings) extension sent, if any.
+    ///
+    /// Chrome sends it, Firefox does not.
+    pub(crate) fn tls_application_settings(mut self, alps: Option<AlpsConfig>) -> ClientBuilder {
+        self.config.tls_application_settings = alps;
+        self
+    }
+
+    /// Controls whether a GREASE Encrypted Client Hello extension is sent.
+    ///
+    /// Chrome has done so on every handshake since version 117.
+    pub(crate) fn tls_enable_ech_grease(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_enable_ech_grease = enable;
+        self
+    }
+
+    /// Controls whether TLS sessions are cached and resumed when connecting
+    /// to a host again.
+    ///
+    /// Browsers resume sessions, so a client which never does stands out.
+    /// Each `Client` has its own cache, and sessions established through a
+    /// proxy are only resumed through the same proxy.
+    ///
+    /// Defaults to `true`.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `boring-tls(-...)` feature to be enabled.
+    pub fn tls_session_cache(mut self, enabled: bool) -> ClientBuilder {
+        self.config.tls_session_cache = enabled;
+        self
+    }
+
+    /// Controls whether idempotent requests are sent as TLS 1.3 early data
+    /// (0-RTT) on resumed connections.
+    ///
+    /// Early data can be replayed by an attacker, so only requests with an
+    /// idempotent method are sent that way, on connections of their own. If
+    /// the server rejects the early data, the request is sent again once the
+    /// handshake completes. This has no effect if the session cache is
+    /// disabled.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `boring-tls(-...)` feature to be enabled.
+    pub fn tls_early_data(mut self, enabled: bool) -> ClientBuilder {
+        self.config.tls_early_data = enabled;
+        self
+    }
+
     /// Restrict the Client to be used with HTTPS only requests.
     ///
     /// Defaults to false.
@@ -965,6 +1416,24 @@ impl ClientBuilder {
         self
     }
 
+    /// Use Encrypted Client Hello with `domain`, with the given
+    /// `ECHConfigList`.
+    ///
+    /// This takes precedence over the configuration the DNS resolver finds,
+    /// see [`Resolve::resolve_ech`]. If the server rejects the configuration,
+    /// the connection is made again with the retry configurations it sends,
+    /// or without ECH if it sends none.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `boring-tls(-...)` feature to be enabled.
+    pub fn ech_config_list(mut self, domain: &str, ech_config_list: &[u8]) -> ClientBuilder {
+        self.config
+            .ech_config_lists
+            .insert(domain.to_string(), ech_config_list.to_vec());
+        self
+    }
+
     /// Override the DNS resolver implementation.
     ///
     /// Pass an `Arc` wrapping a trait object implementing `Resolve`.
@@ -1092,7 +1561,7 @@ impl Client {
     }
 
     pub(super) fn execute_request(&self, req: Request) -> Pending {
-        let (method, url, mut headers, body, timeout, version) = req.pieces();
+        let (method, url, mut headers, body, timeout, version, header_order, retry) = req.pieces();
         if url.scheme() != "http" && url.scheme() != "https" {
             return Pending::new_err(error::url_bad_scheme(url));
         }
@@ -1140,11 +1609,18 @@ impl Client {
 
         self.proxy_auth(&uri, &mut headers);
 
+        let header_order = if header_order.is_empty() {
+            self.inner.header_order.clone()
+        } else {
+            header_order
+        };
+        crate::util::sort_headers(&mut headers, &header_order);
+
         let mut req = hyper::Request::builder()
             .method(method.clone())
             .uri(uri)
             .version(version)
-            .body(body.into_stream())
+            .body(body)
             .expect("valid request parts");
 
         let timeout = timeout
@@ -1154,23 +1630,31 @@ impl Client {
 
         *req.headers_mut() = headers.clone();
 
-        let in_flight = self.inner.hyper.request(req);
+        let in_flight = self.inner.send(req, !matches!(reusable, Some(None)));
+
+        let retry = retry.unwrap_or_else(|| self.inner.retry_policy.clone());
+        retry.on_request();
 
         Pending {
             inner: PendingInner::Request(PendingRequest {
                 method,
+                version,
                 url,
                 headers,
+                header_order,
                 body: reusable,
 
                 urls: Vec::new(),
 
+                retry,
                 retry_count: 0,
+                attempts: 1,
 
                 client: self.inner.clone(),
 
                 in_flight,
                 timeout,
+                retry_delay: None,
             }),
         }
     }
@@ -1259,6 +1743,10 @@ impl Config {
             }
         }
 
+        if self.alt_svc.is_some() {
+            f.field("alt_svc", &true);
+        }
+
         f.field("accepts", &self.accepts);
 
         if !self.proxies.is_empty() {
@@ -1273,8 +1761,20 @@ impl Config {
             f.field("referer", &true);
         }
 
+        if !self.retry_policy.is_default() {
+            f.field("retry_policy", &self.retry_policy);
+        }
+
+        if !self.middleware.is_empty() {
+            f.field("middleware", &self.middleware.len());
+        }
+
         f.field("default_headers", &self.headers);
 
+        if !self.header_order.is_empty() {
+            f.field("header_order", &self.header_order);
+        }
+
         if self.http1_title_case_headers {
             f.field("http1_title_case_headers", &true);
         }
@@ -1312,6 +1812,18 @@ impl Config {
                 f.field("danger_accept_invalid_certs", &true);
             }
 
+            if !self.root_certs.is_empty() {
+                f.field("root_certs", &self.root_certs);
+            }
+
+            if !self.tls_built_in_root_certs {
+                f.field("tls_built_in_root_certs", &false);
+            }
+
+            if self.identity.is_some() {
+                f.field("identity", &true);
+            }
+
             if let Some(ref min_tls_version) = self.min_tls_version {
                 f.field("min_tls_version", min_tls_version);
             }
@@ -1321,22 +1833,56 @@ impl Config {
             }
 
             f.field("tls_sni", &self.tls_sni);
+
+            match self.tls_application_settings {
+                Some(ref alps) if *alps != AlpsConfig::default() => {
+                    f.field("tls_application_settings", alps);
+                }
+                Some(_) => {}
+                None => {
+                    f.field("tls_application_settings", &false);
+                }
+            }
+
+            if self.tls_enable_ech_grease {
+                f.field("tls_enable_ech_grease", &true);
+            }
+
+            if !self.tls_session_cache {
+                f.field("tls_session_cache", &false);
+            }
+
+            if self.tls_early_data {
+                f.field("tls_early_data", &true);
+            }
         }
 
         if !self.dns_overrides.is_empty() {
             f.field("dns_overrides", &self.dns_overrides);
         }
+
+        if !self.ech_config_lists.is_empty() {
+            let domains = self.ech_config_lists.keys().collect::<Vec<_>>();
+            f.field("ech_config_lists", &domains);
+        }
     }
 }
 
-struct ClientRef {
+pub(crate) struct ClientRef {
     accepts: Accepts,
     #[cfg(feature = "cookies")]
     cookie_store: Option<Arc<dyn cookie::CookieStore>>,
+    alt_svc: Option<Arc<alt_svc::Cache>>,
     headers: HeaderMap,
+    header_order: Vec<HeaderName>,
     hyper: HyperClient,
+    hyper_early: Option<HyperClient>,
+    #[cfg(feature = "http3")]
+    h3: H3Client,
     redirect_policy: redirect::Policy,
     referer: bool,
+    retry_policy: retry::Policy,
+    pub(crate) middleware: Vec<Arc<dyn Middleware>>,
     request_timeout: Option<Duration>,
     proxies: Arc<Vec<Proxy>>,
     proxies_maybe_http_auth: bool,
@@ -1344,6 +1890,115 @@ struct ClientRef {
 }
 
 impl ClientRef {
+    /// The client sending requests with `method`.
+    fn hyper_for(&self, method: &Method) -> &HyperClient {
+        match self.hyper_early {
+            Some(ref hyper_early) if method.is_idempotent() => hyper_early,
+            _ => &self.hyper,
+        }
+    }
+
+    /// Sends `req` over HTTP/3 if that is the version it asks for, or
+    /// through hyper otherwise.
+    ///
+    /// With `alt_svc`, a request left at the default version goes to a
+    /// cached HTTP/3 alternative of its origin instead, if there is one.
+    ///
+    /// HTTP/3 can't be sent through a proxy, so a request a proxy
+    /// intercepts fails if it asks for HTTP/3 and never goes to an
+    /// alternative service.
+    #[cfg_attr(not(feature = "http3"), allow(unused_variables))]
+    fn request(
+        &self,
+        req: hyper::Request<super::body::ImplStream>,
+        alt_svc: bool,
+    ) -> ResponseFuture {
+        #[cfg(feature = "http3")]
+        {
+            let proxied = self.is_proxied(req.uri());
+            if req.version() == Version::HTTP_3 {
+                if proxied {
+                    return ResponseFuture::H3(Box::pin(futures_util::future::err(
+                        "HTTP/3 requests cannot be sent through a proxy".into(),
+                    )));
+                }
+                return ResponseFuture::H3(self.h3.request(req, None));
+            }
+            if alt_svc && !proxied && req.version() == Version::HTTP_11 {
+                let route = self
+                    .alt_svc
+                    .as_ref()
+                    .and_then(|cache| cache.route(req.uri()));
+                if let Some(route) = route {
+                    trace!("sending to alternative service {:?}", route);
+                    return ResponseFuture::AltSvc(
+                        self.h3.request(req, Some(route.clone())),
+                        route,
+                    );
+                }
+            }
+        }
+        ResponseFuture::Hyper(self.hyper_for(req.method()).request(req))
+    }
+
+    /// Whether one of the configured proxies intercepts `uri`.
+    #[cfg(feature = "http3")]
+    fn is_proxied(&self, uri: &Uri) -> bool {
+        self.proxies.iter().any(|p| p.intercept(uri).is_some())
+    }
+
+    /// Sends `req` through the middleware, if any, or directly otherwise.
+    fn send(self: &Arc<Self>, req: hyper::Request<Body>, alt_svc: bool) -> ResponseFuture {
+        if self.middleware.is_empty() {
+            self.request(req.map(Body::into_stream), alt_svc)
+        } else {
+            ResponseFuture::Middleware(Next::new(self.clone(), alt_svc).run(req))
+        }
+    }
+
+    /// Sends `req` once it went through all middleware.
+    ///
+    /// Unlike a `PendingRequest`, middleware cannot see which alternative
+    /// service a request went to, so falling back to TCP happens here.
+    pub(crate) fn send_direct(
+        self: Arc<Self>,
+        req: hyper::Request<Body>,
+        alt_svc: bool,
+    ) -> BoxFuture<'static, crate::Result<hyper::Response<Body>>> {
+        #[cfg(feature = "http3")]
+        let fallback = if alt_svc {
+            try_clone_request(&req)
+        } else {
+            None
+        };
+        let in_flight = self.request(req.map(Body::into_stream), alt_svc);
+
+        Box::pin(async move {
+            #[cfg(feature = "http3")]
+            let route = match in_flight {
+                ResponseFuture::AltSvc(_, ref route) => Some(route.clone()),
+                _ => None,
+            };
+
+            match in_flight.await {
+                Ok(res) => Ok(res),
+                #[cfg(feature = "http3")]
+                Err(err) if route.is_some() && fallback.is_some() => {
+                    let route = route.expect("checked above");
+                    debug!("alternative service {:?} failed: {}", route, err);
+                    if let Some(ref cache) = self.alt_svc {
+                        cache.mark_broken(route);
+                    }
+                    let req = fallback.expect("checked above");
+                    self.request(req.map(Body::into_stream), false)
+                        .await
+                        .map_err(crate::error::request)
+                }
+                Err(err) => Err(crate::error::request(err)),
+            }
+        })
+    }
+
     fn fmt_fields(&self, f: &mut fmt::DebugStruct<'_, '_>) {
         // Instead of deriving Debug, only print fields when their output
         // would provide relevant or interesting data.
@@ -1355,6 +2010,10 @@ impl ClientRef {
             }
         }
 
+        if self.alt_svc.is_some() {
+            f.field("alt_svc", &true);
+        }
+
         f.field("accepts", &self.accepts);
 
         if !self.proxies.is_empty() {
@@ -1369,8 +2028,20 @@ impl ClientRef {
             f.field("referer", &true);
         }
 
+        if !self.retry_policy.is_default() {
+            f.field("retry_policy", &self.retry_policy);
+        }
+
+        if !self.middleware.is_empty() {
+            f.field("middleware", &self.middleware.len());
+        }
+
         f.field("default_headers", &self.headers);
 
+        if !self.header_order.is_empty() {
+            f.field("header_order", &self.header_order);
+        }
+
         if let Some(ref d) = self.request_timeout {
             f.field("timeout", d);
         }
@@ -1392,13 +2063,17 @@ enum PendingInner {
 pin_project! {
     struct PendingRequest {
         method: Method,
+        version: Version,
         url: Url,
         headers: HeaderMap,
+        header_order: Vec<HeaderName>,
         body: Option<Option<Bytes>>,
 
         urls: Vec<Url>,
 
+        retry: retry::Policy,
         retry_count: usize,
+        attempts: usize,
 
         client: Arc<ClientRef>,
 
@@ -1406,6 +2081,8 @@ pin_project! {
         in_flight: ResponseFuture,
         #[pin]
         timeout: Option<Pin<Box<Sleep>>>,
+        #[pin]
+        retry_delay: Option<Pin<Box<Sleep>>>,
     }
 }
 
@@ -1418,6 +2095,10 @@ impl PendingRequest {
         self.project().timeout
     }
 
+    fn retry_delay(self: Pin<&mut Self>) -> Pin<&mut Option<Pin<Box<Sleep>>>> {
+        self.project().retry_delay
+    }
+
     fn urls(self: Pin<&mut Self>) -> &mut Vec<Url> {
         self.project().urls
     }
@@ -1426,53 +2107,148 @@ impl PendingRequest {
         self.project().headers
     }
 
-    fn retry_error(mut self: Pin<&mut Self>, err: &(dyn std::error::Error + 'static)) -> bool {
-        if !is_retryable_error(err) {
+    fn retry_error(self: Pin<&mut Self>, err: &(dyn std::error::Error + 'static)) -> bool {
+        if matches!(self.body, Some(None)) {
+            trace!("body not reusable, not retrying {:?}", err);
             return false;
         }
 
+        let delay = match self.retry.check_error(&self.method, self.retry_count, err) {
+            Some(delay) => delay,
+            None => return false,
+        };
         trace!("can retry {:?}", err);
 
-        let body = match self.body {
-            Some(Some(ref body)) => Body::reusable(body.clone()),
-            Some(None) => {
-                debug!("error was retryable, but body not reusable");
-                return false;
-            }
-            None => Body::empty(),
-        };
+        self.schedule_retry(delay);
+        true
+    }
 
-        if self.retry_count >= 2 {
-            trace!("retry count too high");
+    fn retry_status(self: Pin<&mut Self>, res: &hyper::Response<Body>) -> bool {
+        if matches!(self.body, Some(None)) {
             return false;
         }
+
+        let delay = match self.retry.check_status(
+            &self.method,
+            self.retry_count,
+            res.status(),
+            res.headers(),
+        ) {
+            Some(delay) => delay,
+            None => return false,
+        };
+        debug!("retrying '{}' after status {}", self.url, res.status());
+
+        self.schedule_retry(delay);
+        true
+    }
+
+    /// Sends the request again once `delay` has elapsed.
+    fn schedule_retry(mut self: Pin<&mut Self>, delay: Duration) {
         self.retry_count += 1;
+        self.attempts += 1;
+
+        if delay.is_zero() {
+            self.resend();
+        } else {
+            trace!("retry in {:?}", delay);
+            self.as_mut()
+                .retry_delay()
+                .set(Some(Box::pin(tokio::time::sleep(delay))));
+        }
+    }
+
+    fn resend(mut self: Pin<&mut Self>) {
+        let req = self
+            .rebuild_request()
+            .expect("only requests with a reusable body are retried");
+        *self.as_mut().in_flight().get_mut() = self.client.send(req, true);
+    }
+
+    /// Sends the request again over TCP if it went to an alternative service
+    /// which failed, skipping that alternative for a while.
+    #[cfg(feature = "http3")]
+    fn alt_svc_fallback(mut self: Pin<&mut Self>, err: &(dyn std::error::Error + 'static)) -> bool {
+        let route = match *self.as_mut().in_flight() {
+            ResponseFuture::AltSvc(_, ref route) => route.clone(),
+            _ => return false,
+        };
+
+        debug!("alternative service {:?} failed: {}", route, err);
+        if let Some(ref cache) = self.client.alt_svc {
+            cache.mark_broken(route);
+        }
+
+        let req = match self.rebuild_request() {
+            Some(req) => req,
+            None => return false,
+        };
+        *self.as_mut().in_flight().get_mut() = self.client.send(req, false);
+
+        true
+    }
+
+    /// Builds the current request again, unless its body was a stream
+    /// which cannot be sent twice.
+    fn rebuild_request(&self) -> Option<hyper::Request<Body>> {
+        let body = match self.body {
+            Some(Some(ref body)) => Body::reusable(body.clone()),
+            Some(None) => return None,
+            None => Body::empty(),
+        };
 
         let uri = expect_uri(&self.url);
         let mut req = hyper::Request::builder()
             .method(self.method.clone())
             .uri(uri)
-            .body(body.into_stream())
+            .version(self.version)
+            .body(body)
             .expect("valid request parts");
 
         *req.headers_mut() = self.headers.clone();
 
-        *self.as_mut().in_flight().get_mut() = self.client.hyper.request(req);
-
-        true
+        Some(req)
     }
 }
 
-fn is_retryable_error(err: &(dyn std::error::Error + 'static)) -> bool {
-    if let Some(cause) = err.source() {
-        if let Some(err) = cause.downcast_ref::<h2::Error>() {
-            // They sent us a graceful shutdown, try with a new connection!
-            return err.is_go_away()
-                && err.is_remote()
-                && err.reason() == Some(h2::Reason::NO_ERROR);
-        }
+/// The response to a request sent over hyper or, for `Version::HTTP_3`
+/// and alternative services, over QUIC.
+enum ResponseFuture {
+    Hyper(hyper::client::ResponseFuture),
+    #[cfg(feature = "http3")]
+    H3(super::h3_client::ResponseFuture),
+    #[cfg(feature = "http3")]
+    AltSvc(super::h3_client::ResponseFuture, alt_svc::Route),
+    Middleware(BoxFuture<'static, crate::Result<hyper::Response<Body>>>),
+}
+
+impl Future for ResponseFuture {
+    type Output = Result<hyper::Response<Body>, BoxError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let res = match self.get_mut() {
+            ResponseFuture::Hyper(fut) => futures_core::ready!(Pin::new(fut).poll(cx))?,
+            #[cfg(feature = "http3")]
+            ResponseFuture::H3(fut) | ResponseFuture::AltSvc(fut, _) => {
+                futures_core::ready!(fut.as_mut().poll(cx))?
+            }
+            ResponseFuture::Middleware(fut) => return fut.as_mut().poll(cx).map_err(Into::into),
+        };
+        Poll::Ready(Ok(res.map(Body::from)))
     }
-    false
+}
+
+/// Copies `req`, unless its body is a stream.
+#[cfg(feature = "http3")]
+fn try_clone_request(req: &hyper::Request<Body>) -> Option<hyper::Request<Body>> {
+    let mut clone = hyper::Request::builder()
+        .method(req.method().clone())
+        .uri(req.uri().clone())
+        .version(req.version())
+        .body(req.body().try_clone()?)
+        .expect("valid request parts");
+    *clone.headers_mut() = req.headers().clone();
+    Some(clone)
 }
 
 impl Pending {
@@ -1514,12 +2290,32 @@ impl Future for PendingRequest {
         }
 
         loop {
+            if let Some(delay) = self.as_mut().retry_delay().as_pin_mut() {
+                match delay.poll(cx) {
+                    Poll::Ready(()) => {}
+                    Poll::Pending => return Poll::Pending,
+                }
+                self.as_mut().retry_delay().set(None);
+                self.as_mut().resend();
+            }
+
             let res = match self.as_mut().in_flight().as_mut().poll(cx) {
                 Poll::Ready(Err(e)) => {
-                    if self.as_mut().retry_error(&e) {
+                    #[cfg(feature = "http3")]
+                    {
+                        if self.as_mut().alt_svc_fallback(&*e) {
+                            continue;
+                        }
+                    }
+                    if self.as_mut().retry_error(&*e) {
                         continue;
                     }
-                    return Poll::Ready(Err(crate::error::request(e).with_url(self.url.clone())));
+                    // Middleware already reports its errors as `crate::Error`.
+                    let err = match e.downcast::<crate::Error>() {
+                        Ok(err) => *err,
+                        Err(e) => crate::error::request(e),
+                    };
+                    return Poll::Ready(Err(err.with_url(self.url.clone())));
                 }
                 Poll::Ready(Ok(res)) => res,
                 Poll::Pending => return Poll::Pending,
@@ -1535,6 +2331,15 @@ impl Future for PendingRequest {
                     }
                 }
             }
+            if let Some(ref cache) = self.client.alt_svc {
+                for alt_svc in res.headers().get_all(ALT_SVC) {
+                    cache.set_alt_svc(alt_svc, &self.url);
+                }
+            }
+            if self.as_mut().retry_status(&res) {
+                continue;
+            }
+
             let should_redirect = match res.status() {
                 StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
                     self.body = None;
@@ -1613,6 +2418,8 @@ impl Future for PendingRequest {
                             }
 
                             self.url = loc;
+                            self.retry_count = 0;
+                            self.attempts += 1;
                             let mut headers =
                                 std::mem::replace(self.as_mut().headers(), HeaderMap::new());
 
@@ -1625,7 +2432,8 @@ impl Future for PendingRequest {
                             let mut req = hyper::Request::builder()
                                 .method(self.method.clone())
                                 .uri(uri.clone())
-                                .body(body.into_stream())
+                                .version(self.version)
+                                .body(body)
                                 .expect("valid request parts");
 
                             // Add cookies from the cookie store.
@@ -1636,9 +2444,13 @@ impl Future for PendingRequest {
                                 }
                             }
 
+                            // Removing and adding headers above perturbs their order.
+                            crate::util::sort_headers(&mut headers, &self.header_order);
+
                             *req.headers_mut() = headers.clone();
                             std::mem::swap(self.as_mut().headers(), &mut headers);
-                            *self.as_mut().in_flight().get_mut() = self.client.hyper.request(req);
+                            let alt_svc = !matches!(self.body, Some(None));
+                            *self.as_mut().in_flight().get_mut() = self.client.send(req, alt_svc);
                             continue;
                         }
                         redirect::ActionKind::Stop => {
@@ -1656,6 +2468,7 @@ impl Future for PendingRequest {
                 self.url.clone(),
                 self.client.accepts,
                 self.timeout.take(),
+                self.attempts,
             );
             return Poll::Ready(Ok(res));
         }
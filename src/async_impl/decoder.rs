@@ -90,6 +90,11 @@ impl fmt::Debug for Decoder {
 }
 
 impl Decoder {
+    #[cfg(feature = "blocking")]
+    pub(crate) fn empty() -> Decoder {
+        Decoder::plain_text(Body::empty())
+    }
+
     /// A plain text decoder.
     ///
     /// This decoder will emit the underlying chunks as-is.
@@ -0,0 +1,487 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::{Buf, Bytes};
+use futures_util::future::poll_fn;
+use http::{StatusCode, Version};
+use log::{debug, trace};
+use quiche::h3::{self, Header, NameValue};
+use tokio::io::ReadBuf;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Instant, Sleep};
+
+use crate::error::{BoxError, TimedOut};
+
+type Reply = oneshot::Sender<Result<hyper::Response<hyper::Body>, BoxError>>;
+
+const H3_NO_ERROR: u64 = 0x100;
+const H3_REQUEST_CANCELLED: u64 = 0x10c;
+
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+/// A request for the connection task.
+pub(super) struct Message {
+    pub(super) headers: Vec<Header>,
+    pub(super) body: Bytes,
+    pub(super) reply: Reply,
+}
+
+/// Sends requests to a connection task; the connection is closed once every
+/// handle is dropped and its streams are done.
+#[derive(Clone)]
+pub(super) struct Handle {
+    tx: mpsc::UnboundedSender<Message>,
+}
+
+/// A QUIC connection along with its socket, driven by a task of its own.
+pub(super) struct Connection {
+    socket: UdpSocket,
+    local: SocketAddr,
+    conn: quiche::Connection,
+    h3: Option<h3::Connection>,
+    h3_config: Arc<h3::Config>,
+    requests: mpsc::UnboundedReceiver<Message>,
+    accepting: bool,
+    queued: VecDeque<Message>,
+    streams: HashMap<u64, Stream>,
+    timer: Pin<Box<Sleep>>,
+    connect_timeout: Option<Pin<Box<Sleep>>>,
+    buf: Vec<u8>,
+    out: Vec<u8>,
+}
+
+struct Stream {
+    reply: Option<Reply>,
+    request_body: Bytes,
+    body: Option<hyper::body::Sender>,
+    chunk: Option<Bytes>,
+    finished: bool,
+}
+
+enum Event {
+    Request(Option<Message>),
+    Datagram(usize, SocketAddr),
+    Timeout,
+    BodyReady(u64),
+    BodyDropped(u64),
+    Failed(BoxError),
+}
+
+// ===== impl Handle =====
+
+impl Handle {
+    pub(super) fn send(&self, msg: Message) -> Result<(), BoxError> {
+        self.tx
+            .send(msg)
+            .map_err(|_| "HTTP/3 connection closed".into())
+    }
+
+    pub(super) fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+// ===== impl Connection =====
+
+impl Connection {
+    pub(super) fn new(
+        socket: UdpSocket,
+        local: SocketAddr,
+        conn: quiche::Connection,
+        h3_config: Arc<h3::Config>,
+        connect_timeout: Option<Duration>,
+    ) -> (Connection, Handle) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let connection = Connection {
+            socket,
+            local,
+            conn,
+            h3: None,
+            h3_config,
+            requests: rx,
+            accepting: true,
+            queued: VecDeque::new(),
+            streams: HashMap::new(),
+            timer: Box::pin(tokio::time::sleep_until(Instant::now())),
+            connect_timeout: connect_timeout.map(|timeout| Box::pin(tokio::time::sleep(timeout))),
+            buf: vec![0; MAX_DATAGRAM_SIZE],
+            out: vec![0; MAX_DATAGRAM_SIZE],
+        };
+        (connection, Handle { tx })
+    }
+
+    pub(super) async fn run(mut self) {
+        loop {
+            if let Err(e) = self.flush().await {
+                self.fail_all(e);
+                return;
+            }
+            if self.conn.is_closed() {
+                let err = self.close_error();
+                self.fail_all(err);
+                return;
+            }
+
+            match poll_fn(|cx| self.poll_event(cx)).await {
+                Event::Request(Some(msg)) => self.queued.push_back(msg),
+                Event::Request(None) => self.accepting = false,
+                Event::Datagram(len, from) => {
+                    let info = quiche::RecvInfo {
+                        from,
+                        to: self.local,
+                    };
+                    // quiche closes the connection on fatal errors itself.
+                    if let Err(e) = self.conn.recv(&mut self.buf[..len], info) {
+                        debug!("HTTP/3 packet from {} dropped: {}", from, e);
+                    }
+                }
+                Event::Timeout => self.conn.on_timeout(),
+                Event::BodyReady(id) => self.read_body(id),
+                Event::BodyDropped(id) => self.cancel(id),
+                Event::Failed(e) => {
+                    self.fail_all(e);
+                    return;
+                }
+            }
+
+            self.process();
+        }
+    }
+
+    fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<Event> {
+        if self.accepting {
+            if let Poll::Ready(msg) = self.requests.poll_recv(cx) {
+                return Poll::Ready(Event::Request(msg));
+            }
+        }
+
+        for (id, stream) in &mut self.streams {
+            if let (Some(_), Some(body)) = (&stream.chunk, &mut stream.body) {
+                match body.poll_ready(cx) {
+                    Poll::Ready(Ok(())) => return Poll::Ready(Event::BodyReady(*id)),
+                    Poll::Ready(Err(_)) => return Poll::Ready(Event::BodyDropped(*id)),
+                    Poll::Pending => {}
+                }
+            }
+        }
+
+        let mut buf = ReadBuf::new(&mut self.buf);
+        match self.socket.poll_recv_from(cx, &mut buf) {
+            Poll::Ready(Ok(from)) => return Poll::Ready(Event::Datagram(buf.filled().len(), from)),
+            Poll::Ready(Err(e)) => return Poll::Ready(Event::Failed(e.into())),
+            Poll::Pending => {}
+        }
+
+        if let Some(timeout) = self.conn.timeout() {
+            self.timer.as_mut().reset(Instant::now() + timeout);
+            if self.timer.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Event::Timeout);
+            }
+        }
+
+        if self.conn.is_established() {
+            self.connect_timeout = None;
+        } else if let Some(deadline) = &mut self.connect_timeout {
+            if deadline.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Event::Failed(TimedOut.into()));
+            }
+        }
+
+        Poll::Pending
+    }
+
+    /// Sends every packet quiche has ready.
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        loop {
+            let (len, info) = match self.conn.send(&mut self.out) {
+                Ok(sent) => sent,
+                Err(quiche::Error::Done) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+            self.socket.send_to(&self.out[..len], info.to).await?;
+        }
+    }
+
+    /// Opens the HTTP/3 connection once the handshake is done, then moves
+    /// requests and responses along.
+    fn process(&mut self) {
+        if self.h3.is_none() && self.conn.is_established() {
+            match h3::Connection::with_transport(&mut self.conn, &self.h3_config) {
+                Ok(h3) => self.h3 = Some(h3),
+                Err(e) => {
+                    debug!("HTTP/3 connection setup failed: {}", e);
+                    let _ = self.conn.close(true, e.to_wire(), b"");
+                    return;
+                }
+            }
+        }
+        if self.h3.is_none() {
+            return;
+        }
+
+        self.start_requests();
+        self.send_bodies();
+        self.poll_h3();
+
+        if !self.accepting && self.queued.is_empty() && self.streams.is_empty() {
+            let _ = self.conn.close(true, H3_NO_ERROR, b"");
+        }
+    }
+
+    fn start_requests(&mut self) {
+        let h3 = match self.h3 {
+            Some(ref mut h3) => h3,
+            None => return,
+        };
+        while let Some(msg) = self.queued.pop_front() {
+            if msg.reply.is_closed() {
+                continue;
+            }
+            let fin = msg.body.is_empty();
+            match h3.send_request(&mut self.conn, &msg.headers, fin) {
+                Ok(id) => {
+                    trace!("HTTP/3 request sent on stream {}", id);
+                    self.streams.insert(
+                        id,
+                        Stream {
+                            reply: Some(msg.reply),
+                            request_body: msg.body,
+                            body: None,
+                            chunk: None,
+                            finished: false,
+                        },
+                    );
+                }
+                Err(h3::Error::StreamBlocked)
+                | Err(h3::Error::TransportError(quiche::Error::StreamLimit)) => {
+                    self.queued.push_front(msg);
+                    return;
+                }
+                Err(e) => {
+                    let _ = msg.reply.send(Err(e.into()));
+                }
+            }
+        }
+    }
+
+    fn send_bodies(&mut self) {
+        let h3 = match self.h3 {
+            Some(ref mut h3) => h3,
+            None => return,
+        };
+        let mut failed = Vec::new();
+        for (id, stream) in &mut self.streams {
+            if stream.request_body.is_empty() {
+                continue;
+            }
+            match h3.send_body(&mut self.conn, *id, &stream.request_body, true) {
+                Ok(n) => stream.request_body.advance(n),
+                Err(h3::Error::Done) => {}
+                Err(e) => failed.push((*id, e)),
+            }
+        }
+        for (id, e) in failed {
+            self.fail(id, e.into());
+        }
+    }
+
+    fn poll_h3(&mut self) {
+        loop {
+            let event = match self.h3 {
+                Some(ref mut h3) => h3.poll(&mut self.conn),
+                None => return,
+            };
+            match event {
+                Ok((id, h3::Event::Headers { list, more_frames })) => {
+                    self.headers(id, list, more_frames)
+                }
+                Ok((id, h3::Event::Data)) => self.read_body(id),
+                Ok((id, h3::Event::Finished)) => {
+                    if let Some(stream) = self.streams.get_mut(&id) {
+                        stream.finished = true;
+                    }
+                    self.read_body(id);
+                }
+                Ok((id, h3::Event::Reset(code))) => self.fail(
+                    id,
+                    format!("HTTP/3 stream reset with error {:#x}", code).into(),
+                ),
+                Ok((_, h3::Event::GoAway)) => {
+                    // Requests not sent yet have to go over a new connection.
+                    self.accepting = false;
+                    self.requests.close();
+                    while let Ok(msg) = self.requests.try_recv() {
+                        self.queued.push_back(msg);
+                    }
+                    for msg in self.queued.drain(..) {
+                        let _ = msg
+                            .reply
+                            .send(Err("HTTP/3 connection is going away".into()));
+                    }
+                }
+                Ok(_) => {}
+                Err(h3::Error::Done) => return,
+                Err(e) => {
+                    debug!("HTTP/3 connection error: {}", e);
+                    let _ = self.conn.close(true, e.to_wire(), b"");
+                    return;
+                }
+            }
+        }
+    }
+
+    fn headers(&mut self, id: u64, list: Vec<Header>, more_frames: bool) {
+        let stream = match self.streams.get_mut(&id) {
+            Some(stream) => stream,
+            None => return,
+        };
+        // Trailers are dropped.
+        let reply = match stream.reply.take() {
+            Some(reply) => reply,
+            None => return,
+        };
+
+        let mut res = hyper::Response::builder().version(Version::HTTP_3);
+        let mut status = None;
+        for header in &list {
+            match header.name() {
+                b":status" => status = StatusCode::from_bytes(header.value()).ok(),
+                name if name.starts_with(b":") => {}
+                name => res = res.header(name, header.value()),
+            }
+        }
+        let status = match status {
+            Some(status) => status,
+            None => {
+                let _ = reply.send(Err("HTTP/3 response without a valid status".into()));
+                self.cancel(id);
+                return;
+            }
+        };
+        if status.is_informational() {
+            stream.reply = Some(reply);
+            return;
+        }
+
+        let body = if more_frames {
+            let (tx, body) = hyper::Body::channel();
+            stream.body = Some(tx);
+            body
+        } else {
+            hyper::Body::empty()
+        };
+        match res.status(status).body(body) {
+            Ok(res) => {
+                let _ = reply.send(Ok(res));
+                if !more_frames {
+                    self.streams.remove(&id);
+                }
+            }
+            Err(e) => {
+                let _ = reply.send(Err(e.into()));
+                self.cancel(id);
+            }
+        }
+    }
+
+    /// Moves response data from quiche to the body, as fast as it is read.
+    fn read_body(&mut self, id: u64) {
+        let h3 = match self.h3 {
+            Some(ref mut h3) => h3,
+            None => return,
+        };
+        let stream = match self.streams.get_mut(&id) {
+            Some(stream) => stream,
+            None => return,
+        };
+
+        loop {
+            if let Some(chunk) = stream.chunk.take() {
+                if let Some(ref mut body) = stream.body {
+                    if let Err(chunk) = body.try_send_data(chunk) {
+                        stream.chunk = Some(chunk);
+                        return;
+                    }
+                }
+            }
+            match h3.recv_body(&mut self.conn, id, &mut self.buf) {
+                Ok(n) => stream.chunk = Some(Bytes::copy_from_slice(&self.buf[..n])),
+                Err(h3::Error::Done) => break,
+                Err(e) => {
+                    self.fail(id, e.into());
+                    return;
+                }
+            }
+        }
+
+        if stream.finished {
+            // Dropping the sender ends the body.
+            self.streams.remove(&id);
+        }
+    }
+
+    /// Stops a stream nobody is interested in anymore.
+    fn cancel(&mut self, id: u64) {
+        self.streams.remove(&id);
+        let _ = self
+            .conn
+            .stream_shutdown(id, quiche::Shutdown::Read, H3_REQUEST_CANCELLED);
+        let _ = self
+            .conn
+            .stream_shutdown(id, quiche::Shutdown::Write, H3_REQUEST_CANCELLED);
+    }
+
+    fn fail(&mut self, id: u64, err: BoxError) {
+        if let Some(stream) = self.streams.get_mut(&id) {
+            match (stream.reply.take(), stream.body.take()) {
+                (Some(reply), _) => {
+                    let _ = reply.send(Err(err));
+                }
+                (None, Some(body)) => body.abort(),
+                (None, None) => {}
+            }
+        }
+        self.cancel(id);
+    }
+
+    fn fail_all(&mut self, err: BoxError) {
+        debug!("HTTP/3 connection failed: {}", err);
+        let msg = err.to_string();
+
+        self.requests.close();
+        while let Ok(req) = self.requests.try_recv() {
+            self.queued.push_back(req);
+        }
+        for req in self.queued.drain(..) {
+            let _ = req.reply.send(Err(msg.clone().into()));
+        }
+        for (_, stream) in self.streams.drain() {
+            if let Some(reply) = stream.reply {
+                let _ = reply.send(Err(msg.clone().into()));
+            } else if let Some(body) = stream.body {
+                body.abort();
+            }
+        }
+    }
+
+    fn close_error(&self) -> BoxError {
+        if self.conn.is_timed_out() {
+            return "HTTP/3 connection timed out".into();
+        }
+        match self.conn.peer_error().or_else(|| self.conn.local_error()) {
+            Some(e) if !(e.is_app && e.error_code == H3_NO_ERROR) => format!(
+                "HTTP/3 connection closed with error {:#x}: {}",
+                e.error_code,
+                String::from_utf8_lossy(&e.reason)
+            )
+            .into(),
+            _ => "HTTP/3 connection closed".into(),
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! An HTTP/3 client over QUIC, used for requests sent with
+//! `Version::HTTP_3`.
+//!
+//! hyper only speaks HTTP/1 and HTTP/2, so HTTP/3 requests are handed to
+//! quiche instead. Every QUIC connection is owned by a task driving its UDP
+//! socket, which requests are sent to over a channel.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use boring::ssl::SslVersion;
+use http::header::{CONNECTION, HOST, TRANSFER_ENCODING, UPGRADE};
+use http::{HeaderName, Uri};
+use hyper::client::connect::dns::Name;
+use quiche::h3::Header;
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+
+use self::connection::{Connection, Handle, Message};
+use super::body::ImplStream;
+use crate::alt_svc::Route;
+use crate::browser::profile::{Http3Settings, TlsSettings};
+use crate::browser::PseudoOrder;
+use crate::connect::{HandshakeSettings, TlsOverrides};
+use crate::dns::Resolve;
+use crate::error::BoxError;
+
+mod connection;
+
+pub(crate) type ResponseFuture =
+    Pin<Box<dyn Future<Output = Result<hyper::Response<hyper::Body>, BoxError>> + Send>>;
+
+/// Everything the `ClientBuilder` configures for HTTP/3.
+pub(crate) struct H3Config {
+    pub(crate) tls: TlsSettings,
+    pub(crate) settings: Http3Settings,
+    pub(crate) tls_overrides: TlsOverrides,
+    pub(crate) handshake: HandshakeSettings,
+    pub(crate) pseudo_order: Option<[PseudoOrder; 4]>,
+    pub(crate) resolver: Arc<dyn Resolve>,
+    pub(crate) local_address: Option<IpAddr>,
+    pub(crate) connect_timeout: Option<Duration>,
+}
+
+#[derive(Clone)]
+pub(crate) struct H3Client {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    quic: Mutex<quiche::Config>,
+    h3: Arc<quiche::h3::Config>,
+    handshake: HandshakeSettings,
+    pseudo_order: [PseudoOrder; 4],
+    resolver: Arc<dyn Resolve>,
+    local_address: Option<IpAddr>,
+    connect_timeout: Option<Duration>,
+    pool: Mutex<HashMap<String, Handle>>,
+}
+
+// Used for the transport parameters a profile leaves unset, as the QUIC
+// defaults of zero would not let the server send anything.
+const DEFAULT_MAX_IDLE_TIMEOUT: u64 = 30_000;
+const DEFAULT_MAX_UDP_PAYLOAD_SIZE: u64 = 1350;
+const DEFAULT_MAX_DATA: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_STREAM_DATA: u64 = 1024 * 1024;
+const DEFAULT_MAX_STREAMS: u64 = 100;
+
+const DEFAULT_PSEUDO_ORDER: [PseudoOrder; 4] = [
+    PseudoOrder::Method,
+    PseudoOrder::Scheme,
+    PseudoOrder::Authority,
+    PseudoOrder::Path,
+];
+
+// ===== impl H3Client =====
+
+impl H3Client {
+    pub(crate) fn new(config: H3Config) -> Result<H3Client, BoxError> {
+        let H3Config {
+            tls,
+            settings,
+            tls_overrides,
+            handshake,
+            pseudo_order,
+            resolver,
+            local_address,
+            connect_timeout,
+        } = config;
+
+        let mut ctx = tls.context_builder()?;
+        ctx.set_default_verify_paths()?;
+        tls_overrides.apply(&mut ctx)?;
+        // QUIC only runs over TLS 1.3, whatever the profile allows over TCP.
+        ctx.set_min_proto_version(Some(SslVersion::TLS1_3))?;
+        ctx.set_max_proto_version(Some(SslVersion::TLS1_3))?;
+
+        let mut quic = quiche::Config::with_boring_ssl_ctx_builder(quiche::PROTOCOL_VERSION, ctx)?;
+        quic.verify_peer(tls_overrides.certs_verification);
+        quic.set_application_protos(&[b"h3"])?;
+        quic.set_max_idle_timeout(
+            settings
+                .max_idle_timeout
+                .unwrap_or(DEFAULT_MAX_IDLE_TIMEOUT),
+        );
+        quic.set_max_recv_udp_payload_size(
+            settings
+                .max_udp_payload_size
+                .unwrap_or(DEFAULT_MAX_UDP_PAYLOAD_SIZE) as usize,
+        );
+        quic.set_initial_max_data(settings.initial_max_data.unwrap_or(DEFAULT_MAX_DATA));
+        quic.set_initial_max_stream_data_bidi_local(
+            settings
+                .initial_max_stream_data_bidi_local
+                .unwrap_or(DEFAULT_MAX_STREAM_DATA),
+        );
+        quic.set_initial_max_stream_data_bidi_remote(
+            settings
+                .initial_max_stream_data_bidi_remote
+                .unwrap_or(DEFAULT_MAX_STREAM_DATA),
+        );
+        quic.set_initial_max_stream_data_uni(
+            settings
+                .initial_max_stream_data_uni
+                .unwrap_or(DEFAULT_MAX_STREAM_DATA),
+        );
+        quic.set_initial_max_streams_bidi(
+            settings
+                .initial_max_streams_bidi
+                .unwrap_or(DEFAULT_MAX_STREAMS),
+        );
+        quic.set_initial_max_streams_uni(
+            settings
+                .initial_max_streams_uni
+                .unwrap_or(DEFAULT_MAX_STREAMS),
+        );
+        quic.set_disable_active_migration(settings.disable_active_migration);
+        quic.grease(settings.grease);
+
+        // quiche has no dynamic QPACK table, so a table capacity is never
+        // advertised; servers would otherwise be allowed to refer to it.
+        let mut h3 = quiche::h3::Config::new()?;
+        if let Some(max) = settings.qpack_blocked_streams {
+            h3.set_qpack_blocked_streams(max);
+        }
+        if let Some(sz) = settings.max_field_section_size {
+            h3.set_max_field_section_size(sz);
+        }
+
+        Ok(H3Client {
+            inner: Arc::new(Inner {
+                quic: Mutex::new(quic),
+                h3: Arc::new(h3),
+                handshake,
+                pseudo_order: pseudo_order.unwrap_or(DEFAULT_PSEUDO_ORDER),
+                resolver,
+                local_address,
+                connect_timeout,
+                pool: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    /// Sends `req`, to the alternative endpoint of `route` if given rather
+    /// than to the authority of its URI.
+    pub(crate) fn request(
+        &self,
+        req: hyper::Request<ImplStream>,
+        route: Option<Route>,
+    ) -> ResponseFuture {
+        let inner = self.inner.clone();
+        Box::pin(async move { inner.send(req, route).await })
+    }
+}
+
+// ===== impl Inner =====
+
+impl Inner {
+    async fn send(
+        &self,
+        req: hyper::Request<ImplStream>,
+        route: Option<Route>,
+    ) -> Result<hyper::Response<hyper::Body>, BoxError> {
+        let (parts, body) = req.into_parts();
+        if parts.uri.scheme_str() != Some("https") {
+            return Err("HTTP/3 requires an https URL".into());
+        }
+
+        let headers = self.headers(&parts)?;
+        // The request body is sent in one go once the stream is open.
+        let body = hyper::body::to_bytes(body).await?;
+
+        let handle = self.connection(&parts.uri, route.as_ref()).await?;
+        let (tx, rx) = oneshot::channel();
+        handle.send(Message {
+            headers,
+            body,
+            reply: tx,
+        })?;
+        rx.await.map_err(|_| "HTTP/3 connection closed")?
+    }
+
+    fn headers(&self, parts: &http::request::Parts) -> Result<Vec<Header>, BoxError> {
+        let authority = parts.uri.authority().ok_or("URI has no authority")?;
+        let path = parts.uri.path_and_query().map_or("/", |p| p.as_str());
+
+        let mut headers = Vec::with_capacity(parts.headers.len() + 4);
+        for pseudo in self.pseudo_order {
+            let (name, value) = match pseudo {
+                PseudoOrder::Method => (":method", parts.method.as_str()),
+                PseudoOrder::Scheme => (":scheme", "https"),
+                PseudoOrder::Authority => (":authority", authority.as_str()),
+                PseudoOrder::Path => (":path", path),
+            };
+            headers.push(Header::new(name.as_bytes(), value.as_bytes()));
+        }
+        for (name, value) in &parts.headers {
+            if is_connection_specific(name) {
+                continue;
+            }
+            headers.push(Header::new(name.as_str().as_bytes(), value.as_bytes()));
+        }
+        Ok(headers)
+    }
+
+    /// Returns the pooled connection to the authority of `uri`, or to the
+    /// alternative of `route`, or opens a new one.
+    ///
+    /// An alternative is still authenticated as the origin, so the origin
+    /// host is kept for SNI and certificate verification.
+    async fn connection(&self, uri: &Uri, route: Option<&Route>) -> Result<Handle, BoxError> {
+        let server_name = uri.host().ok_or("URI has no host")?;
+        let server_name = server_name.trim_start_matches('[').trim_end_matches(']');
+        let (host, port) = match route {
+            Some(route) => {
+                let alt = &route.alternative;
+                let host = alt.host().trim_start_matches('[').trim_end_matches(']');
+                match host {
+                    "" => (server_name, alt.port()),
+                    host => (host, alt.port()),
+                }
+            }
+            None => (server_name, uri.port_u16().unwrap_or(443)),
+        };
+        let key = format!("{}@{}:{}", server_name, host, port);
+
+        if let Some(handle) = self.pool.lock().unwrap().get(&key) {
+            if !handle.is_closed() {
+                return Ok(handle.clone());
+            }
+        }
+
+        let handle = self.connect(server_name, host, port).await?;
+        self.pool.lock().unwrap().insert(key, handle.clone());
+        Ok(handle)
+    }
+
+    async fn connect(&self, server_name: &str, host: &str, port: u16) -> Result<Handle, BoxError> {
+        let ip = match host.parse::<IpAddr>() {
+            Ok(ip) => ip,
+            Err(_) => {
+                let name = host.parse::<Name>()?;
+                // As over TCP, ports from the resolver are ignored.
+                let mut addrs = self.resolver.resolve(name).await?;
+                addrs
+                    .next()
+                    .ok_or("DNS resolution returned no addresses")?
+                    .ip()
+            }
+        };
+        let peer = SocketAddr::new(ip, port);
+
+        let bind = match self.local_address {
+            Some(ip) => SocketAddr::new(ip, 0),
+            None if peer.is_ipv4() => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+            None => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+        };
+        let socket = UdpSocket::bind(bind).await?;
+        let local = socket.local_addr()?;
+
+        let mut scid = [0; quiche::MAX_CONN_ID_LEN];
+        boring::rand::rand_bytes(&mut scid)?;
+        let scid = quiche::ConnectionId::from_ref(&scid);
+        let mut conn = quiche::connect(
+            Some(server_name),
+            &scid,
+            local,
+            peer,
+            &mut self.quic.lock().unwrap(),
+        )?;
+        // The ClientHello is only written once the first packet is sent.
+        self.handshake.configure_ssl(conn.as_mut())?;
+
+        let (connection, handle) =
+            Connection::new(socket, local, conn, self.h3.clone(), self.connect_timeout);
+        tokio::spawn(connection.run());
+        Ok(handle)
+    }
+}
+
+/// Headers which only make sense for a single HTTP/1 connection, and are
+/// malformed in HTTP/3.
+fn is_connection_specific(name: &HeaderName) -> bool {
+    name == CONNECTION
+        || name == HOST
+        || name == TRANSFER_ENCODING
+        || name == UPGRADE
+        || name == "keep-alive"
+        || name == "proxy-connection"
+}
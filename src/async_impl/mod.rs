@@ -7,6 +7,8 @@ pub use self::upgrade::Upgraded;
 pub mod body;
 pub mod client;
 pub mod decoder;
+#[cfg(feature = "http3")]
+mod h3_client;
 #[cfg(feature = "multipart")]
 pub mod multipart;
 pub(crate) mod request;
@@ -17,7 +17,7 @@ use super::response::Response;
 #[cfg(feature = "multipart")]
 use crate::header::CONTENT_LENGTH;
 use crate::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
-use crate::{Method, Url};
+use crate::{retry, Method, Url};
 use http::{request::Parts, Request as HttpRequest, Version};
 
 /// A request which can be executed with `Client::execute()`.
@@ -28,6 +28,8 @@ pub struct Request {
     body: Option<Body>,
     timeout: Option<Duration>,
     version: Version,
+    header_order: Vec<HeaderName>,
+    retry: Option<retry::Policy>,
 }
 
 /// A builder to construct the properties of a `Request`.
@@ -50,6 +52,8 @@ impl Request {
             body: None,
             timeout: None,
             version: Version::default(),
+            header_order: Vec::new(),
+            retry: None,
         }
     }
 
@@ -125,6 +129,30 @@ impl Request {
         &mut self.version
     }
 
+    /// Get the header order, overriding the client's when not empty.
+    #[inline]
+    pub fn header_order(&self) -> &[HeaderName] {
+        &self.header_order
+    }
+
+    /// Get a mutable reference to the header order.
+    #[inline]
+    pub fn header_order_mut(&mut self) -> &mut Vec<HeaderName> {
+        &mut self.header_order
+    }
+
+    /// Get the retry policy, overriding the client's when set.
+    #[inline]
+    pub fn retry(&self) -> Option<&retry::Policy> {
+        self.retry.as_ref()
+    }
+
+    /// Get a mutable reference to the retry policy.
+    #[inline]
+    pub fn retry_mut(&mut self) -> &mut Option<retry::Policy> {
+        &mut self.retry
+    }
+
     /// Attempt to clone the request.
     ///
     /// `None` is returned if the request can not be cloned, i.e. if the body is a stream.
@@ -137,10 +165,13 @@ impl Request {
         *req.timeout_mut() = self.timeout().cloned();
         *req.headers_mut() = self.headers().clone();
         *req.version_mut() = self.version();
+        *req.header_order_mut() = self.header_order.clone();
+        *req.retry_mut() = self.retry.clone();
         req.body = body;
         Some(req)
     }
 
+    #[allow(clippy::type_complexity)]
     pub(super) fn pieces(
         self,
     ) -> (
@@ -150,6 +181,8 @@ impl Request {
         Option<Body>,
         Option<Duration>,
         Version,
+        Vec<HeaderName>,
+        Option<retry::Policy>,
     ) {
         (
             self.method,
@@ -158,6 +191,8 @@ impl Request {
             self.body,
             self.timeout,
             self.version,
+            self.header_order,
+            self.retry,
         )
     }
 }
@@ -296,6 +331,16 @@ impl RequestBuilder {
         self
     }
 
+    /// Sets the retry policy for this request only.
+    ///
+    /// This overrides the policy set with `ClientBuilder::retry()`.
+    pub fn retry(mut self, policy: retry::Policy) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.retry_mut() = Some(policy);
+        }
+        self
+    }
+
     /// Sends a multipart/form-data body.
     ///
     /// ```
@@ -374,7 +419,26 @@ impl RequestBuilder {
         self
     }
 
+    /// Sets the order headers are sent in for this request only.
+    ///
+    /// This overrides the order set with `ClientBuilder::header_order` or
+    /// taken from an impersonation profile. See there for how unlisted
+    /// headers are handled.
+    pub fn header_order<I>(mut self, order: I) -> RequestBuilder
+    where
+        I: IntoIterator<Item = HeaderName>,
+    {
+        if let Ok(ref mut req) = self.request {
+            req.header_order = order.into_iter().collect();
+        }
+        self
+    }
+
     /// Set HTTP version
+    ///
+    /// `Version::HTTP_3` sends the request over QUIC, which requires the
+    /// optional `http3` feature and an `https` URL. Proxies are not used for
+    /// such requests.
     pub fn version(mut self, version: Version) -> RequestBuilder {
         if let Ok(ref mut req) = self.request {
             req.version = version;
@@ -613,6 +677,8 @@ where
             body: Some(body.into()),
             timeout: None,
             version,
+            header_order: Vec::new(),
+            retry: None,
         })
     }
 }
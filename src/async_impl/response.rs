@@ -28,22 +28,25 @@ pub struct Response {
     // Boxed to save space (11 words to 1 word), and it's not accessed
     // frequently internally.
     url: Box<Url>,
+    attempts: usize,
 }
 
 impl Response {
     pub(super) fn new(
-        res: hyper::Response<hyper::Body>,
+        res: hyper::Response<Body>,
         url: Url,
         accepts: Accepts,
         timeout: Option<Pin<Box<Sleep>>>,
+        attempts: usize,
     ) -> Response {
         let (mut parts, body) = res.into_parts();
-        let decoder = Decoder::detect(&mut parts.headers, Body::response(body, timeout), accepts);
+        let decoder = Decoder::detect(&mut parts.headers, body.with_timeout(timeout), accepts);
         let res = hyper::Response::from_parts(parts, decoder);
 
         Response {
             res,
             url: Box::new(url),
+            attempts,
         }
     }
 
@@ -111,6 +114,15 @@ impl Response {
             .map(|info| info.remote_addr())
     }
 
+    /// Get the number of requests sent to get this `Response`.
+    ///
+    /// This is 1 unless the request was retried, following the client's or
+    /// the request's [`retry::Policy`](crate::retry::Policy), or redirected.
+    /// Every retry and every redirect followed counts as one more attempt.
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
     /// Returns a reference to the associated extensions.
     pub fn extensions(&self) -> &http::Extensions {
         self.res.extensions()
@@ -315,6 +327,18 @@ impl Response {
         self.res.into_body()
     }
 
+    // private
+
+    // The Response's body is an implementation detail.
+    // You no longer need to get a reference to it, there are async methods
+    // on the `Response` itself.
+    //
+    // This method is just used by the blocking API.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn body_mut(&mut self) -> &mut Decoder {
+        self.res.body_mut()
+    }
+
     // util methods
 
     /// Turn a response into an error if the server returned an error.
@@ -402,6 +426,7 @@ impl<T: Into<Body>> From<http::Response<T>> for Response {
         Response {
             res,
             url: Box::new(url),
+            attempts: 1,
         }
     }
 }
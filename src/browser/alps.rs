@@ -0,0 +1,263 @@
+//! The TLS ALPS (application-layer protocol settings) extension
+//!
+//! With ALPS, the client and the server exchange settings for the
+//! negotiated protocol during the TLS handshake. Chrome sends its HTTP/2
+//! `SETTINGS` frame this way, first under the `application_settings`
+//! codepoint (17513) and, in newer versions, under a new one (17613).
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::profile::Http2Settings;
+use crate::error::BoxError;
+
+const FRAME_HEADER_LEN: usize = 9;
+const FRAME_SETTINGS: u8 = 0x4;
+const FRAME_ACCEPT_CH: u8 = 0x89;
+const FLAG_ACK: u8 = 0x1;
+
+/// The ALPS extension of a profile: which protocols it is offered for, what
+/// is sent for them, and under which codepoint.
+///
+/// The default offers ALPS for `h2` with an empty payload, under the
+/// original codepoint.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApplicationSettings {
+    protocols: Vec<String>,
+    payload: AlpsPayload,
+    codepoint: AlpsCodepoint,
+}
+
+/// What the client sends in ALPS for each protocol.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlpsPayload {
+    /// Nothing.
+    Empty,
+    /// The profile's initial HTTP/2 `SETTINGS` frame, as Chrome does.
+    Http2Settings,
+    /// The given bytes.
+    Raw(Vec<u8>),
+}
+
+/// The codepoint of the ALPS extension in the ClientHello.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlpsCodepoint {
+    /// The original `application_settings` codepoint, 17513.
+    Old,
+    /// The codepoint Chrome moved to, 17613.
+    New,
+}
+
+/// The ALPS extension resolved against a profile's HTTP/2 settings, ready to
+/// be added to a connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct AlpsConfig {
+    pub(crate) protocols: Arc<Vec<(String, Vec<u8>)>>,
+    pub(crate) new_codepoint: bool,
+}
+
+// ===== impl ApplicationSettings =====
+
+impl ApplicationSettings {
+    /// Offers ALPS for `protocols`, e.g. `h2`, with an empty payload under
+    /// the original codepoint.
+    pub fn new<I, S>(protocols: I) -> ApplicationSettings
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ApplicationSettings {
+            protocols: protocols.into_iter().map(Into::into).collect(),
+            payload: AlpsPayload::Empty,
+            codepoint: AlpsCodepoint::Old,
+        }
+    }
+
+    /// Sets what is sent for each protocol.
+    pub fn with_payload(mut self, payload: AlpsPayload) -> ApplicationSettings {
+        self.payload = payload;
+        self
+    }
+
+    /// Sets the codepoint of the extension.
+    pub fn with_codepoint(mut self, codepoint: AlpsCodepoint) -> ApplicationSettings {
+        self.codepoint = codepoint;
+        self
+    }
+
+    /// The protocols ALPS is offered for.
+    pub fn protocols(&self) -> &[String] {
+        &self.protocols
+    }
+
+    /// What is sent for each protocol.
+    pub fn payload(&self) -> &AlpsPayload {
+        &self.payload
+    }
+
+    /// The codepoint of the extension.
+    pub fn codepoint(&self) -> AlpsCodepoint {
+        self.codepoint
+    }
+
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        if self.protocols.is_empty() {
+            return Err(crate::error::builder(
+                "ALPS must be offered for at least one protocol",
+            ));
+        }
+        if self.protocols.iter().any(|p| p.is_empty() || p.len() > 255) {
+            return Err(crate::error::builder(
+                "ALPS protocols must be 1 to 255 bytes long",
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn to_config(&self, http2: &Http2Settings) -> AlpsConfig {
+        let payload = match self.payload {
+            AlpsPayload::Empty => Vec::new(),
+            AlpsPayload::Http2Settings => settings_frame(&http2.settings()),
+            AlpsPayload::Raw(ref bytes) => bytes.clone(),
+        };
+        let protocols = self
+            .protocols
+            .iter()
+            .map(|protocol| (protocol.clone(), payload.clone()))
+            .collect();
+
+        AlpsConfig {
+            protocols: Arc::new(protocols),
+            new_codepoint: self.codepoint == AlpsCodepoint::New,
+        }
+    }
+}
+
+impl Default for ApplicationSettings {
+    fn default() -> ApplicationSettings {
+        ApplicationSettings::new(["h2"])
+    }
+}
+
+impl Default for AlpsPayload {
+    fn default() -> AlpsPayload {
+        AlpsPayload::Empty
+    }
+}
+
+impl Default for AlpsCodepoint {
+    fn default() -> AlpsCodepoint {
+        AlpsCodepoint::Old
+    }
+}
+
+// ===== impl AlpsConfig =====
+
+impl Default for AlpsConfig {
+    fn default() -> AlpsConfig {
+        AlpsConfig {
+            protocols: Arc::new(vec![("h2".to_owned(), Vec::new())]),
+            new_codepoint: false,
+        }
+    }
+}
+
+/// Deserializes the `application_settings` of a profile, which used to be a
+/// plain flag enabling ALPS for `h2`.
+pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<ApplicationSettings>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Def {
+        Enabled(bool),
+        Settings(ApplicationSettings),
+    }
+
+    Ok(match Option::<Def>::deserialize(deserializer)? {
+        Some(Def::Enabled(true)) => Some(ApplicationSettings::default()),
+        Some(Def::Enabled(false)) | None => None,
+        Some(Def::Settings(settings)) => Some(settings),
+    })
+}
+
+/// Serializes HTTP/2 settings as a `SETTINGS` frame, header included.
+fn settings_frame(settings: &[(u16, u32)]) -> Vec<u8> {
+    let len = (settings.len() * 6) as u32;
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + len as usize);
+    frame.extend_from_slice(&len.to_be_bytes()[1..]);
+    frame.extend_from_slice(&[FRAME_SETTINGS, 0, 0, 0, 0, 0]);
+    for (id, value) in settings {
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.extend_from_slice(&value.to_be_bytes());
+    }
+    frame
+}
+
+/// Adds `settings` to the first frame of `data`, which an HTTP/2 server
+/// sent, if it is a `SETTINGS` frame. They go ahead of the settings the
+/// frame holds, so that the values the server sent there win.
+///
+/// Returns `None` while `data` doesn't hold the whole frame.
+pub(crate) fn merge_server_h2(settings: &[(u16, u32)], data: &[u8]) -> Option<Vec<u8>> {
+    let header = data.get(..FRAME_HEADER_LEN)?;
+    if header[3] != FRAME_SETTINGS || header[4] & FLAG_ACK != 0 {
+        return Some(data.to_vec());
+    }
+    let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+    let payload = data.get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len)?;
+
+    let mut merged = settings_frame(settings);
+    let merged_len = (merged.len() - FRAME_HEADER_LEN + len) as u32;
+    merged[..3].copy_from_slice(&merged_len.to_be_bytes()[1..]);
+    merged.extend_from_slice(payload);
+    merged.extend_from_slice(&data[FRAME_HEADER_LEN + len..]);
+    Some(merged)
+}
+
+/// Parses the ALPS payload a server sent for `h2`, returning the settings
+/// it contains.
+///
+/// Like Chrome, only `SETTINGS` and `ACCEPT_CH` frames on stream 0 are
+/// accepted; anything else is a protocol error.
+pub(crate) fn parse_server_h2(mut data: &[u8]) -> Result<Vec<(u16, u32)>, BoxError> {
+    let mut settings = Vec::new();
+    while !data.is_empty() {
+        if data.len() < FRAME_HEADER_LEN {
+            return Err("truncated frame in server ALPS".into());
+        }
+        let len = u32::from_be_bytes([0, data[0], data[1], data[2]]) as usize;
+        let (ty, flags) = (data[3], data[4]);
+        let stream_id = u32::from_be_bytes([data[5], data[6], data[7], data[8]]) & 0x7fff_ffff;
+        let payload = data
+            .get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len)
+            .ok_or("truncated frame in server ALPS")?;
+
+        if stream_id != 0 {
+            return Err("frame on a stream in server ALPS".into());
+        }
+        match ty {
+            FRAME_SETTINGS => {
+                if flags & FLAG_ACK != 0 || len % 6 != 0 {
+                    return Err("invalid SETTINGS frame in server ALPS".into());
+                }
+                for setting in payload.chunks_exact(6) {
+                    let id = u16::from_be_bytes([setting[0], setting[1]]);
+                    let value =
+                        u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+                    settings.push((id, value));
+                }
+            }
+            FRAME_ACCEPT_CH => {}
+            _ => return Err(format!("unexpected frame type {} in server ALPS", ty).into()),
+        }
+
+        data = &data[FRAME_HEADER_LEN + len..];
+    }
+    Ok(settings)
+}
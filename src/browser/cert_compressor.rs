@@ -1,7 +1,62 @@
 use std::io::Write as _;
 
-use boring::ssl::{CertificateCompressionAlgorithm, CertificateCompressor};
+use boring::error::ErrorStack;
+use boring::ssl::{CertificateCompressionAlgorithm, CertificateCompressor, SslContextBuilder};
+use serde::{Deserialize, Serialize};
 
+/// A certificate compression algorithm advertised in the ClientHello.
+///
+/// Algorithms are advertised in the order a profile lists them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum CertCompressionAlgorithm {
+    /// Zlib, as advertised by Firefox.
+    Zlib,
+    /// Brotli, as advertised by Chrome.
+    Brotli,
+    /// Zstandard, as advertised by Firefox.
+    Zstd,
+}
+
+impl CertCompressionAlgorithm {
+    /// The identifier of the algorithm on the wire.
+    pub fn id(self) -> u16 {
+        match self {
+            CertCompressionAlgorithm::Zlib => 1,
+            CertCompressionAlgorithm::Brotli => 2,
+            CertCompressionAlgorithm::Zstd => 3,
+        }
+    }
+
+    pub(crate) fn from_id(id: u16) -> Option<CertCompressionAlgorithm> {
+        match id {
+            1 => Some(CertCompressionAlgorithm::Zlib),
+            2 => Some(CertCompressionAlgorithm::Brotli),
+            3 => Some(CertCompressionAlgorithm::Zstd),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn add_to_builder(
+    builder: &mut SslContextBuilder,
+    algorithm: CertCompressionAlgorithm,
+) -> Result<(), ErrorStack> {
+    match algorithm {
+        CertCompressionAlgorithm::Zlib => {
+            builder.add_certificate_compression_algorithm(ZlibCompressor::default())
+        }
+        CertCompressionAlgorithm::Brotli => {
+            builder.add_certificate_compression_algorithm(BrotliCompressor::default())
+        }
+        CertCompressionAlgorithm::Zstd => {
+            builder.add_certificate_compression_algorithm(ZstdCompressor::default())
+        }
+    }
+}
+
+/// Brotli certificate compression (RFC 8879).
 pub struct BrotliCompressor {
     q: u32,
     lgwin: u32,
@@ -35,3 +90,74 @@ impl CertificateCompressor for BrotliCompressor {
         Ok(())
     }
 }
+
+/// Zlib certificate compression (RFC 8879).
+pub struct ZlibCompressor {
+    level: flate2::Compression,
+}
+
+impl Default for ZlibCompressor {
+    fn default() -> Self {
+        Self {
+            level: flate2::Compression::default(),
+        }
+    }
+}
+
+impl CertificateCompressor for ZlibCompressor {
+    const ALGORITHM: CertificateCompressionAlgorithm = CertificateCompressionAlgorithm::ZLIB;
+    const CAN_COMPRESS: bool = true;
+    const CAN_DECOMPRESS: bool = true;
+
+    fn compress<W>(&self, input: &[u8], output: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        let mut writer = flate2::write::ZlibEncoder::new(output, self.level);
+        writer.write_all(input)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn decompress<W>(&self, input: &[u8], output: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        let mut reader = flate2::read::ZlibDecoder::new(input);
+        std::io::copy(&mut reader, output)?;
+        Ok(())
+    }
+}
+
+/// Zstandard certificate compression (RFC 8879).
+pub struct ZstdCompressor {
+    level: i32,
+}
+
+impl Default for ZstdCompressor {
+    fn default() -> Self {
+        Self {
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+impl CertificateCompressor for ZstdCompressor {
+    const ALGORITHM: CertificateCompressionAlgorithm = CertificateCompressionAlgorithm::ZSTD;
+    const CAN_COMPRESS: bool = true;
+    const CAN_DECOMPRESS: bool = true;
+
+    fn compress<W>(&self, input: &[u8], output: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        zstd::stream::copy_encode(input, output, self.level)
+    }
+
+    fn decompress<W>(&self, input: &[u8], output: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        zstd::stream::copy_decode(input, output)
+    }
+}
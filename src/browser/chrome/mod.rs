@@ -1,31 +1,32 @@
 //! Settings for impersonating the Chrome browser
 
-use crate::ClientBuilder;
+use super::ImpersonateProfile;
 
 mod ver;
 
-pub(crate) fn configure_chrome(ver: ChromeVersion, builder: ClientBuilder) -> ClientBuilder {
-    let settings = ver::get_config_from_ver(ver);
-
-    builder
-        .use_boring_tls(settings.tls_builder_func)
-        .http2_initial_stream_window_size(settings.http2.initial_stream_window_size)
-        .http2_initial_connection_window_size(settings.http2.initial_connection_window_size)
-        .http2_max_concurrent_streams(settings.http2.max_concurrent_streams)
-        .http2_max_header_list_size(settings.http2.max_header_list_size)
-        .http2_header_table_size(settings.http2.header_table_size)
-        .http2_enable_push(settings.http2.enable_push)
-        .replace_default_headers(settings.headers)
-        .brotli(settings.brotli)
-        .gzip(settings.gzip)
-}
-
 /// Defines the Chrome version to mimic when setting up a builder
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[allow(missing_docs)]
 pub enum ChromeVersion {
     V104,
     V105,
     V106,
     V108,
+    V110,
+    V116,
+    V120,
+    V124,
+    V126,
+    V129,
+    V131,
+}
+
+impl ChromeVersion {
+    /// Returns the [`ImpersonateProfile`] describing this version.
+    ///
+    /// The profile can be tweaked with [`ImpersonateProfile::to_builder`]
+    /// before being passed to `ClientBuilder::impersonate`.
+    pub fn profile(self) -> ImpersonateProfile {
+        ver::get_config_from_ver(self)
+    }
 }
@@ -1,4 +1,4 @@
-use crate::browser::BrowserSettings;
+use crate::browser::ImpersonateProfile;
 
 use super::ChromeVersion;
 
@@ -6,12 +6,26 @@ mod v104;
 mod v105;
 mod v106;
 mod v108;
+mod v110;
+mod v116;
+mod v120;
+mod v124;
+mod v126;
+mod v129;
+mod v131;
 
-pub(super) fn get_config_from_ver(ver: ChromeVersion) -> BrowserSettings {
+pub(super) fn get_config_from_ver(ver: ChromeVersion) -> ImpersonateProfile {
     match ver {
         ChromeVersion::V104 => v104::get_settings(),
         ChromeVersion::V105 => v105::get_settings(),
         ChromeVersion::V106 => v106::get_settings(),
         ChromeVersion::V108 => v108::get_settings(),
+        ChromeVersion::V110 => v110::get_settings(),
+        ChromeVersion::V116 => v116::get_settings(),
+        ChromeVersion::V120 => v120::get_settings(),
+        ChromeVersion::V124 => v124::get_settings(),
+        ChromeVersion::V126 => v126::get_settings(),
+        ChromeVersion::V129 => v129::get_settings(),
+        ChromeVersion::V131 => v131::get_settings(),
     }
 }
@@ -1,86 +1,81 @@
-use boring::ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslVersion};
 use http::{
     header::{ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, UPGRADE_INSECURE_REQUESTS, USER_AGENT},
     HeaderMap, HeaderValue,
 };
-use std::sync::Arc;
 
-use crate::browser::{cert_compressor, BrowserSettings, Http2Data};
-
-pub(super) fn get_settings() -> BrowserSettings {
-    BrowserSettings {
-        tls_builder_func: Arc::new(create_ssl_connector),
-        http2: Http2Data {
-            initial_stream_window_size: 6291456,
-            initial_connection_window_size: 15728640,
-            max_concurrent_streams: 1000,
-            max_header_list_size: 262144,
-            header_table_size: 65536,
-            enable_push: Some(false),
-        },
-        headers: create_headers(),
-        gzip: true,
-        brotli: true,
-    }
-}
-
-fn create_ssl_connector() -> SslConnectorBuilder {
-    let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
-
-    builder.set_grease_enabled(true);
-
-    builder.enable_ocsp_stapling();
-
-    let cipher_list = [
-        "TLS_AES_128_GCM_SHA256",
-        "TLS_AES_256_GCM_SHA384",
-        "TLS_CHACHA20_POLY1305_SHA256",
-        "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
-        "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
-        "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
-        "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
-        "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
-        "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
-        "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA",
-        "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA",
-        "TLS_RSA_WITH_AES_128_GCM_SHA256",
-        "TLS_RSA_WITH_AES_256_GCM_SHA384",
-        "TLS_RSA_WITH_AES_128_CBC_SHA",
-        "TLS_RSA_WITH_AES_256_CBC_SHA",
-    ];
-
-    builder.set_cipher_list(&cipher_list.join(":")).unwrap();
-
-    let sigalgs_list = [
-        "ecdsa_secp256r1_sha256",
-        "rsa_pss_rsae_sha256",
-        "rsa_pkcs1_sha256",
-        "ecdsa_secp384r1_sha384",
-        "rsa_pss_rsae_sha384",
-        "rsa_pkcs1_sha384",
-        "rsa_pss_rsae_sha512",
-        "rsa_pkcs1_sha512",
-    ];
-
-    builder.set_sigalgs_list(&sigalgs_list.join(":")).unwrap();
-
-    builder.enable_signed_cert_timestamps();
-
-    builder.set_alpn_protos(b"\x02h2\x08http/1.1").unwrap();
-
-    builder
-        .add_certificate_compression_algorithm(cert_compressor::BrotliCompressor::default())
-        .unwrap();
-
-    builder
-        .set_min_proto_version(Some(SslVersion::TLS1_2))
-        .unwrap();
-
-    builder
-        .set_max_proto_version(Some(SslVersion::TLS1_3))
-        .unwrap();
-
-    builder
+use crate::browser::{
+    AlpsPayload, ApplicationSettings, CertCompressionAlgorithm, ImpersonateProfile, Priority,
+    PseudoOrder, SettingId,
+};
+use crate::tls::Version;
+
+pub(super) fn get_settings() -> ImpersonateProfile {
+    ImpersonateProfile::builder()
+        .grease_enabled(true)
+        .ocsp_stapling(true)
+        .cipher_list([
+            "TLS_AES_128_GCM_SHA256",
+            "TLS_AES_256_GCM_SHA384",
+            "TLS_CHACHA20_POLY1305_SHA256",
+            "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+            "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+            "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+            "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+            "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+            "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+            "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA",
+            "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA",
+            "TLS_RSA_WITH_AES_128_GCM_SHA256",
+            "TLS_RSA_WITH_AES_256_GCM_SHA384",
+            "TLS_RSA_WITH_AES_128_CBC_SHA",
+            "TLS_RSA_WITH_AES_256_CBC_SHA",
+        ])
+        .sigalgs_list([
+            "ecdsa_secp256r1_sha256",
+            "rsa_pss_rsae_sha256",
+            "rsa_pkcs1_sha256",
+            "ecdsa_secp384r1_sha384",
+            "rsa_pss_rsae_sha384",
+            "rsa_pkcs1_sha384",
+            "rsa_pss_rsae_sha512",
+            "rsa_pkcs1_sha512",
+        ])
+        .signed_cert_timestamps(true)
+        .alpn_protos(["h2", "http/1.1"])
+        .cert_compression_algorithms([CertCompressionAlgorithm::Brotli])
+        .min_tls_version(Version::TLS_1_2)
+        .max_tls_version(Version::TLS_1_3)
+        .alps(ApplicationSettings::new(["h2"]).with_payload(AlpsPayload::Http2Settings))
+        .http2_initial_stream_window_size(6291456)
+        .http2_initial_connection_window_size(15728640)
+        .http2_max_concurrent_streams(1000)
+        .http2_max_header_list_size(262144)
+        .http2_header_table_size(65536)
+        .http2_enable_push(false)
+        .http2_headers_pseudo_order([
+            PseudoOrder::Method,
+            PseudoOrder::Authority,
+            PseudoOrder::Scheme,
+            PseudoOrder::Path,
+        ])
+        .http2_settings_order([
+            SettingId::HeaderTableSize,
+            SettingId::EnablePush,
+            SettingId::MaxConcurrentStreams,
+            SettingId::InitialWindowSize,
+            SettingId::MaxFrameSize,
+            SettingId::MaxHeaderListSize,
+        ])
+        .http2_headers_priority(Priority {
+            stream_dependency: 0,
+            weight: 256,
+            exclusive: true,
+        })
+        .headers(create_headers())
+        .gzip(true)
+        .brotli(true)
+        .build()
+        .unwrap()
 }
 
 fn create_headers() -> HeaderMap {
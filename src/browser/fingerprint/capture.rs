@@ -0,0 +1,409 @@
+//! A loopback TLS server recording what a client sends on a new connection
+
+use std::future::Future;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use boring::asn1::Asn1Time;
+use boring::bn::BigNum;
+use boring::ec::{EcGroup, EcKey};
+use boring::hash::MessageDigest;
+use boring::nid::Nid;
+use boring::pkey::PKey;
+use boring::ssl::{select_next_proto, AlpnError, SslAcceptor, SslMethod};
+use boring::x509::{X509NameBuilder, X509};
+use futures_util::future::{self, Either};
+use http::header::HeaderName;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::client_hello::ClientHello;
+use super::http2::Http2Capture;
+use super::Fingerprint;
+use crate::error::BoxError;
+
+const MAX_CLIENT_HELLO: usize = 64 * 1024;
+const MAX_REQUEST_HEAD: usize = 64 * 1024;
+
+/// What a client sent on a single connection.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    /// The raw ClientHello handshake message, including its header.
+    pub(crate) client_hello: Vec<u8>,
+    pub(crate) alpn: Option<Vec<u8>>,
+    pub(crate) http2: Option<Http2Capture>,
+    pub(crate) header_order: Vec<HeaderName>,
+}
+
+impl Capture {
+    /// The raw ClientHello handshake message, including its 4 byte header.
+    pub fn client_hello(&self) -> &[u8] {
+        &self.client_hello
+    }
+
+    /// The server name the client sent in its ClientHello, if any.
+    pub fn server_name(&self) -> Option<String> {
+        self.parse_client_hello().ok()?.server_name()
+    }
+
+    /// The protocol negotiated with ALPN, e.g. `b"h2"`.
+    ///
+    /// This is `None` if the client didn't offer ALPN or aborted the
+    /// handshake.
+    pub fn alpn(&self) -> Option<&[u8]> {
+        self.alpn.as_deref()
+    }
+
+    /// The frames the client sent when opening the HTTP/2 connection.
+    ///
+    /// This is `None` if HTTP/2 wasn't negotiated.
+    pub fn http2(&self) -> Option<&Http2Capture> {
+        self.http2.as_ref()
+    }
+
+    /// The names of the regular headers of the first request, in the order
+    /// they were sent.
+    ///
+    /// For HTTP/2 the names are as decoded by HPACK, so lowercase.
+    pub fn header_order(&self) -> &[HeaderName] {
+        &self.header_order
+    }
+
+    /// Computes the fingerprints of what the client sent.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the ClientHello is malformed.
+    pub fn fingerprint(&self) -> crate::Result<Fingerprint> {
+        Fingerprint::from_capture(self).map_err(crate::error::decode)
+    }
+
+    pub(crate) fn parse_client_hello(&self) -> Result<ClientHello, BoxError> {
+        ClientHello::parse(&self.client_hello)
+    }
+}
+
+/// Listens on `localhost`, on both IPv4 and, when available, IPv6.
+pub(crate) struct Listener {
+    v4: TcpListener,
+    v6: Option<TcpListener>,
+    acceptor: SslAcceptor,
+}
+
+impl Listener {
+    pub(crate) async fn bind() -> Result<Listener, BoxError> {
+        let v4 = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let port = v4.local_addr()?.port();
+        let v6 = TcpListener::bind((Ipv6Addr::LOCALHOST, port)).await.ok();
+
+        Ok(Listener {
+            v4,
+            v6,
+            acceptor: acceptor()?,
+        })
+    }
+
+    pub(crate) fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.v4.local_addr()
+    }
+
+    async fn accept(&self) -> io::Result<TcpStream> {
+        let v6 = match self.v6 {
+            Some(ref v6) => v6,
+            None => return self.v4.accept().await.map(|(tcp, _)| tcp),
+        };
+
+        let accepted = match future::select(Box::pin(self.v4.accept()), Box::pin(v6.accept())).await
+        {
+            Either::Left((accepted, _)) => accepted,
+            Either::Right((accepted, _)) => accepted,
+        };
+        accepted.map(|(tcp, _)| tcp)
+    }
+
+    /// Accepts one connection and records it, answering its first request
+    /// with an empty `200 OK`.
+    ///
+    /// Only the ClientHello is recorded if the client aborts the handshake,
+    /// e.g. because it doesn't trust the self-signed certificate.
+    pub(crate) async fn capture(&self) -> Result<Capture, BoxError> {
+        let mut tcp = self.accept().await?;
+        let (records, client_hello) = read_client_hello(&mut tcp).await?;
+
+        let mut capture = Capture {
+            client_hello,
+            alpn: None,
+            http2: None,
+            header_order: Vec::new(),
+        };
+
+        let io = Rewind {
+            prefix: records,
+            pos: 0,
+            inner: tcp,
+        };
+        let stream = match tokio_boring::accept(&self.acceptor, io).await {
+            Ok(stream) => stream,
+            Err(_) => return Ok(capture),
+        };
+
+        capture.alpn = stream.ssl().selected_alpn_protocol().map(<[u8]>::to_vec);
+        if capture.alpn.as_deref() == Some(b"h2") {
+            let recorded = Arc::new(Mutex::new(Vec::new()));
+            let io = Recording {
+                inner: stream,
+                recorded: recorded.clone(),
+            };
+            capture.header_order = serve_h2(io).await?;
+            capture.http2 = Http2Capture::parse(&recorded.lock().unwrap());
+        } else {
+            capture.header_order = serve_http1(stream).await?;
+        }
+
+        Ok(capture)
+    }
+}
+
+fn acceptor() -> Result<SslAcceptor, BoxError> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, "localhost")?;
+    let name = name.build();
+
+    let mut cert = X509::builder()?;
+    cert.set_version(2)?;
+    cert.set_serial_number(&*BigNum::from_u32(1)?.to_asn1_integer()?)?;
+    cert.set_subject_name(&name)?;
+    cert.set_issuer_name(&name)?;
+    cert.set_pubkey(&key)?;
+    cert.set_not_before(&*Asn1Time::days_from_now(0)?)?;
+    cert.set_not_after(&*Asn1Time::days_from_now(1)?)?;
+    cert.sign(&key, MessageDigest::sha256())?;
+    let cert = cert.build();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())?;
+    acceptor.set_private_key(&key)?;
+    acceptor.set_certificate(&cert)?;
+    acceptor.set_alpn_select_callback(|_, client| {
+        select_next_proto(b"\x02h2\x08http/1.1", client).ok_or(AlpnError::NOACK)
+    });
+    Ok(acceptor.build())
+}
+
+/// Reads TLS records until they hold a complete ClientHello, returning both
+/// the raw records and the handshake message.
+async fn read_client_hello(tcp: &mut TcpStream) -> Result<(Vec<u8>, Vec<u8>), BoxError> {
+    let mut records = Vec::new();
+    let mut message = Vec::new();
+
+    loop {
+        if message.len() >= 4 {
+            let len = u32::from_be_bytes([0, message[1], message[2], message[3]]) as usize;
+            if message.len() >= 4 + len {
+                message.truncate(4 + len);
+                return Ok((records, message));
+            }
+        }
+
+        let mut header = [0; 5];
+        read_exact(tcp, &mut header).await?;
+        if header[0] != 22 {
+            return Err("expected a TLS handshake record".into());
+        }
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        if records.len() + len > MAX_CLIENT_HELLO {
+            return Err("ClientHello too large".into());
+        }
+
+        let mut fragment = vec![0; len];
+        read_exact(tcp, &mut fragment).await?;
+        records.extend_from_slice(&header);
+        records.extend_from_slice(&fragment);
+        message.extend_from_slice(&fragment);
+    }
+}
+
+async fn serve_h2<T>(io: T) -> Result<Vec<HeaderName>, BoxError>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut conn = h2::server::handshake(io).await?;
+    let mut header_order = Vec::new();
+
+    if let Some(accepted) = conn.accept().await {
+        let (req, mut respond) = accepted?;
+        header_order = req.headers().keys().cloned().collect();
+        respond.send_response(http::Response::new(()), true)?;
+    }
+
+    conn.graceful_shutdown();
+    let _ = future::poll_fn(|cx| conn.poll_closed(cx)).await;
+
+    Ok(header_order)
+}
+
+async fn serve_http1<T>(mut io: T) -> Result<Vec<HeaderName>, BoxError>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut head = Vec::new();
+    let mut buf = [0; 4096];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = read(&mut io, &mut buf).await?;
+        if n == 0 || head.len() + n > MAX_REQUEST_HEAD {
+            return Err("incomplete HTTP/1 request".into());
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let header_order = head
+        .split(|b| *b == b'\n')
+        .skip(1)
+        .filter_map(|line| {
+            let colon = line.iter().position(|b| *b == b':')?;
+            HeaderName::from_bytes(&line[..colon]).ok()
+        })
+        .collect();
+
+    write_all(
+        &mut io,
+        b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+    )
+    .await?;
+    future::poll_fn(|cx| Pin::new(&mut io).poll_shutdown(cx)).await?;
+
+    Ok(header_order)
+}
+
+async fn read<T: AsyncRead + Unpin>(io: &mut T, buf: &mut [u8]) -> io::Result<usize> {
+    future::poll_fn(|cx| {
+        let mut buf = ReadBuf::new(buf);
+        match Pin::new(&mut *io).poll_read(cx, &mut buf) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    })
+    .await
+}
+
+async fn read_exact<T: AsyncRead + Unpin>(io: &mut T, mut buf: &mut [u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let n = read(io, buf).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf = &mut buf[n..];
+    }
+    Ok(())
+}
+
+async fn write_all<T: AsyncWrite + Unpin>(io: &mut T, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let n = future::poll_fn(|cx| Pin::new(&mut *io).poll_write(cx, buf)).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[n..];
+    }
+    future::poll_fn(|cx| Pin::new(&mut *io).poll_flush(cx)).await
+}
+
+/// Replays bytes already read from `inner` before reading from it again.
+struct Rewind<T> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: T,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Rewind<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.pos < self.prefix.len() {
+            let n = buf.remaining().min(self.prefix.len() - self.pos);
+            buf.put_slice(&self.prefix[self.pos..self.pos + n]);
+            self.pos += n;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Rewind<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Keeps a copy of every byte read from `inner`.
+struct Recording<T> {
+    inner: T,
+    recorded: Arc<Mutex<Vec<u8>>>,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Recording<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            self.recorded
+                .lock()
+                .unwrap()
+                .extend_from_slice(&buf.filled()[filled..]);
+        }
+        poll
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Recording<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Runs `fut`, failing if it takes longer than `timeout`.
+pub(crate) async fn with_timeout<F, T>(timeout: std::time::Duration, fut: F) -> Result<T, BoxError>
+where
+    F: Future<Output = Result<T, BoxError>>,
+{
+    match tokio::time::timeout(timeout, fut).await {
+        Ok(result) => result,
+        Err(_) => Err("timed out waiting for the client".into()),
+    }
+}
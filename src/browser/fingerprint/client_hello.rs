@@ -0,0 +1,333 @@
+//! Parsing a TLS ClientHello and deriving JA3 and JA4 from it
+
+use std::fmt::Write as _;
+
+use boring::hash::{hash, MessageDigest};
+
+use crate::error::BoxError;
+
+const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+
+const EXT_SERVER_NAME: u16 = 0x0000;
+const EXT_SUPPORTED_GROUPS: u16 = 0x000a;
+const EXT_EC_POINT_FORMATS: u16 = 0x000b;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 0x000d;
+const EXT_ALPN: u16 = 0x0010;
+const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// The fields of a ClientHello that fingerprints are computed from.
+#[derive(Clone, Debug)]
+pub(crate) struct ClientHello {
+    pub(crate) legacy_version: u16,
+    pub(crate) cipher_suites: Vec<u16>,
+    pub(crate) extensions: Vec<(u16, Vec<u8>)>,
+}
+
+impl ClientHello {
+    /// Parses a ClientHello handshake message, including its 4 byte header.
+    pub(crate) fn parse(msg: &[u8]) -> Result<ClientHello, BoxError> {
+        Self::try_parse(msg).ok_or_else(|| "malformed ClientHello".into())
+    }
+
+    /// Parses a ClientHello given either as TLS records or as a bare
+    /// handshake message.
+    pub(crate) fn parse_any(data: &[u8]) -> Result<ClientHello, BoxError> {
+        match data.first() {
+            Some(&CONTENT_TYPE_HANDSHAKE) => {
+                let msg = handshake_from_records(data).ok_or("incomplete ClientHello record")?;
+                ClientHello::parse(&msg)
+            }
+            _ => ClientHello::parse(data),
+        }
+    }
+
+    fn try_parse(msg: &[u8]) -> Option<ClientHello> {
+        let mut r = Reader(msg);
+        if r.u8()? != 1 {
+            return None;
+        }
+        let mut r = Reader(r.take(r.u24()? as usize)?);
+
+        let legacy_version = r.u16()?;
+        r.take(32)?;
+        let session_id_len = r.u8()? as usize;
+        r.take(session_id_len)?;
+
+        let cipher_suites_len = r.u16()? as usize;
+        let mut ciphers = Reader(r.take(cipher_suites_len)?);
+        let mut cipher_suites = Vec::new();
+        while !ciphers.is_empty() {
+            cipher_suites.push(ciphers.u16()?);
+        }
+
+        let compression_len = r.u8()? as usize;
+        r.take(compression_len)?;
+
+        let mut extensions = Vec::new();
+        if !r.is_empty() {
+            let extensions_len = r.u16()? as usize;
+            let mut exts = Reader(r.take(extensions_len)?);
+            while !exts.is_empty() {
+                let ty = exts.u16()?;
+                let len = exts.u16()? as usize;
+                extensions.push((ty, exts.take(len)?.to_vec()));
+            }
+        }
+
+        Some(ClientHello {
+            legacy_version,
+            cipher_suites,
+            extensions,
+        })
+    }
+
+    fn extension(&self, ty: u16) -> Option<&[u8]> {
+        self.extensions
+            .iter()
+            .find(|(t, _)| *t == ty)
+            .map(|(_, data)| &data[..])
+    }
+
+    fn u16_list(&self, ty: u16, len_bytes: usize) -> Vec<u16> {
+        let mut list = Vec::new();
+        if let Some(data) = self.extension(ty) {
+            let mut r = Reader(data.get(len_bytes..).unwrap_or_default());
+            while let Some(v) = r.u16() {
+                list.push(v);
+            }
+        }
+        list
+    }
+
+    pub(crate) fn supported_groups(&self) -> Vec<u16> {
+        self.u16_list(EXT_SUPPORTED_GROUPS, 2)
+    }
+
+    pub(crate) fn signature_algorithms(&self) -> Vec<u16> {
+        self.u16_list(EXT_SIGNATURE_ALGORITHMS, 2)
+    }
+
+    pub(crate) fn supported_versions(&self) -> Vec<u16> {
+        self.u16_list(EXT_SUPPORTED_VERSIONS, 1)
+    }
+
+    pub(crate) fn ec_point_formats(&self) -> Vec<u8> {
+        self.extension(EXT_EC_POINT_FORMATS)
+            .and_then(|data| data.get(1..))
+            .unwrap_or_default()
+            .to_vec()
+    }
+
+    pub(crate) fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        let mut protos = Vec::new();
+        if let Some(data) = self.extension(EXT_ALPN) {
+            let mut r = Reader(data.get(2..).unwrap_or_default());
+            while let Some(len) = r.u8() {
+                match r.take(len as usize) {
+                    Some(proto) => protos.push(proto.to_vec()),
+                    None => break,
+                }
+            }
+        }
+        protos
+    }
+
+    pub(crate) fn server_name(&self) -> Option<String> {
+        let data = self.extension(EXT_SERVER_NAME)?;
+        let mut r = Reader(data.get(2..)?);
+        if r.u8()? != 0 {
+            return None;
+        }
+        let len = r.u16()? as usize;
+        String::from_utf8(r.take(len)?.to_vec()).ok()
+    }
+
+    /// The JA3 string, with extensions in the order they were sent.
+    pub(crate) fn ja3(&self) -> String {
+        let extensions = self.extensions.iter().map(|(ty, _)| *ty).collect();
+        self.ja3_with(extensions)
+    }
+
+    /// The JA3N string, where extensions are sorted so the fingerprint is
+    /// stable across the extension permutation Chrome does.
+    pub(crate) fn ja3n(&self) -> String {
+        let mut extensions: Vec<u16> = self.extensions.iter().map(|(ty, _)| *ty).collect();
+        extensions.sort_unstable();
+        self.ja3_with(extensions)
+    }
+
+    fn ja3_with(&self, extensions: Vec<u16>) -> String {
+        fn join<T: ToString>(values: impl IntoIterator<Item = T>) -> String {
+            values
+                .into_iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join("-")
+        }
+
+        format!(
+            "{},{},{},{},{}",
+            self.legacy_version,
+            join(without_grease(&self.cipher_suites)),
+            join(without_grease(&extensions)),
+            join(without_grease(&self.supported_groups())),
+            join(self.ec_point_formats()),
+        )
+    }
+
+    /// The JA4 fingerprint, as specified for TLS over TCP.
+    pub(crate) fn ja4(&self) -> String {
+        let version = without_grease(&self.supported_versions())
+            .max()
+            .unwrap_or(self.legacy_version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            _ => "00",
+        };
+
+        let sni = if self.extension(EXT_SERVER_NAME).is_some() {
+            'd'
+        } else {
+            'i'
+        };
+
+        let mut ciphers: Vec<u16> = without_grease(&self.cipher_suites).collect();
+        let extension_types: Vec<u16> = self.extensions.iter().map(|(ty, _)| *ty).collect();
+        let mut extensions: Vec<u16> = without_grease(&extension_types).collect();
+
+        let alpn = match self.alpn_protocols().first() {
+            Some(proto) if !proto.is_empty() => {
+                let (first, last) = (proto[0], proto[proto.len() - 1]);
+                if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                    format!("{}{}", first as char, last as char)
+                } else {
+                    let first = format!("{:02x}", first);
+                    let last = format!("{:02x}", last);
+                    format!("{}{}", &first[..1], &last[1..])
+                }
+            }
+            _ => "00".to_owned(),
+        };
+
+        let prefix = format!(
+            "t{}{}{:02}{:02}{}",
+            version,
+            sni,
+            ciphers.len().min(99),
+            extensions.len().min(99),
+            alpn,
+        );
+
+        ciphers.sort_unstable();
+        let cipher_hash = truncated_sha256(&hex_list(&ciphers));
+
+        extensions.retain(|ty| *ty != EXT_SERVER_NAME && *ty != EXT_ALPN);
+        extensions.sort_unstable();
+        let mut extension_input = hex_list(&extensions);
+        let sigalgs = self.signature_algorithms();
+        if !sigalgs.is_empty() {
+            extension_input.push('_');
+            extension_input.push_str(&hex_list(&sigalgs));
+        }
+        let extension_hash = truncated_sha256(&extension_input);
+
+        format!("{}_{}_{}", prefix, cipher_hash, extension_hash)
+    }
+}
+
+/// Reassembles the first handshake message from a sequence of TLS records.
+///
+/// Returns `None` if the records end before the message does.
+pub(crate) fn handshake_from_records(mut records: &[u8]) -> Option<Vec<u8>> {
+    let mut message = Vec::new();
+
+    loop {
+        if message.len() >= 4 {
+            let len = u32::from_be_bytes([0, message[1], message[2], message[3]]) as usize;
+            if message.len() >= 4 + len {
+                message.truncate(4 + len);
+                return Some(message);
+            }
+        }
+
+        let mut r = Reader(records);
+        if r.u8()? != CONTENT_TYPE_HANDSHAKE {
+            return None;
+        }
+        r.u16()?;
+        let len = r.u16()? as usize;
+        message.extend_from_slice(r.take(len)?);
+        records = r.0;
+    }
+}
+
+/// Whether the value is one of the reserved GREASE values of RFC 8701.
+pub(crate) fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn without_grease(values: &[u16]) -> impl Iterator<Item = u16> + '_ {
+    values.iter().copied().filter(|v| !is_grease(*v))
+}
+
+fn hex_list(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{:04x}", v))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn truncated_sha256(input: &str) -> String {
+    if input.is_empty() {
+        return "000000000000".to_owned();
+    }
+    hex(&boring::sha::sha256(input.as_bytes())[..6])
+}
+
+pub(crate) fn md5_hex(input: &str) -> Result<String, BoxError> {
+    Ok(hex(&hash(MessageDigest::md5(), input.as_bytes())?))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(out, "{:02x}", b);
+    }
+    out
+}
+
+/// A cursor over big-endian encoded TLS structures.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<u32> {
+        self.take(3)
+            .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]))
+    }
+}
@@ -0,0 +1,615 @@
+//! Deriving an impersonation profile from a captured browser handshake
+
+use std::fmt;
+
+use super::client_hello::{is_grease, ClientHello};
+use super::pcap;
+use crate::browser::{
+    AlpsCodepoint, AlpsPayload, ApplicationSettings, CertCompressionAlgorithm, ImpersonateProfile,
+    ImpersonateProfileBuilder, Priority, PriorityFrame, PseudoOrder, SettingId,
+};
+use crate::tls;
+
+/// An impersonation profile derived from a browser capture.
+///
+/// The TLS options — cipher suites, supported groups, signature algorithms,
+/// ALPN protocols, certificate compression, ALPS, GREASE, OCSP stapling,
+/// signed certificate timestamps, ECH GREASE and TLS versions — are taken
+/// from a ClientHello, given as raw bytes, inside a pcap file, or as a JA3
+/// string. The HTTP/2 options are taken from an Akamai fingerprint string.
+///
+/// Whatever BoringSSL cannot reproduce is listed by
+/// [`GeneratedProfile::unsupported`] rather than silently dropped:
+///
+/// ```no_run
+/// use reqwest_impersonate::browser::fingerprint::GeneratedProfile;
+///
+/// # fn run() -> Result<(), reqwest_impersonate::Error> {
+/// let capture = std::fs::read("chrome.pcapng").unwrap();
+/// let generated = GeneratedProfile::from_pcap(&capture)?
+///     .with_akamai("1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p")?;
+///
+/// for unsupported in generated.unsupported() {
+///     eprintln!("cannot reproduce {}", unsupported);
+/// }
+///
+/// let profile = generated
+///     .to_builder()
+///     .user_agent("Mozilla/5.0 ...")
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// There is no `from_ja4`. Of a ClientHello, a JA4 fingerprint only keeps
+/// the TLS version, the ends of the first ALPN protocol, and how many cipher
+/// suites and extensions were sent. The cipher suites, extensions and
+/// signature algorithms themselves are hashed, so no profile can be derived
+/// from it.
+///
+/// BoringSSL sends extensions in a fixed order, or in a random one with
+/// `permute_extensions`. Since a single capture cannot tell the two apart,
+/// the generated profile leaves `permute_extensions` disabled; the observed
+/// order is available from [`GeneratedProfile::extension_order`].
+#[derive(Clone, Debug)]
+pub struct GeneratedProfile {
+    profile: ImpersonateProfile,
+    extension_order: Vec<u16>,
+    unsupported: Vec<Unsupported>,
+}
+
+/// Something a capture contains that a profile cannot reproduce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Unsupported {
+    /// A cipher suite BoringSSL doesn't implement.
+    CipherSuite(u16),
+    /// A supported group, e.g. an `ffdhe` group, BoringSSL doesn't implement.
+    Group(u16),
+    /// A signature algorithm BoringSSL doesn't implement.
+    SignatureAlgorithm(u16),
+    /// A certificate compression algorithm without a compressor.
+    CertCompressionAlgorithm(u16),
+    /// A TLS extension BoringSSL cannot be configured to send.
+    Extension(u16),
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Unsupported::CipherSuite(id) => write!(f, "cipher suite 0x{:04x}", id),
+            Unsupported::Group(id) => write!(f, "supported group 0x{:04x}", id),
+            Unsupported::SignatureAlgorithm(id) => {
+                write!(f, "signature algorithm 0x{:04x}", id)
+            }
+            Unsupported::CertCompressionAlgorithm(id) => {
+                write!(f, "certificate compression algorithm {}", id)
+            }
+            Unsupported::Extension(id) => match extension_name(id) {
+                Some(name) => write!(f, "extension {} ({})", name, id),
+                None => write!(f, "extension {}", id),
+            },
+        }
+    }
+}
+
+impl GeneratedProfile {
+    /// Derives the TLS options from a ClientHello.
+    ///
+    /// `data` is either the TLS records the client sent, starting with the
+    /// record header, or the bare handshake message.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if `data` doesn't hold a complete ClientHello.
+    pub fn from_client_hello(data: &[u8]) -> crate::Result<GeneratedProfile> {
+        let hello = ClientHello::parse_any(data).map_err(crate::error::builder)?;
+        Ok(GeneratedProfile::from_hello(&hello))
+    }
+
+    /// Derives the TLS options from the first ClientHello in a packet
+    /// capture, in either the pcap or the pcapng format.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the capture is malformed or holds no complete
+    /// ClientHello.
+    pub fn from_pcap(data: &[u8]) -> crate::Result<GeneratedProfile> {
+        let msg = pcap::first_client_hello(data).map_err(crate::error::builder)?;
+        GeneratedProfile::from_client_hello(&msg)
+    }
+
+    /// Derives the TLS options from a JA3 string.
+    ///
+    /// JA3 only lists which extensions were sent, not their contents, so the
+    /// signature algorithms, ALPN protocols, certificate compression
+    /// algorithms and minimum TLS version are left unset.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if `ja3` is not a valid JA3 string.
+    pub fn from_ja3(ja3: &str) -> crate::Result<GeneratedProfile> {
+        let hello = parse_ja3(ja3)
+            .ok_or("invalid JA3 string")
+            .map_err(crate::error::builder)?;
+        let mut generated = GeneratedProfile::from_hello(&hello);
+
+        // JA3 doesn't record GREASE; Chrome and Safari send it, Firefox doesn't.
+        generated.profile.tls.grease_enabled = false;
+        if hello
+            .extensions
+            .iter()
+            .any(|(ty, _)| *ty == EXT_SUPPORTED_VERSIONS)
+        {
+            generated.profile.tls.min_tls_version = None;
+            generated.profile.tls.max_tls_version = Some(tls::Version::TLS_1_3);
+        }
+        Ok(generated)
+    }
+
+    /// Derives the HTTP/2 options from an Akamai fingerprint string, such as
+    /// `1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p`.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if `akamai` is not a valid Akamai fingerprint.
+    pub fn with_akamai(mut self, akamai: &str) -> crate::Result<GeneratedProfile> {
+        let http2 = parse_akamai(akamai)
+            .ok_or("invalid Akamai fingerprint")
+            .map_err(crate::error::builder)?;
+        self.apply_http2(http2);
+        Ok(self)
+    }
+
+    /// Derives the TLS and HTTP/2 options and the header order from a
+    /// connection recorded by the [`EchoServer`](crate::test_util::EchoServer).
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the recorded ClientHello is malformed.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `test-util` feature enabled.
+    #[cfg(feature = "test-util")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+    pub fn from_capture(capture: &crate::test_util::Capture) -> crate::Result<GeneratedProfile> {
+        let hello = capture
+            .parse_client_hello()
+            .map_err(crate::error::builder)?;
+        let mut generated = GeneratedProfile::from_hello(&hello);
+
+        if let Some(h2) = capture.http2() {
+            generated.apply_http2(Http2Options {
+                settings: h2.settings().to_vec(),
+                window_update: h2.window_update(),
+                priority_frames: h2.priority_frames().to_vec(),
+                pseudo_order: h2.pseudo_order().to_vec(),
+            });
+            generated.profile.http2.headers_priority = h2.headers_priority();
+        }
+        generated.profile.header_order = capture.header_order().to_vec();
+
+        Ok(generated)
+    }
+
+    /// The things in the capture which the profile cannot reproduce.
+    pub fn unsupported(&self) -> &[Unsupported] {
+        &self.unsupported
+    }
+
+    /// The types of the extensions in the order the client sent them,
+    /// without GREASE.
+    pub fn extension_order(&self) -> &[u16] {
+        &self.extension_order
+    }
+
+    /// Creates a builder starting from the generated profile, e.g. to add
+    /// the default headers, which a capture doesn't reveal.
+    pub fn to_builder(&self) -> ImpersonateProfileBuilder {
+        self.profile.to_builder()
+    }
+
+    /// Returns the generated profile.
+    ///
+    /// # Errors
+    ///
+    /// See [`ImpersonateProfileBuilder::build`].
+    pub fn build(&self) -> crate::Result<ImpersonateProfile> {
+        self.to_builder().build()
+    }
+
+    fn from_hello(hello: &ClientHello) -> GeneratedProfile {
+        let mut profile = ImpersonateProfile::empty();
+        let mut unsupported = Vec::new();
+        let tls = &mut profile.tls;
+
+        for id in no_grease(&hello.cipher_suites) {
+            match cipher_name(id) {
+                Some(name) => tls.cipher_list.push(name.to_owned()),
+                None => unsupported.push(Unsupported::CipherSuite(id)),
+            }
+        }
+
+        for id in no_grease(&hello.supported_groups()) {
+            match group_name(id) {
+                Some(name) => tls.curves.push(name.to_owned()),
+                None => unsupported.push(Unsupported::Group(id)),
+            }
+        }
+
+        for id in hello.signature_algorithms() {
+            match sigalg_name(id) {
+                Some(name) => tls.sigalgs_list.push(name.to_owned()),
+                None => unsupported.push(Unsupported::SignatureAlgorithm(id)),
+            }
+        }
+
+        tls.alpn_protos = hello
+            .alpn_protocols()
+            .iter()
+            .map(|proto| String::from_utf8_lossy(proto).into_owned())
+            .collect();
+
+        let versions: Vec<u16> = no_grease(&hello.supported_versions()).collect();
+        let max = versions
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(hello.legacy_version);
+        tls.max_tls_version = tls_version(max);
+        tls.min_tls_version = versions.iter().copied().min().and_then(tls_version);
+
+        let mut extension_order = Vec::new();
+        for (ty, data) in &hello.extensions {
+            if is_grease(*ty) {
+                tls.grease_enabled = true;
+                continue;
+            }
+            extension_order.push(*ty);
+
+            match *ty {
+                EXT_STATUS_REQUEST => tls.ocsp_stapling = true,
+                EXT_SIGNED_CERT_TIMESTAMP => tls.signed_cert_timestamps = true,
+                EXT_APPLICATION_SETTINGS | EXT_APPLICATION_SETTINGS_NEW => {
+                    tls.application_settings = Some(application_settings(*ty, data));
+                }
+                EXT_ENCRYPTED_CLIENT_HELLO => tls.enable_ech_grease = true,
+                EXT_COMPRESS_CERTIFICATE => {
+                    let ids = data.get(1..).unwrap_or_default().chunks_exact(2);
+                    for id in ids.map(|b| u16::from_be_bytes([b[0], b[1]])) {
+                        match CertCompressionAlgorithm::from_id(id) {
+                            Some(algorithm) => tls.cert_compression_algorithms.push(algorithm),
+                            None => unsupported.push(Unsupported::CertCompressionAlgorithm(id)),
+                        }
+                    }
+                }
+                ty if SENT_BY_DEFAULT.contains(&ty) => {}
+                ty => unsupported.push(Unsupported::Extension(ty)),
+            }
+        }
+
+        GeneratedProfile {
+            profile,
+            extension_order,
+            unsupported,
+        }
+    }
+
+    fn apply_http2(&mut self, options: Http2Options) {
+        let http2 = &mut self.profile.http2;
+        http2.settings_order.clear();
+        http2.extra_settings.clear();
+
+        for (id, value) in options.settings {
+            let setting = match id {
+                0x1 => {
+                    http2.header_table_size = Some(value);
+                    SettingId::HeaderTableSize
+                }
+                0x2 => {
+                    http2.enable_push = Some(value != 0);
+                    SettingId::EnablePush
+                }
+                0x3 => {
+                    http2.max_concurrent_streams = Some(value);
+                    SettingId::MaxConcurrentStreams
+                }
+                0x4 => {
+                    http2.initial_stream_window_size = Some(value);
+                    SettingId::InitialWindowSize
+                }
+                0x5 => {
+                    http2.max_frame_size = Some(value);
+                    SettingId::MaxFrameSize
+                }
+                0x6 => {
+                    http2.max_header_list_size = Some(value);
+                    SettingId::MaxHeaderListSize
+                }
+                _ => {
+                    http2.extra_settings.push((id, value));
+                    continue;
+                }
+            };
+            http2.settings_order.push(setting);
+        }
+
+        // The increment is on top of the initial window every connection has.
+        http2.initial_connection_window_size = options
+            .window_update
+            .map(|increment| increment.saturating_add(DEFAULT_WINDOW_SIZE));
+        http2.priority_frames = options.priority_frames;
+        http2.headers_pseudo_order = options.pseudo_order.try_into().ok();
+    }
+}
+
+/// The HTTP/2 options both the Akamai string and a capture provide.
+struct Http2Options {
+    settings: Vec<(u16, u32)>,
+    window_update: Option<u32>,
+    priority_frames: Vec<PriorityFrame>,
+    pseudo_order: Vec<PseudoOrder>,
+}
+
+const DEFAULT_WINDOW_SIZE: u32 = 65_535;
+
+fn parse_akamai(akamai: &str) -> Option<Http2Options> {
+    let mut parts = akamai.trim().split('|');
+    let (settings, window_update, priority, pseudo_order) =
+        (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let settings = settings
+        .split(';')
+        .filter(|s| !s.is_empty())
+        .map(|setting| {
+            let (id, value) = setting.split_once(':')?;
+            Some((id.parse().ok()?, value.parse().ok()?))
+        })
+        .collect::<Option<_>>()?;
+
+    let window_update = match window_update {
+        "00" => None,
+        increment => Some(increment.parse().ok()?),
+    };
+
+    let priority_frames = match priority {
+        "0" => Vec::new(),
+        frames => frames
+            .split(',')
+            .map(|frame| {
+                let mut fields = frame.split(':');
+                let stream_id = fields.next()?.parse().ok()?;
+                let exclusive = fields.next()? == "1";
+                let stream_dependency = fields.next()?.parse().ok()?;
+                let weight = fields.next()?.parse().ok()?;
+                Some(PriorityFrame {
+                    stream_id,
+                    priority: Priority {
+                        stream_dependency,
+                        weight,
+                        exclusive,
+                    },
+                })
+            })
+            .collect::<Option<_>>()?,
+    };
+
+    let pseudo_order = pseudo_order
+        .split(',')
+        .map(|pseudo| match pseudo {
+            "m" => Some(PseudoOrder::Method),
+            "a" => Some(PseudoOrder::Authority),
+            "s" => Some(PseudoOrder::Scheme),
+            "p" => Some(PseudoOrder::Path),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    Some(Http2Options {
+        settings,
+        window_update,
+        priority_frames,
+        pseudo_order,
+    })
+}
+
+/// Builds a ClientHello holding what a JA3 string records: the version, the
+/// cipher suites, the extension types, and the contents of the supported
+/// groups and point formats extensions.
+fn parse_ja3(ja3: &str) -> Option<ClientHello> {
+    fn list<T: std::str::FromStr>(field: &str) -> Option<Vec<T>> {
+        field
+            .split('-')
+            .filter(|s| !s.is_empty())
+            .map(|v| v.parse().ok())
+            .collect()
+    }
+
+    let fields: Vec<&str> = ja3.trim().split(',').collect();
+    if fields.len() != 5 {
+        return None;
+    }
+    let (version, ciphers, extensions, groups, formats) =
+        (fields[0], fields[1], fields[2], fields[3], fields[4]);
+
+    let groups: Vec<u16> = list(groups)?;
+    let formats: Vec<u8> = list(formats)?;
+    let extensions = list::<u16>(extensions)?
+        .into_iter()
+        .map(|ty| {
+            let data = match ty {
+                EXT_SUPPORTED_GROUPS => {
+                    let mut data = ((groups.len() * 2) as u16).to_be_bytes().to_vec();
+                    groups
+                        .iter()
+                        .for_each(|g| data.extend_from_slice(&g.to_be_bytes()));
+                    data
+                }
+                EXT_EC_POINT_FORMATS => {
+                    let mut data = vec![formats.len() as u8];
+                    data.extend_from_slice(&formats);
+                    data
+                }
+                _ => Vec::new(),
+            };
+            (ty, data)
+        })
+        .collect();
+
+    Some(ClientHello {
+        legacy_version: version.parse().ok()?,
+        cipher_suites: list(ciphers)?,
+        extensions,
+    })
+}
+
+/// Maps an ALPS extension to the profile's settings.
+///
+/// The payload itself is sent encrypted, so it can't be read from the
+/// ClientHello; the one Chrome sends, its HTTP/2 `SETTINGS`, is assumed.
+/// Without the extension contents, as from a JA3 string, ALPS is assumed to
+/// be offered for `h2`.
+fn application_settings(ty: u16, data: &[u8]) -> ApplicationSettings {
+    let mut protocols = Vec::new();
+    let mut list = data.get(2..).unwrap_or_default();
+    while let Some((&len, rest)) = list.split_first() {
+        let len = usize::from(len);
+        if rest.len() < len {
+            break;
+        }
+        protocols.push(String::from_utf8_lossy(&rest[..len]).into_owned());
+        list = &rest[len..];
+    }
+    if protocols.is_empty() {
+        protocols.push("h2".to_owned());
+    }
+
+    let codepoint = if ty == EXT_APPLICATION_SETTINGS_NEW {
+        AlpsCodepoint::New
+    } else {
+        AlpsCodepoint::Old
+    };
+    ApplicationSettings::new(protocols)
+        .with_payload(AlpsPayload::Http2Settings)
+        .with_codepoint(codepoint)
+}
+
+fn no_grease(values: &[u16]) -> impl Iterator<Item = u16> + '_ {
+    values.iter().copied().filter(|v| !is_grease(*v))
+}
+
+fn tls_version(version: u16) -> Option<tls::Version> {
+    match version {
+        0x0301 => Some(tls::Version::TLS_1_0),
+        0x0302 => Some(tls::Version::TLS_1_1),
+        0x0303 => Some(tls::Version::TLS_1_2),
+        0x0304 => Some(tls::Version::TLS_1_3),
+        _ => None,
+    }
+}
+
+const EXT_STATUS_REQUEST: u16 = 5;
+const EXT_SUPPORTED_GROUPS: u16 = 10;
+const EXT_EC_POINT_FORMATS: u16 = 11;
+const EXT_SIGNED_CERT_TIMESTAMP: u16 = 18;
+const EXT_COMPRESS_CERTIFICATE: u16 = 27;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+const EXT_APPLICATION_SETTINGS: u16 = 17513;
+const EXT_APPLICATION_SETTINGS_NEW: u16 = 17613;
+const EXT_ENCRYPTED_CLIENT_HELLO: u16 = 65037;
+
+/// Extensions BoringSSL sends on its own, or derives from options handled
+/// separately, such as the supported groups and signature algorithms.
+const SENT_BY_DEFAULT: &[u16] = &[
+    0,     // server_name
+    10,    // supported_groups
+    11,    // ec_point_formats
+    13,    // signature_algorithms
+    16,    // application_layer_protocol_negotiation
+    21,    // padding
+    23,    // extended_master_secret
+    35,    // session_ticket
+    43,    // supported_versions
+    45,    // psk_key_exchange_modes
+    51,    // key_share
+    65281, // renegotiation_info
+];
+
+fn extension_name(id: u16) -> Option<&'static str> {
+    Some(match id {
+        17 => "status_request_v2",
+        22 => "encrypt_then_mac",
+        28 => "record_size_limit",
+        34 => "delegated_credentials",
+        41 => "pre_shared_key",
+        42 => "early_data",
+        49 => "post_handshake_auth",
+        50 => "signature_algorithms_cert",
+        _ => return None,
+    })
+}
+
+fn cipher_name(id: u16) -> Option<&'static str> {
+    Some(match id {
+        0x1301 => "TLS_AES_128_GCM_SHA256",
+        0x1302 => "TLS_AES_256_GCM_SHA384",
+        0x1303 => "TLS_CHACHA20_POLY1305_SHA256",
+        0xc02b => "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+        0xc02f => "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+        0xc02c => "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+        0xc030 => "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+        0xcca9 => "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+        0xcca8 => "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+        0xc009 => "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA",
+        0xc00a => "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA",
+        0xc013 => "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA",
+        0xc014 => "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA",
+        0xc023 => "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA256",
+        0xc027 => "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256",
+        0xc035 => "TLS_ECDHE_PSK_WITH_AES_128_CBC_SHA",
+        0xc036 => "TLS_ECDHE_PSK_WITH_AES_256_CBC_SHA",
+        0xccac => "TLS_ECDHE_PSK_WITH_CHACHA20_POLY1305_SHA256",
+        0x009c => "TLS_RSA_WITH_AES_128_GCM_SHA256",
+        0x009d => "TLS_RSA_WITH_AES_256_GCM_SHA384",
+        0x002f => "TLS_RSA_WITH_AES_128_CBC_SHA",
+        0x0035 => "TLS_RSA_WITH_AES_256_CBC_SHA",
+        0x000a => "TLS_RSA_WITH_3DES_EDE_CBC_SHA",
+        0x008c => "TLS_PSK_WITH_AES_128_CBC_SHA",
+        0x008d => "TLS_PSK_WITH_AES_256_CBC_SHA",
+        _ => return None,
+    })
+}
+
+fn group_name(id: u16) -> Option<&'static str> {
+    Some(match id {
+        0x0017 => "P-256",
+        0x0018 => "P-384",
+        0x0019 => "P-521",
+        0x001d => "X25519",
+        0x6399 => "X25519Kyber768Draft00",
+        0x11ec => "X25519MLKEM768",
+        _ => return None,
+    })
+}
+
+fn sigalg_name(id: u16) -> Option<&'static str> {
+    Some(match id {
+        0x0201 => "rsa_pkcs1_sha1",
+        0x0203 => "ecdsa_sha1",
+        0x0401 => "rsa_pkcs1_sha256",
+        0x0403 => "ecdsa_secp256r1_sha256",
+        0x0501 => "rsa_pkcs1_sha384",
+        0x0503 => "ecdsa_secp384r1_sha384",
+        0x0601 => "rsa_pkcs1_sha512",
+        0x0603 => "ecdsa_secp521r1_sha512",
+        0x0804 => "rsa_pss_rsae_sha256",
+        0x0805 => "rsa_pss_rsae_sha384",
+        0x0806 => "rsa_pss_rsae_sha512",
+        0x0807 => "ed25519",
+        _ => return None,
+    })
+}
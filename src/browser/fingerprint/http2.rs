@@ -0,0 +1,241 @@
+//! Parsing the frames an HTTP/2 client sends when opening a connection
+
+use crate::browser::{Priority, PriorityFrame, PseudoOrder};
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const FRAME_HEADERS: u8 = 0x1;
+const FRAME_PRIORITY: u8 = 0x2;
+const FRAME_SETTINGS: u8 = 0x4;
+const FRAME_WINDOW_UPDATE: u8 = 0x8;
+
+const FLAG_ACK: u8 = 0x1;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+/// What a client sent on a new HTTP/2 connection, up to and including the
+/// `HEADERS` frame of its first request.
+#[derive(Clone, Debug, Default)]
+pub struct Http2Capture {
+    pub(crate) settings: Vec<(u16, u32)>,
+    pub(crate) window_update: Option<u32>,
+    pub(crate) priority_frames: Vec<PriorityFrame>,
+    pub(crate) headers_priority: Option<Priority>,
+    pub(crate) pseudo_order: Vec<PseudoOrder>,
+}
+
+impl Http2Capture {
+    /// The id and value of each setting of the first `SETTINGS` frame, in
+    /// the order they were sent.
+    pub fn settings(&self) -> &[(u16, u32)] {
+        &self.settings
+    }
+
+    /// The increment of the connection level `WINDOW_UPDATE` frame, if one
+    /// was sent.
+    pub fn window_update(&self) -> Option<u32> {
+        self.window_update
+    }
+
+    /// The `PRIORITY` frames sent before the first request.
+    pub fn priority_frames(&self) -> &[PriorityFrame] {
+        &self.priority_frames
+    }
+
+    /// The priority sent in the `HEADERS` frame of the first request.
+    pub fn headers_priority(&self) -> Option<Priority> {
+        self.headers_priority
+    }
+
+    /// The order of the pseudo-headers of the first request.
+    pub fn pseudo_order(&self) -> &[PseudoOrder] {
+        &self.pseudo_order
+    }
+
+    /// Parses the bytes a client sent, starting with the connection preface.
+    pub(crate) fn parse(mut data: &[u8]) -> Option<Http2Capture> {
+        data = data.strip_prefix(PREFACE)?;
+
+        let mut capture = Http2Capture::default();
+        while data.len() >= 9 {
+            let len = u32::from_be_bytes([0, data[0], data[1], data[2]]) as usize;
+            let (ty, flags) = (data[3], data[4]);
+            let stream_id = u32::from_be_bytes([data[5], data[6], data[7], data[8]]) & 0x7fff_ffff;
+            let payload = data.get(9..9 + len)?;
+            data = &data[9 + len..];
+
+            match ty {
+                FRAME_SETTINGS if flags & FLAG_ACK == 0 => {
+                    for setting in payload.chunks_exact(6) {
+                        let id = u16::from_be_bytes([setting[0], setting[1]]);
+                        let value =
+                            u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+                        capture.settings.push((id, value));
+                    }
+                }
+                FRAME_WINDOW_UPDATE if stream_id == 0 && payload.len() == 4 => {
+                    let increment =
+                        u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]);
+                    capture.window_update = Some(increment & 0x7fff_ffff);
+                }
+                FRAME_PRIORITY if payload.len() == 5 => {
+                    capture.priority_frames.push(PriorityFrame {
+                        stream_id,
+                        priority: parse_priority(payload),
+                    });
+                }
+                FRAME_HEADERS => {
+                    let mut block = payload;
+                    if flags & FLAG_PADDED != 0 {
+                        let pad = *block.first()? as usize;
+                        block = block.get(1..block.len().checked_sub(pad)?)?;
+                    }
+                    if flags & FLAG_PRIORITY != 0 {
+                        capture.headers_priority = Some(parse_priority(block.get(..5)?));
+                        block = &block[5..];
+                    }
+                    capture.pseudo_order = pseudo_order(block);
+                    return Some(capture);
+                }
+                _ => {}
+            }
+        }
+
+        Some(capture)
+    }
+
+    /// The Akamai HTTP/2 fingerprint string.
+    pub(crate) fn akamai(&self) -> String {
+        let settings = self
+            .settings
+            .iter()
+            .map(|(id, value)| format!("{}:{}", id, value))
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let window_update = match self.window_update {
+            Some(increment) => increment.to_string(),
+            None => "00".to_owned(),
+        };
+
+        let priority = if self.priority_frames.is_empty() {
+            "0".to_owned()
+        } else {
+            self.priority_frames
+                .iter()
+                .map(|frame| {
+                    format!(
+                        "{}:{}:{}:{}",
+                        frame.stream_id,
+                        frame.priority.exclusive as u8,
+                        frame.priority.stream_dependency,
+                        frame.priority.weight,
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let pseudo_order = self
+            .pseudo_order
+            .iter()
+            .map(|pseudo| match pseudo {
+                PseudoOrder::Method => "m",
+                PseudoOrder::Authority => "a",
+                PseudoOrder::Scheme => "s",
+                PseudoOrder::Path => "p",
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "{}|{}|{}|{}",
+            settings, window_update, priority, pseudo_order
+        )
+    }
+}
+
+fn parse_priority(payload: &[u8]) -> Priority {
+    let dependency = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]);
+    Priority {
+        stream_dependency: dependency & 0x7fff_ffff,
+        weight: u16::from(payload[4]) + 1,
+        exclusive: dependency & 0x8000_0000 != 0,
+    }
+}
+
+/// Reads the order of the pseudo-headers at the start of an HPACK block.
+///
+/// Pseudo-headers always come first, and their names are always taken from
+/// the static table, so neither the dynamic table nor Huffman decoding is
+/// needed. Decoding stops at the first regular header.
+fn pseudo_order(mut block: &[u8]) -> Vec<PseudoOrder> {
+    let mut order = Vec::new();
+
+    while let Some(&first) = block.first() {
+        let (index, has_value) = if first & 0x80 != 0 {
+            // Indexed header field
+            (decode_int(&mut block, 7), false)
+        } else if first & 0xc0 == 0x40 {
+            // Literal header field with incremental indexing
+            (decode_int(&mut block, 6), true)
+        } else if first & 0xe0 == 0x20 {
+            // Dynamic table size update
+            if decode_int(&mut block, 5).is_none() {
+                break;
+            }
+            continue;
+        } else {
+            // Literal header field without indexing, or never indexed
+            (decode_int(&mut block, 4), true)
+        };
+
+        let pseudo = match index {
+            Some(1) => PseudoOrder::Authority,
+            Some(2) | Some(3) => PseudoOrder::Method,
+            Some(4) | Some(5) => PseudoOrder::Path,
+            Some(6) | Some(7) => PseudoOrder::Scheme,
+            _ => break,
+        };
+        order.push(pseudo);
+
+        if has_value && !skip_string(&mut block) {
+            break;
+        }
+    }
+
+    order
+}
+
+/// Decodes an HPACK integer with an N-bit prefix (RFC 7541, section 5.1).
+fn decode_int(block: &mut &[u8], prefix_bits: u8) -> Option<usize> {
+    let mask = (1u16 << prefix_bits) as usize - 1;
+    let (&first, mut rest) = block.split_first()?;
+    let mut value = first as usize & mask;
+
+    if value == mask {
+        let mut shift = 0;
+        loop {
+            let (&b, tail) = rest.split_first()?;
+            rest = tail;
+            value = value.checked_add(((b & 0x7f) as usize).checked_shl(shift)?)?;
+            shift += 7;
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+
+    *block = rest;
+    Some(value)
+}
+
+fn skip_string(block: &mut &[u8]) -> bool {
+    match decode_int(block, 7) {
+        Some(len) if len <= block.len() => {
+            *block = &block[len..];
+            true
+        }
+        _ => false,
+    }
+}
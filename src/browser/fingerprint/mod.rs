@@ -0,0 +1,179 @@
+//! Computing the TLS and HTTP/2 fingerprints a client presents
+//!
+//! Servers tell clients apart by how they open a connection: the
+//! [JA3](https://github.com/salesforce/ja3) and
+//! [JA4](https://github.com/FoxIO-LLC/ja4) fingerprints summarize the TLS
+//! ClientHello, while the Akamai fingerprint summarizes the HTTP/2 SETTINGS,
+//! WINDOW_UPDATE and PRIORITY frames and the pseudo-header order.
+//!
+//! [`Fingerprint`] computes these for a client by letting it connect to a
+//! server on the loopback interface and recording what it sends, so a
+//! profile can be checked without reaching out to an external echo site:
+//!
+//! ```no_run
+//! use reqwest_impersonate::browser::{fingerprint::Fingerprint, ChromeVersion};
+//!
+//! # async fn run() -> Result<(), reqwest_impersonate::Error> {
+//! let fingerprint = Fingerprint::of_profile(ChromeVersion::V120.profile()).await?;
+//! assert_eq!(
+//!     fingerprint.akamai(),
+//!     Some("1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p")
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use futures_util::future::Either;
+
+use crate::browser::ImpersonateProfile;
+use crate::error::BoxError;
+use crate::{Client, ClientBuilder};
+
+use self::capture::Listener;
+pub use self::generate::{GeneratedProfile, Unsupported};
+
+pub(crate) mod capture;
+mod client_hello;
+mod generate;
+pub(crate) mod http2;
+mod pcap;
+
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The fingerprints of a client's TLS ClientHello and HTTP/2 connection.
+#[derive(Clone, Debug)]
+pub struct Fingerprint {
+    ja3: String,
+    ja3_hash: String,
+    ja3n: String,
+    ja3n_hash: String,
+    ja4: String,
+    akamai: Option<String>,
+}
+
+impl Fingerprint {
+    /// Computes the fingerprints of an already built `Client`.
+    ///
+    /// The client makes a `GET` request to `https://localhost` on a random
+    /// port. A client which verifies certificates rejects the self-signed
+    /// one of the loopback server, so only the TLS fingerprints can be
+    /// computed and [`Fingerprint::akamai`] is `None`. Use
+    /// [`Fingerprint::of_builder`] to get all of them.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the loopback server cannot be started, or the
+    /// client doesn't send a ClientHello within 10 seconds, e.g. because it
+    /// is configured to use a proxy.
+    pub async fn of_client(client: &Client) -> crate::Result<Fingerprint> {
+        let listener = Listener::bind().await.map_err(crate::error::builder)?;
+        let port = listener.local_addr().map_err(crate::error::builder)?.port();
+        Fingerprint::capture(client, &listener, port).await
+    }
+
+    /// Computes the fingerprints of the `Client` the `ClientBuilder` builds.
+    ///
+    /// Certificate verification and proxies are disabled, so the handshake
+    /// with the loopback server completes and the HTTP/2 frames are seen
+    /// too. Neither affects what the client sends.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the builder fails to build a `Client`, or for
+    /// the reasons [`Fingerprint::of_client`] fails.
+    pub async fn of_builder(builder: ClientBuilder) -> crate::Result<Fingerprint> {
+        let listener = Listener::bind().await.map_err(crate::error::builder)?;
+        let addr = listener.local_addr().map_err(crate::error::builder)?;
+        let client = builder
+            .danger_accept_invalid_certs(true)
+            .resolve("localhost", addr)
+            .no_proxy()
+            .build()?;
+        Fingerprint::capture(&client, &listener, addr.port()).await
+    }
+
+    /// Computes the fingerprints of a `Client` impersonating `profile`.
+    ///
+    /// # Errors
+    ///
+    /// See [`Fingerprint::of_builder`].
+    pub async fn of_profile(profile: ImpersonateProfile) -> crate::Result<Fingerprint> {
+        Fingerprint::of_builder(Client::builder().impersonate(profile)).await
+    }
+
+    async fn capture(
+        client: &Client,
+        listener: &Listener,
+        port: u16,
+    ) -> crate::Result<Fingerprint> {
+        let url = format!("https://localhost:{}/", port);
+        let request = client.get(&url).send();
+        let capture = capture::with_timeout(CAPTURE_TIMEOUT, listener.capture());
+        futures_util::pin_mut!(request, capture);
+
+        // The request is dropped as soon as the capture ends, as nothing may
+        // ever answer it. It fails early when the client rejects the
+        // certificate, which still leaves the ClientHello to compute
+        // fingerprints from.
+        let capture = match futures_util::future::select(capture, request).await {
+            Either::Left((capture, _)) => capture,
+            Either::Right((_, capture)) => capture.await,
+        };
+
+        capture.map_err(crate::error::request)?.fingerprint()
+    }
+
+    fn from_capture(capture: &capture::Capture) -> Result<Fingerprint, BoxError> {
+        let hello = capture.parse_client_hello()?;
+        let ja3 = hello.ja3();
+        let ja3n = hello.ja3n();
+
+        Ok(Fingerprint {
+            ja3_hash: client_hello::md5_hex(&ja3)?,
+            ja3n_hash: client_hello::md5_hex(&ja3n)?,
+            ja3,
+            ja3n,
+            ja4: hello.ja4(),
+            akamai: capture.http2.as_ref().map(|h2| h2.akamai()),
+        })
+    }
+
+    /// The JA3 string, e.g. `771,4865-4866-...,0-23-...,29-23-24,0`.
+    ///
+    /// GREASE values are left out. Since Chrome permutes its extensions on
+    /// every handshake, [`Fingerprint::ja3n`] is more useful to compare.
+    pub fn ja3(&self) -> &str {
+        &self.ja3
+    }
+
+    /// The MD5 hash of the JA3 string, as a lowercase hex string.
+    pub fn ja3_hash(&self) -> &str {
+        &self.ja3_hash
+    }
+
+    /// The JA3N string, which is the JA3 string with sorted extensions.
+    pub fn ja3n(&self) -> &str {
+        &self.ja3n
+    }
+
+    /// The MD5 hash of the JA3N string, as a lowercase hex string.
+    pub fn ja3n_hash(&self) -> &str {
+        &self.ja3n_hash
+    }
+
+    /// The JA4 fingerprint, e.g. `t13d1516h2_8daaf6152771_b1ff8ab2d16f`.
+    pub fn ja4(&self) -> &str {
+        &self.ja4
+    }
+
+    /// The Akamai HTTP/2 fingerprint, e.g.
+    /// `1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p`.
+    ///
+    /// This is `None` if the client didn't complete the TLS handshake or
+    /// didn't negotiate HTTP/2.
+    pub fn akamai(&self) -> Option<&str> {
+        self.akamai.as_deref()
+    }
+}
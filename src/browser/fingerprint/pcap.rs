@@ -0,0 +1,262 @@
+//! Extracting the first TLS ClientHello from a packet capture
+//!
+//! Both the classic pcap and the pcapng formats are read, with Ethernet,
+//! loopback, raw IP and Linux cooked link layers. Only TCP over IPv4 or IPv6
+//! without extension headers is followed, which is what browsers produce.
+
+use std::collections::HashMap;
+
+use super::client_hello::handshake_from_records;
+use crate::error::BoxError;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+// Some systems write raw IP with the DLT value rather than the link type.
+const DLT_RAW: u32 = 12;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+const IPPROTO_TCP: u8 = 6;
+
+/// Returns the first complete ClientHello handshake message in the capture.
+pub(crate) fn first_client_hello(data: &[u8]) -> Result<Vec<u8>, BoxError> {
+    let mut flows = Flows::default();
+    let mut found = None;
+
+    let mut on_packet = |linktype: u32, frame: &[u8]| {
+        if found.is_none() {
+            if let Some(segment) = tcp_segment(linktype, frame) {
+                found = flows.push(segment);
+            }
+        }
+    };
+
+    match data.get(..4) {
+        Some([0x0a, 0x0d, 0x0d, 0x0a]) => read_pcapng(data, &mut on_packet)?,
+        Some(_) => read_pcap(data, &mut on_packet)?,
+        None => return Err("capture file too short".into()),
+    }
+
+    found.ok_or_else(|| "no TLS ClientHello found in the capture".into())
+}
+
+fn read_pcap(data: &[u8], on_packet: &mut dyn FnMut(u32, &[u8])) -> Result<(), BoxError> {
+    let magic = [data[0], data[1], data[2], data[3]];
+    let big_endian = match magic {
+        [0xa1, 0xb2, 0xc3, 0xd4] | [0xa1, 0xb2, 0x3c, 0x4d] => true,
+        [0xd4, 0xc3, 0xb2, 0xa1] | [0x4d, 0x3c, 0xb2, 0xa1] => false,
+        _ => return Err("not a pcap or pcapng file".into()),
+    };
+    let u32_at = |buf: &[u8], at: usize| read_u32(buf, at, big_endian);
+
+    let linktype = u32_at(data, 20).ok_or("truncated pcap header")? & 0x0fff_ffff;
+    let mut rest = &data[24..];
+    while rest.len() >= 16 {
+        let len = u32_at(rest, 8).ok_or("truncated pcap record")? as usize;
+        let frame = rest.get(16..16 + len).ok_or("truncated pcap record")?;
+        on_packet(linktype, frame);
+        rest = &rest[16 + len..];
+    }
+    Ok(())
+}
+
+fn read_pcapng(data: &[u8], on_packet: &mut dyn FnMut(u32, &[u8])) -> Result<(), BoxError> {
+    const BLOCK_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+    const BLOCK_INTERFACE: u32 = 1;
+    const BLOCK_SIMPLE_PACKET: u32 = 3;
+    const BLOCK_ENHANCED_PACKET: u32 = 6;
+
+    let mut big_endian = false;
+    let mut linktypes = Vec::new();
+    let mut rest = data;
+
+    while rest.len() >= 12 {
+        // The byte order is only known once the section header is read.
+        if rest[..4] == [0x0a, 0x0d, 0x0d, 0x0a] {
+            big_endian = match rest.get(8..12) {
+                Some([0x1a, 0x2b, 0x3c, 0x4d]) => true,
+                Some([0x4d, 0x3c, 0x2b, 0x1a]) => false,
+                _ => return Err("invalid pcapng byte order magic".into()),
+            };
+        }
+
+        let ty = read_u32(rest, 0, big_endian).ok_or("truncated pcapng block")?;
+        let len = read_u32(rest, 4, big_endian).ok_or("truncated pcapng block")? as usize;
+        if len < 12 || len > rest.len() {
+            return Err("truncated pcapng block".into());
+        }
+        let body = &rest[8..len - 4];
+
+        match ty {
+            BLOCK_SECTION_HEADER => linktypes.clear(),
+            BLOCK_INTERFACE => {
+                let linktype = read_u16(body, 0, big_endian).ok_or("truncated pcapng block")?;
+                linktypes.push(u32::from(linktype));
+            }
+            BLOCK_ENHANCED_PACKET => {
+                let interface = read_u32(body, 0, big_endian).ok_or("truncated pcapng block")?;
+                let cap_len = read_u32(body, 12, big_endian).ok_or("truncated pcapng block")?;
+                let frame = body
+                    .get(20..20 + cap_len as usize)
+                    .ok_or("truncated pcapng block")?;
+                if let Some(&linktype) = linktypes.get(interface as usize) {
+                    on_packet(linktype, frame);
+                }
+            }
+            BLOCK_SIMPLE_PACKET => {
+                if let (Some(&linktype), Some(frame)) = (linktypes.first(), body.get(4..)) {
+                    on_packet(linktype, frame);
+                }
+            }
+            _ => {}
+        }
+
+        rest = &rest[len..];
+    }
+    Ok(())
+}
+
+/// A TCP segment, keyed by its direction.
+struct Segment<'a> {
+    flow: Vec<u8>,
+    seq: u32,
+    payload: &'a [u8],
+}
+
+fn tcp_segment(linktype: u32, frame: &[u8]) -> Option<Segment<'_>> {
+    let packet = match linktype {
+        LINKTYPE_ETHERNET => {
+            let mut ethertype = read_u16(frame, 12, true)?;
+            let mut offset = 14;
+            if ethertype == ETHERTYPE_VLAN {
+                ethertype = read_u16(frame, 16, true)?;
+                offset = 18;
+            }
+            ip_packet(ethertype, frame.get(offset..)?)?
+        }
+        LINKTYPE_NULL => {
+            // The address family is in the byte order of the capturing host.
+            let family = read_u32(frame, 0, false)?;
+            let family = if family > 0xffff {
+                family.swap_bytes()
+            } else {
+                family
+            };
+            let ethertype = match family {
+                2 => ETHERTYPE_IPV4,
+                24 | 28 | 30 => ETHERTYPE_IPV6,
+                _ => return None,
+            };
+            ip_packet(ethertype, frame.get(4..)?)?
+        }
+        LINKTYPE_RAW | DLT_RAW => {
+            let ethertype = match frame.first()? >> 4 {
+                4 => ETHERTYPE_IPV4,
+                6 => ETHERTYPE_IPV6,
+                _ => return None,
+            };
+            ip_packet(ethertype, frame)?
+        }
+        LINKTYPE_LINUX_SLL => ip_packet(read_u16(frame, 14, true)?, frame.get(16..)?)?,
+        LINKTYPE_LINUX_SLL2 => ip_packet(read_u16(frame, 0, true)?, frame.get(20..)?)?,
+        _ => return None,
+    };
+
+    let (mut flow, tcp) = packet;
+    let header_len = usize::from(tcp.get(12)? >> 4) * 4;
+    flow.extend_from_slice(tcp.get(..4)?);
+
+    Some(Segment {
+        flow,
+        seq: read_u32(tcp, 4, true)?,
+        payload: tcp.get(header_len..)?,
+    })
+}
+
+/// Returns the source and destination addresses and the TCP header and
+/// payload of an IP packet.
+fn ip_packet(ethertype: u16, packet: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    match ethertype {
+        ETHERTYPE_IPV4 => {
+            let header_len = usize::from(packet.first()? & 0x0f) * 4;
+            let total_len = usize::from(read_u16(packet, 2, true)?);
+            if *packet.get(9)? != IPPROTO_TCP {
+                return None;
+            }
+            let addrs = packet.get(12..20)?.to_vec();
+            Some((addrs, packet.get(header_len..total_len)?))
+        }
+        ETHERTYPE_IPV6 => {
+            let payload_len = usize::from(read_u16(packet, 4, true)?);
+            if *packet.get(6)? != IPPROTO_TCP {
+                return None;
+            }
+            let addrs = packet.get(8..40)?.to_vec();
+            Some((addrs, packet.get(40..40 + payload_len)?))
+        }
+        _ => None,
+    }
+}
+
+/// The client-to-server byte streams which started with a ClientHello.
+#[derive(Default)]
+struct Flows {
+    streams: HashMap<Vec<u8>, (u32, Vec<u8>)>,
+}
+
+impl Flows {
+    fn push(&mut self, segment: Segment<'_>) -> Option<Vec<u8>> {
+        if segment.payload.is_empty() {
+            return None;
+        }
+
+        let next_seq = segment.seq.wrapping_add(segment.payload.len() as u32);
+        match self.streams.get_mut(&segment.flow) {
+            Some((expected, data)) => {
+                // Retransmitted and out of order segments are skipped.
+                if *expected != segment.seq {
+                    return None;
+                }
+                *expected = next_seq;
+                data.extend_from_slice(segment.payload);
+                handshake_from_records(data)
+            }
+            None => {
+                if !starts_client_hello(segment.payload) {
+                    return None;
+                }
+                let data = segment.payload.to_vec();
+                let client_hello = handshake_from_records(&data);
+                self.streams.insert(segment.flow, (next_seq, data));
+                client_hello
+            }
+        }
+    }
+}
+
+fn starts_client_hello(payload: &[u8]) -> bool {
+    matches!(payload, [22, 3, _, _, _, 1, ..])
+}
+
+fn read_u16(buf: &[u8], at: usize, big_endian: bool) -> Option<u16> {
+    let bytes = [*buf.get(at)?, *buf.get(at + 1)?];
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn read_u32(buf: &[u8], at: usize, big_endian: bool) -> Option<u32> {
+    let bytes: [u8; 4] = buf.get(at..at + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
@@ -0,0 +1,39 @@
+//! Settings for impersonating the Firefox browser
+
+use super::ImpersonateProfile;
+
+mod ver;
+
+/// Defines the Firefox version to mimic when setting up a builder
+///
+/// The profiles send Firefox's cipher suites, groups and signature
+/// algorithms, without GREASE. Some of what Firefox sends is not implemented
+/// by BoringSSL, so it is missing from their ClientHello, and a server
+/// checking the whole JA3 or JA4 fingerprint can tell them apart from Firefox:
+///
+/// - the `delegated_credentials` and `record_size_limit` extensions,
+/// - the `ffdhe2048` and `ffdhe3072` groups,
+/// - a second key share, for P-256 next to X25519,
+/// - the order of the extensions, which BoringSSL fixes.
+///
+/// Firefox 117 sends the same fingerprint as `V109` but for its user agent,
+/// so it has no variant of its own; change the user agent of `V109` to
+/// impersonate it. `V120` also compresses certificates, sends an ECH GREASE
+/// extension, and no longer freezes the `rv:` token of its user agent at
+/// 109.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum FirefoxVersion {
+    V109,
+    V120,
+}
+
+impl FirefoxVersion {
+    /// Returns the [`ImpersonateProfile`] describing this version.
+    ///
+    /// The profile can be tweaked with [`ImpersonateProfile::to_builder`]
+    /// before being passed to `ClientBuilder::impersonate`.
+    pub fn profile(self) -> ImpersonateProfile {
+        ver::get_config_from_ver(self)
+    }
+}
@@ -0,0 +1,13 @@
+use crate::browser::ImpersonateProfile;
+
+use super::FirefoxVersion;
+
+mod v109;
+mod v120;
+
+pub(super) fn get_config_from_ver(ver: FirefoxVersion) -> ImpersonateProfile {
+    match ver {
+        FirefoxVersion::V109 => v109::get_settings(),
+        FirefoxVersion::V120 => v120::get_settings(),
+    }
+}
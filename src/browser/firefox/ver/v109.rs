@@ -0,0 +1,155 @@
+use http::{
+    header::{ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, TE, UPGRADE_INSECURE_REQUESTS, USER_AGENT},
+    HeaderMap,
+};
+
+use crate::browser::{ImpersonateProfile, Priority, PriorityFrame, PseudoOrder, SettingId};
+use crate::tls::Version;
+
+pub(super) fn get_settings() -> ImpersonateProfile {
+    ImpersonateProfile::builder()
+        .ocsp_stapling(true)
+        .curves(["X25519", "P-256", "P-384", "P-521"])
+        .cipher_list([
+            "TLS_AES_128_GCM_SHA256",
+            "TLS_CHACHA20_POLY1305_SHA256",
+            "TLS_AES_256_GCM_SHA384",
+            "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+            "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+            "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+            "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+            "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+            "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+            "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA",
+            "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA",
+            "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA",
+            "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA",
+            "TLS_RSA_WITH_AES_128_GCM_SHA256",
+            "TLS_RSA_WITH_AES_256_GCM_SHA384",
+            "TLS_RSA_WITH_AES_128_CBC_SHA",
+            "TLS_RSA_WITH_AES_256_CBC_SHA",
+        ])
+        .sigalgs_list([
+            "ecdsa_secp256r1_sha256",
+            "ecdsa_secp384r1_sha384",
+            "ecdsa_secp521r1_sha512",
+            "rsa_pss_rsae_sha256",
+            "rsa_pss_rsae_sha384",
+            "rsa_pss_rsae_sha512",
+            "rsa_pkcs1_sha256",
+            "rsa_pkcs1_sha384",
+            "rsa_pkcs1_sha512",
+            "ecdsa_sha1",
+            "rsa_pkcs1_sha1",
+        ])
+        .alpn_protos(["h2", "http/1.1"])
+        .min_tls_version(Version::TLS_1_2)
+        .max_tls_version(Version::TLS_1_3)
+        .http2_initial_stream_window_size(131072)
+        .http2_initial_connection_window_size(12582912)
+        .http2_header_table_size(65536)
+        .http2_max_frame_size(16384)
+        .http2_headers_pseudo_order([
+            PseudoOrder::Method,
+            PseudoOrder::Path,
+            PseudoOrder::Authority,
+            PseudoOrder::Scheme,
+        ])
+        .http2_settings_order([
+            SettingId::HeaderTableSize,
+            SettingId::EnablePush,
+            SettingId::MaxConcurrentStreams,
+            SettingId::InitialWindowSize,
+            SettingId::MaxFrameSize,
+            SettingId::MaxHeaderListSize,
+        ])
+        // Firefox builds its dependency tree out of idle streams, and makes
+        // each request depend on one of them.
+        .http2_priority_frames([
+            PriorityFrame {
+                stream_id: 3,
+                priority: Priority {
+                    stream_dependency: 0,
+                    weight: 201,
+                    exclusive: false,
+                },
+            },
+            PriorityFrame {
+                stream_id: 5,
+                priority: Priority {
+                    stream_dependency: 0,
+                    weight: 101,
+                    exclusive: false,
+                },
+            },
+            PriorityFrame {
+                stream_id: 7,
+                priority: Priority {
+                    stream_dependency: 0,
+                    weight: 1,
+                    exclusive: false,
+                },
+            },
+            PriorityFrame {
+                stream_id: 9,
+                priority: Priority {
+                    stream_dependency: 7,
+                    weight: 1,
+                    exclusive: false,
+                },
+            },
+            PriorityFrame {
+                stream_id: 11,
+                priority: Priority {
+                    stream_dependency: 3,
+                    weight: 1,
+                    exclusive: false,
+                },
+            },
+            PriorityFrame {
+                stream_id: 13,
+                priority: Priority {
+                    stream_dependency: 0,
+                    weight: 241,
+                    exclusive: false,
+                },
+            },
+        ])
+        .http2_headers_priority(Priority {
+            stream_dependency: 13,
+            weight: 42,
+            exclusive: false,
+        })
+        .headers(create_headers())
+        .gzip(true)
+        .brotli(true)
+        .build()
+        .unwrap()
+}
+
+fn create_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        USER_AGENT,
+        "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/109.0"
+            .parse()
+            .unwrap(),
+    );
+    headers.insert(
+        ACCEPT,
+        "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8"
+            .parse()
+            .unwrap(),
+    );
+    headers.insert(ACCEPT_LANGUAGE, "en-US,en;q=0.5".parse().unwrap());
+    headers.insert(ACCEPT_ENCODING, "gzip, deflate, br".parse().unwrap());
+    headers.insert(UPGRADE_INSECURE_REQUESTS, "1".parse().unwrap());
+    headers.insert("sec-fetch-dest", "document".parse().unwrap());
+    headers.insert("sec-fetch-mode", "navigate".parse().unwrap());
+    headers.insert("sec-fetch-site", "none".parse().unwrap());
+    headers.insert("sec-fetch-user", "?1".parse().unwrap());
+    headers.insert(TE, "trailers".parse().unwrap());
+
+    headers
+}
@@ -0,0 +1,133 @@
+//! HTTP/2 framing details that differ between browsers
+
+use serde::{Deserialize, Serialize};
+
+/// A pseudo-header field of an HTTP/2 request.
+///
+/// Browsers send the four request pseudo-headers in a fixed but distinct
+/// order, which makes the order part of their HTTP/2 fingerprint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PseudoOrder {
+    /// The `:method` pseudo-header.
+    Method,
+    /// The `:scheme` pseudo-header.
+    Scheme,
+    /// The `:authority` pseudo-header.
+    Authority,
+    /// The `:path` pseudo-header.
+    Path,
+}
+
+impl PseudoOrder {
+    pub(crate) fn to_h2(self) -> h2::frame::PseudoOrder {
+        match self {
+            PseudoOrder::Method => h2::frame::PseudoOrder::Method,
+            PseudoOrder::Scheme => h2::frame::PseudoOrder::Scheme,
+            PseudoOrder::Authority => h2::frame::PseudoOrder::Authority,
+            PseudoOrder::Path => h2::frame::PseudoOrder::Path,
+        }
+    }
+}
+
+/// A standard HTTP/2 `SETTINGS` parameter, used to order the initial
+/// `SETTINGS` frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingId {
+    /// `SETTINGS_HEADER_TABLE_SIZE` (0x1).
+    HeaderTableSize,
+    /// `SETTINGS_ENABLE_PUSH` (0x2).
+    EnablePush,
+    /// `SETTINGS_MAX_CONCURRENT_STREAMS` (0x3).
+    MaxConcurrentStreams,
+    /// `SETTINGS_INITIAL_WINDOW_SIZE` (0x4).
+    InitialWindowSize,
+    /// `SETTINGS_MAX_FRAME_SIZE` (0x5).
+    MaxFrameSize,
+    /// `SETTINGS_MAX_HEADER_LIST_SIZE` (0x6).
+    MaxHeaderListSize,
+}
+
+impl SettingId {
+    /// The identifier of the parameter on the wire.
+    pub fn id(self) -> u16 {
+        match self {
+            SettingId::HeaderTableSize => 0x1,
+            SettingId::EnablePush => 0x2,
+            SettingId::MaxConcurrentStreams => 0x3,
+            SettingId::InitialWindowSize => 0x4,
+            SettingId::MaxFrameSize => 0x5,
+            SettingId::MaxHeaderListSize => 0x6,
+        }
+    }
+
+    pub(crate) fn to_h2(self) -> h2::frame::SettingsOrder {
+        match self {
+            SettingId::HeaderTableSize => h2::frame::SettingsOrder::HeaderTableSize,
+            SettingId::EnablePush => h2::frame::SettingsOrder::EnablePush,
+            SettingId::MaxConcurrentStreams => h2::frame::SettingsOrder::MaxConcurrentStreams,
+            SettingId::InitialWindowSize => h2::frame::SettingsOrder::InitialWindowSize,
+            SettingId::MaxFrameSize => h2::frame::SettingsOrder::MaxFrameSize,
+            SettingId::MaxHeaderListSize => h2::frame::SettingsOrder::MaxHeaderListSize,
+        }
+    }
+}
+
+/// The priority information of an HTTP/2 stream, as carried by `HEADERS`
+/// and `PRIORITY` frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Priority {
+    /// The stream this stream depends on, `0` for the root.
+    pub stream_dependency: u32,
+    /// The weight, from 1 to 256.
+    ///
+    /// This is the weight as understood by the peer, which is one more
+    /// than the value sent on the wire.
+    pub weight: u16,
+    /// Whether the dependency is exclusive.
+    pub exclusive: bool,
+}
+
+impl Priority {
+    pub(crate) fn is_valid(&self) -> bool {
+        (1..=256).contains(&self.weight) && self.stream_dependency <= MAX_STREAM_ID
+    }
+
+    pub(crate) fn to_h2(self) -> h2::frame::StreamDependency {
+        h2::frame::StreamDependency::new(
+            self.stream_dependency.into(),
+            (self.weight - 1) as u8,
+            self.exclusive,
+        )
+    }
+}
+
+/// A `PRIORITY` frame sent right after the connection preface, before any
+/// request.
+///
+/// Firefox uses these to build a tree of idle streams it later makes its
+/// requests depend on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PriorityFrame {
+    /// The stream the frame is sent on.
+    pub stream_id: u32,
+    /// The priority given to that stream.
+    #[serde(flatten)]
+    pub priority: Priority,
+}
+
+impl PriorityFrame {
+    pub(crate) fn is_valid(&self) -> bool {
+        self.stream_id != 0
+            && self.stream_id <= MAX_STREAM_ID
+            && self.stream_id != self.priority.stream_dependency
+            && self.priority.is_valid()
+    }
+
+    pub(crate) fn to_h2(self) -> h2::frame::Priority {
+        h2::frame::Priority::new(self.stream_id.into(), self.priority.to_h2())
+    }
+}
+
+const MAX_STREAM_ID: u32 = (1 << 31) - 1;
@@ -0,0 +1,965 @@
+//! A composable description of the browser being impersonated
+
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::time::Duration;
+
+use boring::error::ErrorStack;
+use boring::ssl::{SslConnector, SslConnectorBuilder, SslContextBuilder, SslMethod};
+use http::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::alps::{self, ApplicationSettings};
+use super::cert_compressor::{self, CertCompressionAlgorithm};
+use super::{Priority, PriorityFrame, PseudoOrder, SettingId};
+use crate::tls;
+
+/// Everything a `Client` needs to look like a particular browser.
+///
+/// A profile bundles the TLS ClientHello options, the HTTP/2 connection
+/// settings, the default headers along with their order, and the response
+/// encodings to accept. Shipped profiles can be obtained from the version
+/// enums, e.g. [`ChromeVersion::profile`](super::ChromeVersion::profile), and
+/// tweaked through [`ImpersonateProfile::to_builder`]:
+///
+/// ```
+/// use reqwest_impersonate::browser::ChromeVersion;
+///
+/// # fn run() -> Result<(), reqwest_impersonate::Error> {
+/// let profile = ChromeVersion::V131
+///     .profile()
+///     .to_builder()
+///     .header("sec-ch-ua-platform", "\"Linux\"")
+///     .user_agent("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36")
+///     .build()?;
+///
+/// let client = reqwest_impersonate::Client::builder()
+///     .impersonate(profile)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Profiles also implement `Serialize` and `Deserialize`, so new fingerprints
+/// can be shipped as configuration, see [`ImpersonateProfile::from_json`].
+/// Every field is optional; headers are a list of name and value pairs, kept
+/// in the order they are sent:
+///
+/// ```json
+/// {
+///   "tls": {
+///     "cipher_list": ["TLS_AES_128_GCM_SHA256", "TLS_AES_256_GCM_SHA384"],
+///     "curves": ["X25519", "P-256"],
+///     "alpn_protos": ["h2", "http/1.1"],
+///     "cert_compression_algorithms": ["brotli"],
+///     "grease_enabled": true,
+///     "min_tls_version": "1.2"
+///   },
+///   "http2": {
+///     "initial_stream_window_size": 6291456,
+///     "headers_pseudo_order": ["method", "authority", "scheme", "path"]
+///   },
+///   "headers": [["user-agent", "Mozilla/5.0"], ["accept", "*/*"]],
+///   "gzip": true
+/// }
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "ProfileDef")]
+pub struct ImpersonateProfile {
+    pub(crate) tls: TlsSettings,
+    pub(crate) http2: Http2Settings,
+    pub(crate) http3: Http3Settings,
+    pub(crate) headers: HeaderMap,
+    pub(crate) header_order: Vec<HeaderName>,
+    pub(crate) gzip: bool,
+    pub(crate) brotli: bool,
+    pub(crate) deflate: bool,
+}
+
+/// A builder to construct or tweak an [`ImpersonateProfile`].
+#[must_use]
+#[derive(Debug)]
+pub struct ImpersonateProfileBuilder {
+    profile: ImpersonateProfile,
+    error: Option<crate::Error>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct TlsSettings {
+    pub(crate) cipher_list: Vec<String>,
+    pub(crate) sigalgs_list: Vec<String>,
+    pub(crate) curves: Vec<String>,
+    pub(crate) alpn_protos: Vec<String>,
+    pub(crate) cert_compression_algorithms: Vec<CertCompressionAlgorithm>,
+    pub(crate) grease_enabled: bool,
+    pub(crate) permute_extensions: bool,
+    pub(crate) ocsp_stapling: bool,
+    pub(crate) signed_cert_timestamps: bool,
+    pub(crate) min_tls_version: Option<tls::Version>,
+    pub(crate) max_tls_version: Option<tls::Version>,
+    #[serde(deserialize_with = "alps::deserialize")]
+    pub(crate) application_settings: Option<ApplicationSettings>,
+    pub(crate) enable_ech_grease: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Http2Settings {
+    pub(crate) initial_stream_window_size: Option<u32>,
+    pub(crate) initial_connection_window_size: Option<u32>,
+    pub(crate) max_concurrent_streams: Option<u32>,
+    pub(crate) max_header_list_size: Option<u32>,
+    pub(crate) header_table_size: Option<u32>,
+    pub(crate) max_frame_size: Option<u32>,
+    pub(crate) enable_push: Option<bool>,
+    pub(crate) headers_pseudo_order: Option<[PseudoOrder; 4]>,
+    pub(crate) settings_order: Vec<SettingId>,
+    pub(crate) extra_settings: Vec<(u16, u32)>,
+    pub(crate) headers_priority: Option<Priority>,
+    pub(crate) priority_frames: Vec<PriorityFrame>,
+}
+
+/// QUIC transport parameters and HTTP/3 settings. Unset values fall back to
+/// the defaults of the HTTP/3 client, not to the QUIC ones, which would not
+/// let any data through.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Http3Settings {
+    /// In milliseconds.
+    pub(crate) max_idle_timeout: Option<u64>,
+    pub(crate) max_udp_payload_size: Option<u64>,
+    pub(crate) initial_max_data: Option<u64>,
+    pub(crate) initial_max_stream_data_bidi_local: Option<u64>,
+    pub(crate) initial_max_stream_data_bidi_remote: Option<u64>,
+    pub(crate) initial_max_stream_data_uni: Option<u64>,
+    pub(crate) initial_max_streams_bidi: Option<u64>,
+    pub(crate) initial_max_streams_uni: Option<u64>,
+    pub(crate) disable_active_migration: bool,
+    pub(crate) grease: bool,
+    pub(crate) qpack_blocked_streams: Option<u64>,
+    pub(crate) max_field_section_size: Option<u64>,
+}
+
+// ===== impl ImpersonateProfile =====
+
+impl ImpersonateProfile {
+    /// Creates a builder for a profile starting from an empty configuration.
+    ///
+    /// An empty profile uses BoringSSL's defaults for every TLS option and
+    /// sends no default headers.
+    pub fn builder() -> ImpersonateProfileBuilder {
+        ImpersonateProfileBuilder {
+            profile: ImpersonateProfile::empty(),
+            error: None,
+        }
+    }
+
+    pub(crate) fn empty() -> ImpersonateProfile {
+        ImpersonateProfile {
+            tls: TlsSettings::default(),
+            http2: Http2Settings::default(),
+            http3: Http3Settings::default(),
+            headers: HeaderMap::new(),
+            header_order: Vec::new(),
+            gzip: false,
+            brotli: false,
+            deflate: false,
+        }
+    }
+
+    /// Loads a profile from its JSON representation.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the JSON is malformed, or if the resulting
+    /// profile would be rejected by [`ImpersonateProfileBuilder::build`].
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `json` feature enabled.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn from_json(json: &str) -> crate::Result<ImpersonateProfile> {
+        serde_json::from_str(json).map_err(crate::error::builder)
+    }
+
+    /// Loads a profile from its TOML representation.
+    ///
+    /// The layout is the same as for [`ImpersonateProfile::from_json`].
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the TOML is malformed, or if the resulting
+    /// profile would be rejected by [`ImpersonateProfileBuilder::build`].
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `toml` feature enabled.
+    #[cfg(feature = "toml")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "toml")))]
+    pub fn from_toml(toml: &str) -> crate::Result<ImpersonateProfile> {
+        toml::from_str(toml).map_err(crate::error::builder)
+    }
+
+    /// Creates a builder starting from a copy of this profile.
+    pub fn to_builder(&self) -> ImpersonateProfileBuilder {
+        ImpersonateProfileBuilder {
+            profile: self.clone(),
+            error: None,
+        }
+    }
+
+    /// The cipher suites offered, in order.
+    pub fn cipher_list(&self) -> &[String] {
+        &self.tls.cipher_list
+    }
+
+    /// The signature algorithms offered, in order.
+    pub fn sigalgs_list(&self) -> &[String] {
+        &self.tls.sigalgs_list
+    }
+
+    /// The supported groups offered, in order.
+    pub fn curves(&self) -> &[String] {
+        &self.tls.curves
+    }
+
+    /// The ALPN protocols offered, in order.
+    pub fn alpn_protos(&self) -> &[String] {
+        &self.tls.alpn_protos
+    }
+
+    /// The certificate compression algorithms advertised, in order.
+    pub fn cert_compression_algorithms(&self) -> &[CertCompressionAlgorithm] {
+        &self.tls.cert_compression_algorithms
+    }
+
+    /// The ALPS (application settings) extension sent, if any.
+    pub fn application_settings(&self) -> Option<&ApplicationSettings> {
+        self.tls.application_settings.as_ref()
+    }
+
+    /// The default headers sent with every request.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The order headers are sent in.
+    ///
+    /// Headers not listed here are sent after the listed ones.
+    pub fn header_order(&self) -> &[HeaderName] {
+        &self.header_order
+    }
+
+    /// Returns the default headers, rearranged to follow `header_order`.
+    pub(crate) fn ordered_headers(&self) -> HeaderMap {
+        let mut headers = self.headers.clone();
+        crate::util::sort_headers(&mut headers, &self.header_order);
+        headers
+    }
+}
+
+// ===== impl ImpersonateProfileBuilder =====
+
+impl ImpersonateProfileBuilder {
+    /// Returns the `ImpersonateProfile`.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if a header could not be parsed, if one of the
+    /// HTTP/2 options is inconsistent, such as a pseudo-header listed twice,
+    /// or if BoringSSL rejects one of the TLS options, such as an unknown
+    /// cipher name.
+    pub fn build(self) -> crate::Result<ImpersonateProfile> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        self.profile.http2.validate()?;
+
+        if let Some(ref alps) = self.profile.tls.application_settings {
+            alps.validate()?;
+        }
+
+        self.profile.tls.connector_builder()?;
+
+        Ok(self.profile)
+    }
+
+    // TLS options
+
+    /// Sets the cipher suites, using either their IANA or OpenSSL names.
+    pub fn cipher_list<I, S>(mut self, ciphers: I) -> ImpersonateProfileBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.profile.tls.cipher_list = ciphers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the signature algorithms, e.g. `ecdsa_secp256r1_sha256`.
+    pub fn sigalgs_list<I, S>(mut self, sigalgs: I) -> ImpersonateProfileBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.profile.tls.sigalgs_list = sigalgs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the supported groups, e.g. `X25519` or `P-256`.
+    pub fn curves<I, S>(mut self, curves: I) -> ImpersonateProfileBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.profile.tls.curves = curves.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the ALPN protocols, e.g. `h2` and `http/1.1`.
+    pub fn alpn_protos<I, S>(mut self, protos: I) -> ImpersonateProfileBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.profile.tls.alpn_protos = protos.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the certificate compression algorithms advertised, in order.
+    pub fn cert_compression_algorithms<I>(mut self, algorithms: I) -> ImpersonateProfileBuilder
+    where
+        I: IntoIterator<Item = CertCompressionAlgorithm>,
+    {
+        self.profile.tls.cert_compression_algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Enables GREASE values in the ClientHello.
+    pub fn grease_enabled(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.tls.grease_enabled = enable;
+        self
+    }
+
+    /// Randomizes the order of the ClientHello extensions on every handshake.
+    pub fn permute_extensions(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.tls.permute_extensions = enable;
+        self
+    }
+
+    /// Requests OCSP stapling from the server.
+    pub fn ocsp_stapling(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.tls.ocsp_stapling = enable;
+        self
+    }
+
+    /// Requests signed certificate timestamps from the server.
+    pub fn signed_cert_timestamps(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.tls.signed_cert_timestamps = enable;
+        self
+    }
+
+    /// Sets the minimum TLS version offered.
+    pub fn min_tls_version(
+        mut self,
+        version: impl Into<Option<tls::Version>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.tls.min_tls_version = version.into();
+        self
+    }
+
+    /// Sets the maximum TLS version offered.
+    pub fn max_tls_version(
+        mut self,
+        version: impl Into<Option<tls::Version>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.tls.max_tls_version = version.into();
+        self
+    }
+
+    /// Sends the ALPS (application settings) extension for `h2`, with an
+    /// empty payload under the original codepoint.
+    ///
+    /// Use [`ImpersonateProfileBuilder::alps`] to set the protocols, payload
+    /// and codepoint.
+    pub fn application_settings(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.tls.application_settings = if enable {
+            Some(ApplicationSettings::default())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Sets the ALPS (application settings) extension, or disables it with
+    /// `None`.
+    pub fn alps(
+        mut self,
+        settings: impl Into<Option<ApplicationSettings>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.tls.application_settings = settings.into();
+        self
+    }
+
+    /// Sends a GREASE Encrypted Client Hello extension.
+    pub fn enable_ech_grease(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.tls.enable_ech_grease = enable;
+        self
+    }
+
+    // HTTP/2 options
+
+    /// Sets the `SETTINGS_INITIAL_WINDOW_SIZE` sent for HTTP/2.
+    pub fn http2_initial_stream_window_size(
+        mut self,
+        sz: impl Into<Option<u32>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http2.initial_stream_window_size = sz.into();
+        self
+    }
+
+    /// Sets the HTTP/2 connection-level flow control window.
+    ///
+    /// The `WINDOW_UPDATE` frame sent after the connection preface
+    /// increments the window by this size minus the initial 65535 bytes.
+    pub fn http2_initial_connection_window_size(
+        mut self,
+        sz: impl Into<Option<u32>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http2.initial_connection_window_size = sz.into();
+        self
+    }
+
+    /// Sets the `SETTINGS_MAX_CONCURRENT_STREAMS` sent for HTTP/2.
+    pub fn http2_max_concurrent_streams(
+        mut self,
+        sz: impl Into<Option<u32>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http2.max_concurrent_streams = sz.into();
+        self
+    }
+
+    /// Sets the `SETTINGS_MAX_HEADER_LIST_SIZE` sent for HTTP/2.
+    pub fn http2_max_header_list_size(
+        mut self,
+        sz: impl Into<Option<u32>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http2.max_header_list_size = sz.into();
+        self
+    }
+
+    /// Sets the `SETTINGS_HEADER_TABLE_SIZE` sent for HTTP/2.
+    pub fn http2_header_table_size(
+        mut self,
+        sz: impl Into<Option<u32>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http2.header_table_size = sz.into();
+        self
+    }
+
+    /// Sets the `SETTINGS_MAX_FRAME_SIZE` sent for HTTP/2.
+    pub fn http2_max_frame_size(mut self, sz: impl Into<Option<u32>>) -> ImpersonateProfileBuilder {
+        self.profile.http2.max_frame_size = sz.into();
+        self
+    }
+
+    /// Sets the `SETTINGS_ENABLE_PUSH` sent for HTTP/2.
+    pub fn http2_enable_push(
+        mut self,
+        enable: impl Into<Option<bool>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http2.enable_push = enable.into();
+        self
+    }
+
+    /// Sets the order of the request pseudo-headers in HTTP/2 `HEADERS` frames.
+    pub fn http2_headers_pseudo_order(
+        mut self,
+        order: impl Into<Option<[PseudoOrder; 4]>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http2.headers_pseudo_order = order.into();
+        self
+    }
+
+    /// Sets the order of the parameters in the initial HTTP/2 `SETTINGS` frame.
+    ///
+    /// Parameters left out of the list are sent after the listed ones, in
+    /// their usual order.
+    pub fn http2_settings_order<I>(mut self, order: I) -> ImpersonateProfileBuilder
+    where
+        I: IntoIterator<Item = SettingId>,
+    {
+        self.profile.http2.settings_order = order.into_iter().collect();
+        self
+    }
+
+    /// Adds non-standard parameters, identifier and value, sent at the end of
+    /// the initial HTTP/2 `SETTINGS` frame.
+    ///
+    /// This can be used to send reserved GREASE settings, or settings from
+    /// extensions such as `SETTINGS_NO_RFC7540_PRIORITIES` (0x9).
+    pub fn http2_extra_settings<I>(mut self, settings: I) -> ImpersonateProfileBuilder
+    where
+        I: IntoIterator<Item = (u16, u32)>,
+    {
+        self.profile.http2.extra_settings = settings.into_iter().collect();
+        self
+    }
+
+    /// Sets the priority attached to the HTTP/2 `HEADERS` frame of every request.
+    pub fn http2_headers_priority(
+        mut self,
+        priority: impl Into<Option<Priority>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http2.headers_priority = priority.into();
+        self
+    }
+
+    /// Sets the HTTP/2 `PRIORITY` frames sent when a connection is opened.
+    pub fn http2_priority_frames<I>(mut self, frames: I) -> ImpersonateProfileBuilder
+    where
+        I: IntoIterator<Item = PriorityFrame>,
+    {
+        self.profile.http2.priority_frames = frames.into_iter().collect();
+        self
+    }
+
+    // HTTP/3 options
+
+    /// Sets the QUIC `max_idle_timeout` transport parameter.
+    pub fn http3_max_idle_timeout(
+        mut self,
+        timeout: impl Into<Option<Duration>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.max_idle_timeout = timeout.into().map(|t| t.as_millis() as u64);
+        self
+    }
+
+    /// Sets the QUIC `max_udp_payload_size` transport parameter.
+    pub fn http3_max_udp_payload_size(
+        mut self,
+        sz: impl Into<Option<u64>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.max_udp_payload_size = sz.into();
+        self
+    }
+
+    /// Sets the QUIC `initial_max_data` transport parameter.
+    pub fn http3_initial_max_data(
+        mut self,
+        sz: impl Into<Option<u64>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.initial_max_data = sz.into();
+        self
+    }
+
+    /// Sets the QUIC `initial_max_stream_data_bidi_local` transport parameter.
+    pub fn http3_initial_max_stream_data_bidi_local(
+        mut self,
+        sz: impl Into<Option<u64>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.initial_max_stream_data_bidi_local = sz.into();
+        self
+    }
+
+    /// Sets the QUIC `initial_max_stream_data_bidi_remote` transport parameter.
+    pub fn http3_initial_max_stream_data_bidi_remote(
+        mut self,
+        sz: impl Into<Option<u64>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.initial_max_stream_data_bidi_remote = sz.into();
+        self
+    }
+
+    /// Sets the QUIC `initial_max_stream_data_uni` transport parameter.
+    pub fn http3_initial_max_stream_data_uni(
+        mut self,
+        sz: impl Into<Option<u64>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.initial_max_stream_data_uni = sz.into();
+        self
+    }
+
+    /// Sets the QUIC `initial_max_streams_bidi` transport parameter.
+    pub fn http3_initial_max_streams_bidi(
+        mut self,
+        max: impl Into<Option<u64>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.initial_max_streams_bidi = max.into();
+        self
+    }
+
+    /// Sets the QUIC `initial_max_streams_uni` transport parameter.
+    pub fn http3_initial_max_streams_uni(
+        mut self,
+        max: impl Into<Option<u64>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.initial_max_streams_uni = max.into();
+        self
+    }
+
+    /// Sends the QUIC `disable_active_migration` transport parameter.
+    pub fn http3_disable_active_migration(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.http3.disable_active_migration = enable;
+        self
+    }
+
+    /// Sends GREASE QUIC transport parameters and HTTP/3 settings and frames.
+    pub fn http3_grease(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.http3.grease = enable;
+        self
+    }
+
+    /// Sets the `SETTINGS_QPACK_BLOCKED_STREAMS` sent for HTTP/3.
+    pub fn http3_qpack_blocked_streams(
+        mut self,
+        max: impl Into<Option<u64>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.qpack_blocked_streams = max.into();
+        self
+    }
+
+    /// Sets the `SETTINGS_MAX_FIELD_SECTION_SIZE` sent for HTTP/3.
+    pub fn http3_max_field_section_size(
+        mut self,
+        sz: impl Into<Option<u64>>,
+    ) -> ImpersonateProfileBuilder {
+        self.profile.http3.max_field_section_size = sz.into();
+        self
+    }
+
+    // Header options
+
+    /// Sets a default header, replacing any previous value.
+    ///
+    /// A header which wasn't part of the profile yet is sent after the
+    /// existing ones, unless `header_order` says otherwise.
+    pub fn header<K, V>(mut self, key: K, value: V) -> ImpersonateProfileBuilder
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        match <HeaderName as TryFrom<K>>::try_from(key) {
+            Ok(key) => match <HeaderValue as TryFrom<V>>::try_from(value) {
+                Ok(value) => {
+                    self.profile.headers.insert(key, value);
+                }
+                Err(e) => self.error = Some(crate::error::builder(e.into())),
+            },
+            Err(e) => self.error = Some(crate::error::builder(e.into())),
+        }
+        self
+    }
+
+    /// Replaces all default headers.
+    ///
+    /// Unless `header_order` is set, headers are sent in the order they were
+    /// inserted into the map.
+    pub fn headers(mut self, headers: HeaderMap) -> ImpersonateProfileBuilder {
+        self.profile.headers = headers;
+        self
+    }
+
+    /// Sets the `User-Agent` default header.
+    pub fn user_agent<V>(self, value: V) -> ImpersonateProfileBuilder
+    where
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        self.header(USER_AGENT, value)
+    }
+
+    /// Sets the order headers are sent in.
+    pub fn header_order<I>(mut self, order: I) -> ImpersonateProfileBuilder
+    where
+        I: IntoIterator<Item = HeaderName>,
+    {
+        self.profile.header_order = order.into_iter().collect();
+        self
+    }
+
+    // Encoding options
+
+    /// Accepts gzip encoded responses.
+    pub fn gzip(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.gzip = enable;
+        self
+    }
+
+    /// Accepts brotli encoded responses.
+    pub fn brotli(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.brotli = enable;
+        self
+    }
+
+    /// Accepts deflate encoded responses.
+    pub fn deflate(mut self, enable: bool) -> ImpersonateProfileBuilder {
+        self.profile.deflate = enable;
+        self
+    }
+}
+
+// ===== impl Http2Settings =====
+
+impl Http2Settings {
+    /// The parameters of the initial `SETTINGS` frame, in the order they are
+    /// sent.
+    pub(crate) fn settings(&self) -> Vec<(u16, u32)> {
+        const STANDARD: [SettingId; 6] = [
+            SettingId::HeaderTableSize,
+            SettingId::EnablePush,
+            SettingId::MaxConcurrentStreams,
+            SettingId::InitialWindowSize,
+            SettingId::MaxFrameSize,
+            SettingId::MaxHeaderListSize,
+        ];
+
+        let unlisted = STANDARD
+            .iter()
+            .filter(|id| !self.settings_order.contains(id));
+        let mut settings: Vec<(u16, u32)> = self
+            .settings_order
+            .iter()
+            .chain(unlisted)
+            .filter_map(|id| {
+                let value = match id {
+                    SettingId::HeaderTableSize => self.header_table_size,
+                    SettingId::EnablePush => self.enable_push.map(u32::from),
+                    SettingId::MaxConcurrentStreams => self.max_concurrent_streams,
+                    SettingId::InitialWindowSize => self.initial_stream_window_size,
+                    SettingId::MaxFrameSize => self.max_frame_size,
+                    SettingId::MaxHeaderListSize => self.max_header_list_size,
+                };
+                value.map(|value| (id.id(), value))
+            })
+            .collect();
+        settings.extend_from_slice(&self.extra_settings);
+        settings
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if let Some(order) = self.headers_pseudo_order {
+            if has_duplicates(&order) {
+                return Err(crate::error::builder(
+                    "HTTP/2 pseudo-header order must list every pseudo-header once",
+                ));
+            }
+        }
+
+        if has_duplicates(&self.settings_order) {
+            return Err(crate::error::builder(
+                "HTTP/2 settings order must not list a setting twice",
+            ));
+        }
+
+        let extra_ids = self
+            .extra_settings
+            .iter()
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        if has_duplicates(&extra_ids) || extra_ids.iter().any(|id| (0x1..=0x6).contains(id)) {
+            return Err(crate::error::builder(
+                "HTTP/2 extra settings must be unique and not standard settings",
+            ));
+        }
+
+        if !self.headers_priority.map_or(true, |p| p.is_valid())
+            || !self.priority_frames.iter().all(PriorityFrame::is_valid)
+        {
+            return Err(crate::error::builder("invalid HTTP/2 stream priority"));
+        }
+
+        Ok(())
+    }
+}
+
+fn has_duplicates<T: PartialEq>(items: &[T]) -> bool {
+    items
+        .iter()
+        .enumerate()
+        .any(|(i, item)| items[..i].contains(item))
+}
+
+// ===== serde =====
+
+/// The serialized form of an `ImpersonateProfile`.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProfileDef {
+    tls: TlsSettings,
+    http2: Http2Settings,
+    http3: Http3Settings,
+    headers: Vec<(String, String)>,
+    header_order: Vec<String>,
+    gzip: bool,
+    brotli: bool,
+    deflate: bool,
+}
+
+impl TryFrom<ImpersonateProfile> for ProfileDef {
+    type Error = crate::Error;
+
+    /// Fails if a header value isn't UTF-8, as it could not be written out
+    /// as it is.
+    fn try_from(profile: ImpersonateProfile) -> crate::Result<ProfileDef> {
+        let headers = profile
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let value = std::str::from_utf8(value.as_bytes())
+                    .map_err(|_| crate::error::builder(format!("header {} is not UTF-8", name)))?;
+                Ok((name.as_str().to_owned(), value.to_owned()))
+            })
+            .collect::<crate::Result<_>>()?;
+        let header_order = profile
+            .header_order
+            .iter()
+            .map(|name| name.as_str().to_owned())
+            .collect();
+
+        Ok(ProfileDef {
+            tls: profile.tls,
+            http2: profile.http2,
+            http3: profile.http3,
+            headers,
+            header_order,
+            gzip: profile.gzip,
+            brotli: profile.brotli,
+            deflate: profile.deflate,
+        })
+    }
+}
+
+impl Serialize for ImpersonateProfile {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ProfileDef::try_from(self.clone())
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl TryFrom<ProfileDef> for ImpersonateProfile {
+    type Error = crate::Error;
+
+    fn try_from(def: ProfileDef) -> crate::Result<ImpersonateProfile> {
+        let mut headers = HeaderMap::with_capacity(def.headers.len());
+        for (name, value) in def.headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(crate::error::builder)?;
+            let value = HeaderValue::from_str(&value).map_err(crate::error::builder)?;
+            headers.append(name, value);
+        }
+
+        let header_order = def
+            .header_order
+            .iter()
+            .map(|name| HeaderName::from_bytes(name.as_bytes()))
+            .collect::<Result<_, _>>()
+            .map_err(crate::error::builder)?;
+
+        ImpersonateProfileBuilder {
+            profile: ImpersonateProfile {
+                tls: def.tls,
+                http2: def.http2,
+                http3: def.http3,
+                headers,
+                header_order,
+                gzip: def.gzip,
+                brotli: def.brotli,
+                deflate: def.deflate,
+            },
+            error: None,
+        }
+        .build()
+    }
+}
+
+// ===== impl TlsSettings =====
+
+impl TlsSettings {
+    /// Creates a `SslConnectorBuilder` configured with these settings.
+    pub(crate) fn connector_builder(&self) -> crate::Result<SslConnectorBuilder> {
+        self.try_connector_builder().map_err(crate::error::builder)
+    }
+
+    fn try_connector_builder(&self) -> Result<SslConnectorBuilder, crate::error::BoxError> {
+        let mut builder = SslConnector::builder(SslMethod::tls())?;
+        self.configure(&mut builder)?;
+        Ok(builder)
+    }
+
+    /// Creates a bare `SslContextBuilder` configured with these settings,
+    /// for transports which set up the connector themselves.
+    #[cfg(feature = "http3")]
+    pub(crate) fn context_builder(&self) -> Result<SslContextBuilder, crate::error::BoxError> {
+        let mut builder = SslContextBuilder::new(SslMethod::tls())?;
+        self.configure(&mut builder)?;
+        Ok(builder)
+    }
+
+    fn configure(&self, builder: &mut SslContextBuilder) -> Result<(), crate::error::BoxError> {
+        builder.set_grease_enabled(self.grease_enabled);
+
+        builder.set_permute_extensions(self.permute_extensions);
+
+        if self.ocsp_stapling {
+            builder.enable_ocsp_stapling();
+        }
+
+        if !self.curves.is_empty() {
+            set_curves_list(builder, &self.curves.join(":"))?;
+        }
+
+        if !self.cipher_list.is_empty() {
+            builder.set_cipher_list(&self.cipher_list.join(":"))?;
+        }
+
+        if !self.sigalgs_list.is_empty() {
+            builder.set_sigalgs_list(&self.sigalgs_list.join(":"))?;
+        }
+
+        if self.signed_cert_timestamps {
+            builder.enable_signed_cert_timestamps();
+        }
+
+        if !self.alpn_protos.is_empty() {
+            let mut protos = Vec::new();
+            for proto in &self.alpn_protos {
+                let len = u8::try_from(proto.len()).map_err(|_| "ALPN protocol too long")?;
+                protos.push(len);
+                protos.extend_from_slice(proto.as_bytes());
+            }
+            builder.set_alpn_protos(&protos)?;
+        }
+
+        for algorithm in &self.cert_compression_algorithms {
+            cert_compressor::add_to_builder(builder, *algorithm)?;
+        }
+
+        builder.set_min_proto_version(self.min_tls_version.map(tls::Version::to_boring))?;
+
+        builder.set_max_proto_version(self.max_tls_version.map(tls::Version::to_boring))?;
+
+        Ok(())
+    }
+}
+
+fn set_curves_list(
+    builder: &mut SslContextBuilder,
+    curves: &str,
+) -> Result<(), crate::error::BoxError> {
+    let curves = CString::new(curves).map_err(|_| "curve names must not contain NUL")?;
+    let ret = unsafe { boring_sys::SSL_CTX_set1_curves_list(builder.as_ptr(), curves.as_ptr()) };
+    if ret == 1 {
+        Ok(())
+    } else {
+        Err(ErrorStack::get().into())
+    }
+}
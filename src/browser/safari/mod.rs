@@ -0,0 +1,27 @@
+//! Settings for impersonating the Safari browser on macOS and iOS
+
+use super::ImpersonateProfile;
+
+mod ver;
+
+/// Defines the Safari version to mimic when setting up a builder
+///
+/// The `Ios` variants impersonate Mobile Safari on an iPhone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum SafariVersion {
+    V16_5,
+    V17,
+    Ios16_5,
+    Ios17,
+}
+
+impl SafariVersion {
+    /// Returns the [`ImpersonateProfile`] describing this version.
+    ///
+    /// The profile can be tweaked with [`ImpersonateProfile::to_builder`]
+    /// before being passed to `ClientBuilder::impersonate`.
+    pub fn profile(self) -> ImpersonateProfile {
+        ver::get_config_from_ver(self)
+    }
+}
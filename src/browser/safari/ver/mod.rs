@@ -0,0 +1,17 @@
+use crate::browser::ImpersonateProfile;
+
+use super::SafariVersion;
+
+mod ios16_5;
+mod ios17;
+mod v16_5;
+mod v17;
+
+pub(super) fn get_config_from_ver(ver: SafariVersion) -> ImpersonateProfile {
+    match ver {
+        SafariVersion::V16_5 => v16_5::get_settings(),
+        SafariVersion::V17 => v17::get_settings(),
+        SafariVersion::Ios16_5 => ios16_5::get_settings(),
+        SafariVersion::Ios17 => ios17::get_settings(),
+    }
+}
@@ -0,0 +1,937 @@
+//! HTTP caching
+//!
+//! A `Client` built with [`ClientBuilder::cache`](crate::ClientBuilder::cache)
+//! keeps the responses to its `GET` requests in a [`CacheStore`], and answers
+//! later requests from it the way a browser's private cache does, following
+//! [RFC 9111]:
+//!
+//! - A response is stored unless it or its request says `no-store`. Partial
+//!   responses and responses with `Vary: *` are never stored.
+//! - A stored response is used while it is fresh, for as long as its
+//!   `Cache-Control: max-age`, its `Expires` header or, lacking both, 10% of
+//!   the time since its `Last-Modified` date allows.
+//! - Once stale, or when it says `no-cache`, a stored response is revalidated
+//!   with `If-None-Match` and `If-Modified-Since` from its `ETag` and
+//!   `Last-Modified` headers, and used again if the server answers
+//!   `304 Not Modified`.
+//! - A stored response is only used for requests with the same values of the
+//!   headers named by its `Vary` header.
+//! - A successful `POST`, `PUT`, `DELETE` or other unsafe request removes what
+//!   is stored for its URL.
+//! - Responses with a body larger than the store's
+//!   [`max_entry_size`](CacheStore::max_entry_size) are passed through without
+//!   being stored.
+//! - `Set-Cookie` and hop-by-hop headers are not stored, so cookies are only
+//!   set by the response that carried them.
+//!
+//! Requests with their own conditional or `Range` headers, or saying
+//! `Cache-Control: no-store`, are passed through as is. `no-cache`, `max-age`,
+//! `min-fresh` and `only-if-cached` request directives are honored.
+//!
+//! [RFC 9111]: https://www.rfc-editor.org/rfc/rfc9111
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::{self, StreamExt};
+use http::header::{
+    HeaderName, AGE, CACHE_CONTROL, CONNECTION, CONTENT_LENGTH, DATE, ETAG, EXPIRES, IF_MATCH,
+    IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, IF_UNMODIFIED_SINCE, LAST_MODIFIED, PRAGMA,
+    PROXY_AUTHENTICATE, RANGE, SET_COOKIE, TE, TRAILER, TRANSFER_ENCODING, UPGRADE, VARY,
+};
+use http::{HeaderMap, HeaderValue, Method, StatusCode, Version};
+use log::debug;
+
+use crate::middleware::{BoxFuture, Middleware, Next};
+use crate::Body;
+
+/// Where the responses kept by the cache are stored.
+///
+/// Entries are stored under the URL of their request. A store may drop
+/// entries at any time, e.g. to bound its size.
+pub trait CacheStore: Send + Sync + 'static {
+    /// Returns the entry stored under `key`, if any.
+    fn get(&self, key: &str) -> Option<Entry>;
+
+    /// Stores `entry` under `key`, replacing the entry stored before.
+    fn put(&self, key: &str, entry: Entry);
+
+    /// Removes the entry stored under `key`, if any.
+    fn remove(&self, key: &str);
+
+    /// Returns the size in bytes of the largest response body to store.
+    ///
+    /// Larger responses are passed through without being buffered. Defaults
+    /// to 8 MiB.
+    fn max_entry_size(&self) -> usize {
+        DEFAULT_MAX_ENTRY_SIZE
+    }
+}
+
+const DEFAULT_MAX_ENTRY_SIZE: usize = 8 * 1024 * 1024;
+
+/// A response kept by the cache.
+///
+/// An `Entry` can be converted to bytes and back, for stores keeping entries
+/// outside of the process.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Bytes,
+    /// The request headers named by the `Vary` response header.
+    vary: HeaderMap,
+    request_time: SystemTime,
+    response_time: SystemTime,
+}
+
+/// A `CacheStore` keeping entries in memory.
+///
+/// Once full, the least recently used entry is dropped to make room.
+pub struct MemoryStore {
+    max_entries: usize,
+    max_entry_size: usize,
+    inner: Mutex<MemoryInner>,
+}
+
+#[derive(Default)]
+struct MemoryInner {
+    entries: HashMap<String, (u64, Entry)>,
+    uses: u64,
+}
+
+/// A `CacheStore` keeping entries as files in a directory.
+///
+/// Each entry is a file of its own, which is read and written with blocking
+/// file system calls. The directory is not bounded in size.
+#[derive(Debug)]
+pub struct DiskStore {
+    dir: PathBuf,
+    max_entry_size: usize,
+}
+
+/// The cache middleware added by `ClientBuilder::cache`.
+pub(crate) struct Cache {
+    store: Box<dyn CacheStore>,
+}
+
+/// The `Cache-Control` directives the cache acts on.
+#[derive(Debug, Default, PartialEq)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<Duration>,
+    min_fresh: Option<Duration>,
+    only_if_cached: bool,
+    public: bool,
+}
+
+// ===== impl Entry =====
+
+impl Entry {
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the body of the response, as it was received.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Converts the entry to bytes, which `Entry::from_bytes` reads back.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = format!(
+            "{} {:?} {} {}\n",
+            self.status.as_u16(),
+            self.version,
+            unix_time(self.request_time),
+            unix_time(self.response_time),
+        )
+        .into_bytes();
+        for map in [&self.headers, &self.vary] {
+            for (name, value) in map {
+                bytes.extend_from_slice(name.as_str().as_bytes());
+                bytes.extend_from_slice(b": ");
+                bytes.extend_from_slice(value.as_bytes());
+                bytes.push(b'\n');
+            }
+            bytes.push(b'\n');
+        }
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
+    /// Reads an entry from the bytes `Entry::to_bytes` returned.
+    ///
+    /// Returns `None` if the bytes are not a valid entry.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Entry> {
+        let (line, mut rest) = split_line(bytes)?;
+        let line = std::str::from_utf8(line).ok()?;
+        let mut parts = line.split(' ');
+        let status = StatusCode::from_bytes(parts.next()?.as_bytes()).ok()?;
+        let version = match parts.next()? {
+            "HTTP/0.9" => Version::HTTP_09,
+            "HTTP/1.0" => Version::HTTP_10,
+            "HTTP/1.1" => Version::HTTP_11,
+            "HTTP/2.0" => Version::HTTP_2,
+            "HTTP/3.0" => Version::HTTP_3,
+            _ => return None,
+        };
+        let request_time = UNIX_EPOCH + Duration::from_secs(parts.next()?.parse().ok()?);
+        let response_time = UNIX_EPOCH + Duration::from_secs(parts.next()?.parse().ok()?);
+        if parts.next().is_some() {
+            return None;
+        }
+
+        let mut maps = [HeaderMap::new(), HeaderMap::new()];
+        for map in &mut maps {
+            loop {
+                let (line, next) = split_line(rest)?;
+                rest = next;
+                if line.is_empty() {
+                    break;
+                }
+                let colon = line.iter().position(|&b| b == b':')?;
+                let name = HeaderName::from_bytes(&line[..colon]).ok()?;
+                let value = HeaderValue::from_bytes(line[colon + 1..].strip_prefix(b" ")?).ok()?;
+                map.append(name, value);
+            }
+        }
+        let [headers, vary] = maps;
+
+        Some(Entry {
+            status,
+            version,
+            headers,
+            body: Bytes::copy_from_slice(rest),
+            vary,
+            request_time,
+            response_time,
+        })
+    }
+
+    fn new(
+        res: &http::response::Parts,
+        req_headers: &HeaderMap,
+        request_time: SystemTime,
+        response_time: SystemTime,
+    ) -> Entry {
+        let mut vary = HeaderMap::new();
+        for name in vary_names(&res.headers) {
+            for value in req_headers.get_all(&name) {
+                vary.append(name.clone(), value.clone());
+            }
+        }
+
+        let mut headers = res.headers.clone();
+        strip_per_response_headers(&mut headers);
+
+        Entry {
+            status: res.status,
+            version: res.version,
+            headers,
+            body: Bytes::new(),
+            vary,
+            request_time,
+            response_time,
+        }
+    }
+
+    /// Whether the entry can be used for a request with these headers.
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        vary_names(&self.headers).all(|name| {
+            self.vary
+                .get_all(&name)
+                .iter()
+                .eq(headers.get_all(&name).iter())
+        })
+    }
+
+    /// Whether the entry can be used without revalidating it.
+    fn is_fresh(&self, req: &CacheControl, now: SystemTime) -> bool {
+        let cache_control = CacheControl::parse(&self.headers);
+        if cache_control.no_cache {
+            return false;
+        }
+
+        let mut lifetime = self.freshness_lifetime(&cache_control);
+        if let Some(max_age) = req.max_age {
+            lifetime = lifetime.min(max_age);
+        }
+        lifetime > self.current_age(now) + req.min_fresh.unwrap_or_default()
+    }
+
+    /// Whether the entry can ever be used, fresh or once revalidated.
+    fn is_useful(&self) -> bool {
+        let cache_control = CacheControl::parse(&self.headers);
+        self.freshness_lifetime(&cache_control) > Duration::ZERO
+            || self.headers.contains_key(ETAG)
+            || self.headers.contains_key(LAST_MODIFIED)
+    }
+
+    fn freshness_lifetime(&self, cache_control: &CacheControl) -> Duration {
+        if let Some(max_age) = cache_control.max_age {
+            return max_age;
+        }
+
+        let date = self.date();
+        if let Some(expires) = self.headers.get(EXPIRES) {
+            // An invalid date, such as "0", means already expired.
+            return header_date(expires)
+                .and_then(|expires| expires.duration_since(date).ok())
+                .unwrap_or_default();
+        }
+
+        if cache_control.public || is_heuristically_cacheable(self.status) {
+            if let Some(last_modified) = self.headers.get(LAST_MODIFIED).and_then(header_date) {
+                return date.duration_since(last_modified).unwrap_or_default() / 10;
+            }
+        }
+        Duration::ZERO
+    }
+
+    /// The age of the response, as defined in RFC 9111 section 4.2.3.
+    fn current_age(&self, now: SystemTime) -> Duration {
+        let age_value = self
+            .headers
+            .get(AGE)
+            .and_then(|age| age.to_str().ok()?.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_default();
+        let apparent_age = self
+            .response_time
+            .duration_since(self.date())
+            .unwrap_or_default();
+        let response_delay = self
+            .response_time
+            .duration_since(self.request_time)
+            .unwrap_or_default();
+        let corrected_initial_age = apparent_age.max(age_value + response_delay);
+        let resident_time = now.duration_since(self.response_time).unwrap_or_default();
+        corrected_initial_age + resident_time
+    }
+
+    fn date(&self) -> SystemTime {
+        self.headers
+            .get(DATE)
+            .and_then(header_date)
+            .unwrap_or(self.response_time)
+    }
+
+    /// Adds headers asking the server whether the entry is still valid.
+    ///
+    /// Returns `false` if the entry has no validators to send.
+    fn add_validators(&self, headers: &mut HeaderMap) -> bool {
+        let mut added = false;
+        if let Some(etag) = self.headers.get(ETAG) {
+            headers.insert(IF_NONE_MATCH, etag.clone());
+            added = true;
+        }
+        if let Some(last_modified) = self.headers.get(LAST_MODIFIED) {
+            headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+            added = true;
+        }
+        added
+    }
+
+    /// Updates the entry with a `304 Not Modified` response.
+    fn update(&mut self, headers: &HeaderMap, request_time: SystemTime, response_time: SystemTime) {
+        let mut headers = headers.clone();
+        headers.remove(CONTENT_LENGTH);
+        strip_per_response_headers(&mut headers);
+        crate::util::replace_headers(&mut self.headers, headers);
+        self.request_time = request_time;
+        self.response_time = response_time;
+    }
+
+    fn to_response(&self, now: SystemTime) -> http::Response<Body> {
+        let mut res = http::Response::new(Body::from(self.body.clone()));
+        *res.status_mut() = self.status;
+        *res.version_mut() = self.version;
+        *res.headers_mut() = self.headers.clone();
+        res.headers_mut()
+            .insert(AGE, HeaderValue::from(self.current_age(now).as_secs()));
+        res
+    }
+}
+
+// ===== impl MemoryStore =====
+
+impl MemoryStore {
+    /// Creates a store keeping at most `max_entries` entries.
+    pub fn new(max_entries: usize) -> MemoryStore {
+        MemoryStore {
+            max_entries,
+            max_entry_size: DEFAULT_MAX_ENTRY_SIZE,
+            inner: Mutex::new(MemoryInner::default()),
+        }
+    }
+
+    /// Sets the size in bytes of the largest response body to store.
+    ///
+    /// Defaults to 8 MiB.
+    pub fn with_max_entry_size(mut self, size: usize) -> MemoryStore {
+        self.max_entry_size = size;
+        self
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<Entry> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.uses += 1;
+        let uses = inner.uses;
+        let (last_use, entry) = inner.entries.get_mut(key)?;
+        *last_use = uses;
+        Some(entry.clone())
+    }
+
+    fn put(&self, key: &str, entry: Entry) {
+        if self.max_entries == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.entries.contains_key(key) && inner.entries.len() >= self.max_entries {
+            let lru = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, (last_use, _))| *last_use)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                inner.entries.remove(&lru);
+            }
+        }
+        inner.uses += 1;
+        let uses = inner.uses;
+        inner.entries.insert(key.to_owned(), (uses, entry));
+    }
+
+    fn remove(&self, key: &str) {
+        self.inner.lock().unwrap().entries.remove(key);
+    }
+
+    fn max_entry_size(&self) -> usize {
+        self.max_entry_size
+    }
+}
+
+impl fmt::Debug for MemoryStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryStore")
+            .field("entries", &self.inner.lock().unwrap().entries.len())
+            .field("max_entries", &self.max_entries)
+            .field("max_entry_size", &self.max_entry_size)
+            .finish()
+    }
+}
+
+// ===== impl DiskStore =====
+
+impl DiskStore {
+    /// Creates a store keeping entries in `dir`, which is created if missing.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if `dir` cannot be created.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<DiskStore> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(DiskStore {
+            dir,
+            max_entry_size: DEFAULT_MAX_ENTRY_SIZE,
+        })
+    }
+
+    /// Sets the size in bytes of the largest response body to store.
+    ///
+    /// Defaults to 8 MiB.
+    pub fn with_max_entry_size(mut self, size: usize) -> DiskStore {
+        self.max_entry_size = size;
+        self
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        // FNV-1a, which unlike `DefaultHasher` is stable across Rust versions.
+        let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        });
+        self.dir.join(format!("{:016x}", hash))
+    }
+}
+
+impl CacheStore for DiskStore {
+    fn get(&self, key: &str) -> Option<Entry> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        // Files start with their key, in case of a hash collision.
+        let (stored_key, entry) = split_line(&bytes)?;
+        if stored_key != key.as_bytes() {
+            return None;
+        }
+        Entry::from_bytes(entry)
+    }
+
+    fn put(&self, key: &str, entry: Entry) {
+        let mut bytes = key.as_bytes().to_vec();
+        bytes.push(b'\n');
+        bytes.extend_from_slice(&entry.to_bytes());
+
+        // Written to a temporary file first, so a concurrent `get` never
+        // reads half an entry.
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{:x}.tmp", crate::util::fast_random()));
+        if let Err(err) = fs::write(&tmp, bytes).and_then(|()| fs::rename(&tmp, &path)) {
+            debug!("failed to store cache entry for {}: {}", key, err);
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = fs::remove_file(self.path(key));
+    }
+
+    fn max_entry_size(&self) -> usize {
+        self.max_entry_size
+    }
+}
+
+// ===== impl Cache =====
+
+impl Cache {
+    pub(crate) fn new<S: CacheStore>(store: S) -> Cache {
+        Cache {
+            store: Box::new(store),
+        }
+    }
+
+    async fn send(
+        &self,
+        mut req: http::Request<Body>,
+        next: Next,
+    ) -> crate::Result<http::Response<Body>> {
+        let key = req.uri().to_string();
+
+        if req.method() != Method::GET {
+            let safe = req.method().is_safe();
+            let res = next.run(req).await?;
+            if !safe && (res.status().is_success() || res.status().is_redirection()) {
+                self.store.remove(&key);
+            }
+            return Ok(res);
+        }
+
+        let cache_control = CacheControl::parse_request(req.headers());
+        let conditional = [
+            IF_MATCH,
+            IF_NONE_MATCH,
+            IF_MODIFIED_SINCE,
+            IF_UNMODIFIED_SINCE,
+            IF_RANGE,
+        ]
+        .iter()
+        .any(|name| req.headers().contains_key(name));
+        if cache_control.no_store || conditional || req.headers().contains_key(RANGE) {
+            return next.run(req).await;
+        }
+
+        let stored = self
+            .store
+            .get(&key)
+            .filter(|entry| entry.matches(req.headers()));
+        if let Some(ref entry) = stored {
+            let now = SystemTime::now();
+            if !cache_control.no_cache && entry.is_fresh(&cache_control, now) {
+                return Ok(entry.to_response(now));
+            }
+        }
+        if cache_control.only_if_cached {
+            let mut res = http::Response::new(Body::empty());
+            *res.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+            return Ok(res);
+        }
+
+        let req_headers = req.headers().clone();
+        let revalidating = stored.filter(|entry| entry.add_validators(req.headers_mut()));
+        let request_time = SystemTime::now();
+        let res = next.run(req).await?;
+        let response_time = SystemTime::now();
+
+        if let Some(mut entry) = revalidating {
+            if res.status() == StatusCode::NOT_MODIFIED {
+                entry.update(res.headers(), request_time, response_time);
+                let mut cached = entry.to_response(response_time);
+                self.store.put(&key, entry);
+                // Cookies set by the 304 itself still reach the caller.
+                for cookie in res.headers().get_all(SET_COOKIE) {
+                    cached.headers_mut().append(SET_COOKIE, cookie.clone());
+                }
+                return Ok(cached);
+            }
+        }
+
+        if !is_storable(res.status(), res.headers()) {
+            self.store.remove(&key);
+            return Ok(res);
+        }
+        let (parts, body) = res.into_parts();
+        let mut entry = Entry::new(&parts, &req_headers, request_time, response_time);
+        if !entry.is_useful() {
+            self.store.remove(&key);
+            return Ok(http::Response::from_parts(parts, body));
+        }
+
+        let max_size = self.store.max_entry_size();
+        let too_large = parts
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse::<u64>().ok())
+            .map_or(false, |len| len > max_size as u64);
+        if too_large {
+            debug!("response for {} is too large to cache", key);
+            self.store.remove(&key);
+            return Ok(http::Response::from_parts(parts, body));
+        }
+
+        let mut buf = BytesMut::new();
+        let mut chunks = body.into_stream();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            if buf.len() + chunk.len() > max_size {
+                debug!("response for {} is too large to cache", key);
+                self.store.remove(&key);
+                let read = stream::iter([Ok(buf.freeze()), Ok(chunk)]);
+                let body = Body::stream(read.chain(chunks));
+                return Ok(http::Response::from_parts(parts, body));
+            }
+            buf.extend_from_slice(&chunk);
+        }
+
+        entry.body = buf.freeze();
+        let body = Body::from(entry.body.clone());
+        self.store.put(&key, entry);
+        Ok(http::Response::from_parts(parts, body))
+    }
+}
+
+impl Middleware for Cache {
+    fn handle<'a>(
+        &'a self,
+        req: http::Request<Body>,
+        next: Next,
+    ) -> BoxFuture<'a, crate::Result<http::Response<Body>>> {
+        Box::pin(self.send(req, next))
+    }
+}
+
+// ===== impl CacheControl =====
+
+impl CacheControl {
+    fn parse(headers: &HeaderMap) -> CacheControl {
+        let mut cache_control = CacheControl::default();
+        for value in headers.get_all(CACHE_CONTROL) {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            for directive in value.split(',') {
+                let (name, arg) = match directive.split_once('=') {
+                    Some((name, arg)) => (name, Some(arg.trim().trim_matches('"'))),
+                    None => (directive, None),
+                };
+                let secs = arg
+                    .and_then(|arg| arg.parse().ok())
+                    .map(Duration::from_secs);
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "no-store" => cache_control.no_store = true,
+                    "no-cache" => cache_control.no_cache = true,
+                    // An invalid max-age means the response is stale.
+                    "max-age" => cache_control.max_age = Some(secs.unwrap_or_default()),
+                    "min-fresh" => cache_control.min_fresh = secs,
+                    "only-if-cached" => cache_control.only_if_cached = true,
+                    "public" => cache_control.public = true,
+                    _ => (),
+                }
+            }
+        }
+        cache_control
+    }
+
+    /// Parses the directives of a request, which may use `Pragma: no-cache`
+    /// instead of `Cache-Control`.
+    fn parse_request(headers: &HeaderMap) -> CacheControl {
+        let mut cache_control = CacheControl::parse(headers);
+        if !headers.contains_key(CACHE_CONTROL) {
+            cache_control.no_cache = headers
+                .get_all(PRAGMA)
+                .iter()
+                .any(|value| value.as_bytes().eq_ignore_ascii_case(b"no-cache"));
+        }
+        cache_control
+    }
+}
+
+/// Removes the headers which must not be replayed from the cache: cookies,
+/// and the hop-by-hop headers of RFC 9111 section 3.1.
+fn strip_per_response_headers(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    for name in listed {
+        headers.remove(name);
+    }
+    for name in [
+        SET_COOKIE,
+        CONNECTION,
+        PROXY_AUTHENTICATE,
+        TE,
+        TRAILER,
+        TRANSFER_ENCODING,
+        UPGRADE,
+    ] {
+        headers.remove(name);
+    }
+    headers.remove("keep-alive");
+    headers.remove("proxy-connection");
+}
+
+fn is_storable(status: StatusCode, headers: &HeaderMap) -> bool {
+    if status.is_informational()
+        || status == StatusCode::PARTIAL_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+    {
+        return false;
+    }
+    let vary_all = headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| name.trim() == "*");
+
+    let cache_control = CacheControl::parse(headers);
+    !cache_control.no_store
+        && !vary_all
+        && (cache_control.max_age.is_some()
+            || cache_control.public
+            || headers.contains_key(EXPIRES)
+            || is_heuristically_cacheable(status))
+}
+
+/// The status codes which may be stored without explicit freshness, as listed
+/// in RFC 9110 section 15.1. `206 Partial Content` is never stored.
+fn is_heuristically_cacheable(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+fn vary_names(headers: &HeaderMap) -> impl Iterator<Item = HeaderName> + '_ {
+    headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+}
+
+fn header_date(value: &HeaderValue) -> Option<SystemTime> {
+    httpdate::parse_http_date(value.to_str().ok()?).ok()
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+fn split_line(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = bytes.iter().position(|&b| b == b'\n')?;
+    Some((&bytes[..end], &bytes[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn stored(headers: &[(&'static str, &'static str)]) -> Entry {
+        let mut map = HeaderMap::new();
+        for &(name, value) in headers {
+            map.append(name, HeaderValue::from_static(value));
+        }
+        Entry {
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers: map,
+            body: Bytes::from_static(b"hello"),
+            vary: HeaderMap::new(),
+            request_time: at(784_111_777),
+            response_time: at(784_111_777),
+        }
+    }
+
+    #[test]
+    fn parse_cache_control() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static("public, Max-Age=\"60\", no-cache"),
+        );
+        headers.append(CACHE_CONTROL, HeaderValue::from_static("min-fresh=5"));
+        assert_eq!(
+            CacheControl::parse(&headers),
+            CacheControl {
+                no_cache: true,
+                max_age: Some(Duration::from_secs(60)),
+                min_fresh: Some(Duration::from_secs(5)),
+                public: true,
+                ..CacheControl::default()
+            }
+        );
+
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=soon"));
+        assert_eq!(CacheControl::parse(&headers).max_age, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn parse_request_pragma() {
+        let mut headers = HeaderMap::new();
+        headers.insert(PRAGMA, HeaderValue::from_static("no-cache"));
+        assert!(CacheControl::parse_request(&headers).no_cache);
+
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=0"));
+        assert!(!CacheControl::parse_request(&headers).no_cache);
+    }
+
+    #[test]
+    fn freshness_max_age() {
+        let entry = stored(&[("cache-control", "max-age=60"), ("age", "10")]);
+        let req = CacheControl::default();
+        assert_eq!(entry.current_age(at(784_111_777)), Duration::from_secs(10));
+        assert!(entry.is_fresh(&req, at(784_111_826)));
+        assert!(!entry.is_fresh(&req, at(784_111_827)));
+
+        let req = CacheControl {
+            max_age: Some(Duration::from_secs(30)),
+            ..CacheControl::default()
+        };
+        assert!(!entry.is_fresh(&req, at(784_111_797)));
+    }
+
+    #[test]
+    fn freshness_expires() {
+        let entry = stored(&[
+            ("date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+            ("expires", "Sun, 06 Nov 1994 08:50:37 GMT"),
+        ]);
+        let cache_control = CacheControl::parse(&entry.headers);
+        assert_eq!(
+            entry.freshness_lifetime(&cache_control),
+            Duration::from_secs(60)
+        );
+
+        let entry = stored(&[("expires", "0")]);
+        assert_eq!(entry.freshness_lifetime(&cache_control), Duration::ZERO);
+    }
+
+    #[test]
+    fn freshness_heuristic() {
+        let mut entry = stored(&[
+            ("date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+            ("last-modified", "Sun, 06 Nov 1994 08:32:57 GMT"),
+        ]);
+        let cache_control = CacheControl::default();
+        assert_eq!(
+            entry.freshness_lifetime(&cache_control),
+            Duration::from_secs(100)
+        );
+
+        entry.status = StatusCode::CREATED;
+        assert_eq!(entry.freshness_lifetime(&cache_control), Duration::ZERO);
+    }
+
+    #[test]
+    fn vary_matches() {
+        let mut entry = stored(&[("vary", "Accept-Language")]);
+        entry
+            .vary
+            .insert("accept-language", HeaderValue::from_static("en"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-language", HeaderValue::from_static("en"));
+        assert!(entry.matches(&headers));
+        headers.insert("accept-language", HeaderValue::from_static("fr"));
+        assert!(!entry.matches(&headers));
+        assert!(!entry.matches(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn entry_bytes_round_trip() {
+        let mut entry = stored(&[
+            ("etag", "\"v1\""),
+            ("set-cookie", "a=1"),
+            ("set-cookie", ""),
+        ]);
+        entry.vary.insert("accept", HeaderValue::from_static("*/*"));
+        entry.body = Bytes::from_static(b"line\n\nbreaks");
+
+        let read = Entry::from_bytes(&entry.to_bytes()).unwrap();
+        assert_eq!(read.status, entry.status);
+        assert_eq!(read.version, entry.version);
+        assert_eq!(read.headers, entry.headers);
+        assert_eq!(read.vary, entry.vary);
+        assert_eq!(read.body, entry.body);
+        assert_eq!(read.request_time, entry.request_time);
+        assert_eq!(read.response_time, entry.response_time);
+
+        assert!(Entry::from_bytes(b"200 HTTP/1.1 0 0\n").is_none());
+    }
+
+    #[test]
+    fn per_response_headers_are_not_stored() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        headers.append(SET_COOKIE, HeaderValue::from_static("a=1"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("b=2"));
+        headers.insert(CONNECTION, HeaderValue::from_static("keep-alive, x-hop"));
+        headers.insert("keep-alive", HeaderValue::from_static("timeout=5"));
+        headers.insert("x-hop", HeaderValue::from_static("1"));
+
+        strip_per_response_headers(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[ETAG], "\"v1\"");
+    }
+
+    #[test]
+    fn memory_store_evicts_least_recently_used() {
+        let store = MemoryStore::new(2);
+        store.put("a", stored(&[]));
+        store.put("b", stored(&[]));
+        assert!(store.get("a").is_some());
+        store.put("c", stored(&[]));
+        assert!(store.get("a").is_some());
+        assert!(store.get("b").is_none());
+        assert!(store.get("c").is_some());
+    }
+}
@@ -1,4 +1,8 @@
-use boring::ssl::{ConnectConfiguration, SslConnectorBuilder};
+use boring::error::ErrorStack;
+use boring::ssl::{
+    ConnectConfiguration, SslConnector, SslConnectorBuilder, SslContextBuilder, SslRef,
+};
+use boring::x509::store::X509StoreBuilder;
 use foreign_types::ForeignTypeRef;
 use http::header::HeaderValue;
 use http::uri::{Authority, Scheme};
@@ -11,15 +15,22 @@ use pin_project_lite::pin_project;
 use std::future::Future;
 use std::io::{self, IoSlice};
 use std::net::IpAddr;
+use std::os::raw::c_int;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
 use self::boring_tls_conn::BoringTlsConn;
+pub(crate) use self::ech::EchConfigs;
+use self::ech::EchRejected;
+pub(crate) use self::session_cache::SessionCache;
+use self::session_cache::SessionKey;
+use crate::browser::alps::{self, AlpsConfig};
 use crate::dns::DynResolver;
 use crate::error::BoxError;
 use crate::proxy::{Proxy, ProxyScheme};
+use crate::tls;
 
 pub(crate) type HttpConnector = hyper::client::HttpConnector<DynResolver>;
 
@@ -31,32 +42,174 @@ pub(crate) struct Connector {
     timeout: Option<Duration>,
     nodelay: bool,
     user_agent: Option<HeaderValue>,
-    certs_verification: bool,
+    handshake: HandshakeSettings,
+    session_cache: Option<Arc<SessionCache>>,
+    early_data: bool,
+    ech: Option<Arc<EchConfigs>>,
 }
 
 #[derive(Clone)]
 enum Inner {
     BoringTls {
         http: HttpConnector,
-        tls: Arc<dyn Fn() -> SslConnectorBuilder + Send + Sync>,
+        // Built once, so every connection shares the same `SslContext`,
+        // which TLS sessions can only be resumed with.
+        tls: SslConnector,
     },
 }
 
-fn tls_add_application_settings(conf: &mut ConnectConfiguration) {
-    const ALPN_H2: &str = "h2";
-    const ALPN_H2_LENGTH: usize = 2;
-    unsafe {
+/// TLS options set on the `ClientBuilder`, applied on top of the
+/// `SslConnectorBuilder` the TLS builder func returns, whichever profile it
+/// comes from.
+#[derive(Clone)]
+pub(crate) struct TlsOverrides {
+    pub(crate) certs_verification: bool,
+    pub(crate) root_certs: Vec<tls::Certificate>,
+    pub(crate) built_in_root_certs: bool,
+    pub(crate) identity: Option<tls::Identity>,
+    pub(crate) min_tls_version: Option<tls::Version>,
+    pub(crate) max_tls_version: Option<tls::Version>,
+}
+
+impl TlsOverrides {
+    pub(crate) fn apply(&self, builder: &mut SslContextBuilder) -> Result<(), BoxError> {
+        if !self.certs_verification {
+            builder.set_verify(boring::ssl::SslVerifyMode::NONE);
+        }
+
+        if self.built_in_root_certs {
+            for cert in &self.root_certs {
+                cert.add_to_store(builder.cert_store_mut())?;
+            }
+        } else {
+            let mut store = X509StoreBuilder::new()?;
+            for cert in &self.root_certs {
+                cert.add_to_store(&mut store)?;
+            }
+            builder.set_cert_store(store.build());
+        }
+
+        if let Some(ref identity) = self.identity {
+            identity.add_to_builder(builder)?;
+        }
+
+        if let Some(version) = self.min_tls_version {
+            builder.set_min_proto_version(Some(version.to_boring()))?;
+        }
+
+        if let Some(version) = self.max_tls_version {
+            builder.set_max_proto_version(Some(version.to_boring()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// TLS options which BoringSSL only exposes on a single connection, applied to
+/// every `ConnectConfiguration` right before the handshake.
+#[derive(Clone)]
+pub(crate) struct HandshakeSettings {
+    pub(crate) application_settings: Option<AlpsConfig>,
+    pub(crate) enable_ech_grease: bool,
+    pub(crate) sni: bool,
+}
+
+impl HandshakeSettings {
+    /// The settings for a QUIC handshake, which only ever negotiates `h3`.
+    ///
+    /// ALPS is offered for `h3` instead, with an empty payload, if it is
+    /// offered at all.
+    #[cfg(feature = "http3")]
+    pub(crate) fn for_h3(&self) -> HandshakeSettings {
+        HandshakeSettings {
+            application_settings: self.application_settings.as_ref().map(|alps| AlpsConfig {
+                protocols: Arc::new(vec![("h3".to_owned(), Vec::new())]),
+                new_codepoint: alps.new_codepoint,
+            }),
+            enable_ech_grease: self.enable_ech_grease,
+            sni: self.sni,
+        }
+    }
+
+    fn configure(&self, conf: &mut ConnectConfiguration) -> Result<(), ErrorStack> {
+        self.configure_ssl(conf)?;
+
+        conf.set_use_server_name_indication(self.sni);
+
+        Ok(())
+    }
+
+    /// Applies the settings which can be set on any connection, such as one
+    /// quiche created, rather than only on a `ConnectConfiguration`.
+    pub(crate) fn configure_ssl(&self, ssl: &mut SslRef) -> Result<(), ErrorStack> {
+        if let Some(ref alps) = self.application_settings {
+            for (protocol, settings) in alps.protocols.iter() {
+                add_application_settings(ssl, protocol.as_bytes(), settings)?;
+            }
+            set_alps_use_new_codepoint(ssl, alps.new_codepoint);
+        }
+
+        ssl.set_enable_ech_grease(self.enable_ech_grease);
+
+        Ok(())
+    }
+}
+
+fn add_application_settings(
+    ssl: &mut SslRef,
+    protocol: &[u8],
+    settings: &[u8],
+) -> Result<(), ErrorStack> {
+    // Safety: BoringSSL copies both buffers.
+    let ret = unsafe {
         boring_sys::SSL_add_application_settings(
-            conf.as_ptr(),
-            ALPN_H2.as_ptr(),
-            ALPN_H2_LENGTH,
-            std::ptr::null(),
-            0,
+            ssl.as_ptr(),
+            protocol.as_ptr(),
+            protocol.len(),
+            settings.as_ptr(),
+            settings.len(),
         )
     };
+    if ret == 1 {
+        Ok(())
+    } else {
+        Err(ErrorStack::get())
+    }
+}
+
+fn set_alps_use_new_codepoint(ssl: &mut SslRef, enable: bool) {
+    unsafe { boring_sys::SSL_set_alps_use_new_codepoint(ssl.as_ptr(), enable as c_int) }
+}
+
+/// Processes the ALPS settings the server sent, if ALPS was negotiated,
+/// returning the HTTP/2 settings among them.
+///
+/// As in Chrome, a malformed `h2` payload fails the connection.
+fn check_peer_application_settings(ssl: &SslRef) -> Result<Vec<(u16, u32)>, BoxError> {
+    if unsafe { boring_sys::SSL_has_application_settings(ssl.as_ptr()) } != 1 {
+        return Ok(Vec::new());
+    }
+
+    let mut data = std::ptr::null();
+    let mut len = 0;
+    unsafe { boring_sys::SSL_get0_peer_application_settings(ssl.as_ptr(), &mut data, &mut len) };
+    let data = if data.is_null() {
+        &[][..]
+    } else {
+        // Safety: BoringSSL keeps the buffer alive as long as the connection.
+        unsafe { std::slice::from_raw_parts(data, len) }
+    };
+
+    if ssl.selected_alpn_protocol() == Some(b"h2") {
+        let settings = alps::parse_server_h2(data)?;
+        log::trace!("server ALPS settings for h2: {:?}", settings);
+        return Ok(settings);
+    }
+    Ok(Vec::new())
 }
 
 impl Connector {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new_boring_tls<T>(
         mut http: HttpConnector,
         tls: Arc<dyn Fn() -> SslConnectorBuilder + Send + Sync>,
@@ -64,23 +217,105 @@ impl Connector {
         user_agent: Option<HeaderValue>,
         local_addr: T,
         nodelay: bool,
-        certs_verification: bool,
-    ) -> Connector
+        tls_overrides: TlsOverrides,
+        handshake: HandshakeSettings,
+        session_cache: Option<Arc<SessionCache>>,
+    ) -> Result<Connector, BoxError>
     where
         T: Into<Option<IpAddr>>,
     {
         http.set_local_address(local_addr.into());
         http.enforce_http(false);
 
-        Connector {
-            inner: Inner::BoringTls { http, tls },
+        let mut tls = tls();
+        tls_overrides.apply(&mut tls)?;
+        if let Some(ref cache) = session_cache {
+            cache.install(&mut tls);
+        }
+
+        Ok(Connector {
+            inner: Inner::BoringTls {
+                http,
+                tls: tls.build(),
+            },
             proxies,
             verbose: verbose::OFF,
             timeout: None,
             nodelay,
             user_agent,
-            certs_verification,
+            handshake,
+            session_cache,
+            early_data: false,
+            ech: None,
+        })
+    }
+
+    /// Sends requests as TLS 1.3 early data on resumed connections.
+    ///
+    /// Only a connector dedicated to idempotent requests may do this, since
+    /// early data can be replayed by an attacker.
+    pub(crate) fn set_early_data(&mut self, enabled: bool) {
+        self.early_data = enabled;
+    }
+
+    /// Uses Encrypted Client Hello with hosts `ech` has a configuration for.
+    pub(crate) fn set_ech(&mut self, ech: Arc<EchConfigs>) {
+        self.ech = Some(ech);
+    }
+
+    /// Performs the TLS handshake with `host` over `conn`, resuming a cached
+    /// session when there is one.
+    ///
+    /// `via` identifies the proxy the connection goes through, so sessions
+    /// are never shared between direct and proxied connections. Early data
+    /// is only sent if `early_data` is set too.
+    async fn tls_handshake<T>(
+        &self,
+        tls: &SslConnector,
+        host: &str,
+        port: u16,
+        via: Option<String>,
+        early_data: bool,
+        conn: T,
+    ) -> Result<BoringTlsConn<T>, BoxError>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        // IPv6 literals are bracketed in URIs, but not in certificates.
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        let mut conf = tls.configure()?;
+        self.handshake.configure(&mut conf)?;
+
+        let ech_config_list = match self.ech {
+            Some(ref ech) => ech.lookup(host).await?,
+            None => None,
+        };
+        if let Some(ref ech_config_list) = ech_config_list {
+            conf.set_ech_config_list(ech_config_list)?;
+        }
+
+        if let Some(ref cache) = self.session_cache {
+            let key = SessionKey::new(host, port, via);
+            cache.resume(&mut conf, key, early_data)?;
         }
+
+        let stream = match tokio_boring::connect(conf, host, conn).await {
+            Ok(stream) => stream,
+            Err(err) => match (&self.ech, ech_config_list) {
+                (Some(ech), Some(_)) if ech::is_rejected(&err) => {
+                    let retry_configs = err
+                        .ssl()
+                        .and_then(|ssl| ssl.get_ech_retry_configs())
+                        .map(<[u8]>::to_vec);
+                    ech.rejected(host, retry_configs);
+                    return Err(Box::new(EchRejected));
+                }
+                _ => return Err(err.into()),
+            },
+        };
+        let peer_settings = check_peer_application_settings(stream.ssl())?;
+        Ok(BoringTlsConn::new(stream, peer_settings))
     }
 
     pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
@@ -109,18 +344,17 @@ impl Connector {
             Inner::BoringTls { tls, .. } => {
                 if dst.scheme() == Some(&Scheme::HTTPS) {
                     let host = dst.host().ok_or("no host in url")?.to_string();
+                    let port = dst.port_u16().unwrap_or(443);
+                    let via = match &proxy {
+                        ProxyScheme::Socks5 { addr, .. } => Some(format!("socks5://{}", addr)),
+                        _ => None,
+                    };
                     let conn = socks::connect(proxy, dst, dns).await?;
-                    let mut tls_connector = tls();
-                    if !self.certs_verification {
-                        tls_connector.set_verify(boring::ssl::SslVerifyMode::NONE);
-                    }
-                    let mut conf = tls_connector.build().configure()?;
-
-                    tls_add_application_settings(&mut conf);
-
-                    let io = tokio_boring::connect(conf, &host, conn).await?;
+                    let io = self
+                        .tls_handshake(tls, &host, port, via, self.early_data, conn)
+                        .await?;
                     return Ok(Conn {
-                        inner: self.verbose.wrap(BoringTlsConn { inner: io }),
+                        inner: self.verbose.wrap(io),
                         is_proxy: false,
                     });
                 }
@@ -133,8 +367,35 @@ impl Connector {
         })
     }
 
+    async fn connect(self, dst: Uri, proxy_scheme: Option<ProxyScheme>) -> Result<Conn, BoxError> {
+        match proxy_scheme {
+            Some(proxy_scheme) => self.connect_via_proxy(dst, proxy_scheme).await,
+            None => self.connect_with_maybe_proxy(dst, false).await,
+        }
+    }
+
+    /// Connects, and connects again once if the server rejected ECH, using
+    /// the retry configurations it sent, or no ECH if it sent none.
+    async fn connect_retrying_ech(
+        self,
+        dst: Uri,
+        proxy_scheme: Option<ProxyScheme>,
+    ) -> Result<Conn, BoxError> {
+        match self
+            .clone()
+            .connect(dst.clone(), proxy_scheme.clone())
+            .await
+        {
+            Err(err) if err.is::<EchRejected>() => {
+                log::debug!("ECH rejected by {:?}, connecting again", dst.host());
+                self.connect(dst, proxy_scheme).await
+            }
+            result => result,
+        }
+    }
+
     async fn connect_with_maybe_proxy(self, dst: Uri, is_proxy: bool) -> Result<Conn, BoxError> {
-        match self.inner {
+        match &self.inner {
             Inner::BoringTls { http, tls } => {
                 let mut http = http.clone();
 
@@ -145,34 +406,31 @@ impl Connector {
                     http.set_nodelay(true);
                 }
 
-                let mut tls_connector = tls();
-                if !self.certs_verification {
-                    tls_connector.set_verify(boring::ssl::SslVerifyMode::NONE);
+                if dst.scheme() != Some(&Scheme::HTTPS) {
+                    let io = http.call(dst).await?;
+                    return Ok(Conn {
+                        inner: self.verbose.wrap(io),
+                        is_proxy,
+                    });
                 }
-                let mut http = hyper_boring::HttpsConnector::with_connector(http, tls_connector)?;
 
-                http.set_callback(|conf, _| {
-                    tls_add_application_settings(conf);
-                    Ok(())
-                });
-
-                let io = http.call(dst).await?;
+                let host = dst.host().ok_or("no host in url")?.to_owned();
+                let port = dst.port_u16().unwrap_or(443);
+                let tcp = http.call(dst).await?;
+                // Early data sent to an HTTPS proxy would be the request to
+                // forward, which the proxy may not treat as replayable.
+                let early_data = self.early_data && !is_proxy;
+                let stream = self
+                    .tls_handshake(tls, &host, port, None, early_data, tcp)
+                    .await?;
 
-                if let hyper_boring::MaybeHttpsStream::Https(stream) = io {
-                    if !self.nodelay {
-                        let stream_ref = stream.get_ref();
-                        stream_ref.set_nodelay(false)?;
-                    }
-                    Ok(Conn {
-                        inner: self.verbose.wrap(BoringTlsConn { inner: stream }),
-                        is_proxy,
-                    })
-                } else {
-                    Ok(Conn {
-                        inner: self.verbose.wrap(io),
-                        is_proxy,
-                    })
+                if !self.nodelay {
+                    stream.get_ref().set_nodelay(false)?;
                 }
+                Ok(Conn {
+                    inner: self.verbose.wrap(stream),
+                    is_proxy,
+                })
             }
         }
     }
@@ -195,46 +453,26 @@ impl Connector {
         match &self.inner {
             Inner::BoringTls { http, tls } => {
                 if dst.scheme() == Some(&Scheme::HTTPS) {
-                    let host = dst.host().to_owned();
+                    let host = dst.host().ok_or("no host in url")?;
                     let port = dst.port().map(|p| p.as_u16()).unwrap_or(443);
-                    let http = http.clone();
-                    let mut tls_connector = tls();
-                    if !self.certs_verification {
-                        tls_connector.set_verify(boring::ssl::SslVerifyMode::NONE);
-                    }
-                    let mut http =
-                        hyper_boring::HttpsConnector::with_connector(http, tls_connector)?;
-
-                    http.set_callback(|conf, _| {
-                        tls_add_application_settings(conf);
-
-                        Ok(())
-                    });
-
-                    let conn = http.call(proxy_dst).await?;
+                    let via = proxy_dst.to_string();
+                    let conn = http.clone().call(proxy_dst.clone()).await?;
                     log::trace!("tunneling HTTPS over proxy");
-                    let tunneled = tunnel(
-                        conn,
-                        host.ok_or("no host in url")?.to_string(),
-                        port,
-                        self.user_agent.clone(),
-                        auth,
-                    )
-                    .await?;
-                    let mut tls_connector = tls();
-                    if !self.certs_verification {
-                        tls_connector.set_verify(boring::ssl::SslVerifyMode::NONE);
-                    }
-                    let mut conf = tls_connector.build().configure()?;
 
-                    tls_add_application_settings(&mut conf);
-
-                    let io = tokio_boring::connect(conf, host.ok_or("no host in url")?, tunneled)
+                    if proxy_dst.scheme() != Some(&Scheme::HTTPS) {
+                        return self
+                            .tunnel_tls(tls, conn, host, port, via, auth)
+                            .await;
+                    }
+                    // The CONNECT request is never sent as early data.
+                    let proxy_host = proxy_dst.host().ok_or("no host in proxy url")?;
+                    let proxy_port = proxy_dst.port_u16().unwrap_or(443);
+                    let conn = self
+                        .tls_handshake(tls, proxy_host, proxy_port, None, false, conn)
                         .await?;
-                    return Ok(Conn {
-                        inner: self.verbose.wrap(BoringTlsConn { inner: io }),
-                        is_proxy: false,
-                    });
+                    return self
+                        .tunnel_tls(tls, conn, host, port, via, auth)
+                        .await;
                 }
             }
         }
@@ -242,6 +480,37 @@ impl Connector {
         self.connect_with_maybe_proxy(proxy_dst, true).await
     }
 
+    /// Tunnels to `host` through the proxy connection `conn`, then performs
+    /// the TLS handshake with it.
+    async fn tunnel_tls<T>(
+        &self,
+        tls: &SslConnector,
+        conn: T,
+        host: &str,
+        port: u16,
+        via: String,
+        auth: Option<HeaderValue>,
+    ) -> Result<Conn, BoxError>
+    where
+        T: AsyncConn,
+    {
+        let tunneled = tunnel(
+            conn,
+            host.to_string(),
+            port,
+            self.user_agent.clone(),
+            auth,
+        )
+        .await?;
+        let io = self
+            .tls_handshake(tls, host, port, Some(via), self.early_data, tunneled)
+            .await?;
+        Ok(Conn {
+            inner: self.verbose.wrap(io),
+            is_proxy: false,
+        })
+    }
+
     pub fn set_keepalive(&mut self, dur: Option<Duration>) {
         match &mut self.inner {
             Inner::BoringTls { http, .. } => http.set_keepalive(dur),
@@ -286,17 +555,10 @@ impl Service<Uri> for Connector {
     fn call(&mut self, dst: Uri) -> Self::Future {
         log::debug!("starting new connection: {:?}", dst);
         let timeout = self.timeout;
-        for prox in self.proxies.iter() {
-            if let Some(proxy_scheme) = prox.intercept(&dst) {
-                return Box::pin(with_timeout(
-                    self.clone().connect_via_proxy(dst, proxy_scheme),
-                    timeout,
-                ));
-            }
-        }
+        let proxy_scheme = self.proxies.iter().find_map(|prox| prox.intercept(&dst));
 
         Box::pin(with_timeout(
-            self.clone().connect_with_maybe_proxy(dst, false),
+            self.clone().connect_retrying_ech(dst, proxy_scheme),
             timeout,
         ))
     }
@@ -450,22 +712,165 @@ fn tunnel_eof() -> BoxError {
 }
 
 mod boring_tls_conn {
+    use boring::ssl::{self, ErrorCode};
+    use foreign_types::ForeignTypeRef;
     use hyper::client::connect::{Connected, Connection};
     use pin_project_lite::pin_project;
     use std::{
         io::{self, IoSlice},
+        os::raw::c_int,
         pin::Pin,
         task::{Context, Poll},
     };
     use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
     use tokio_boring::SslStream;
 
+    use crate::browser::alps;
+
     pin_project! {
         pub(super) struct BoringTlsConn<T> {
-            #[pin] pub(super) inner: SslStream<T>,
+            #[pin] inner: SslStream<T>,
+            // What was written as early data, while the server may still
+            // reject it.
+            early_data: Option<Vec<u8>>,
+            // Rejected early data, still to be written again.
+            replay: Option<(Vec<u8>, usize)>,
+            peer_settings: Option<PeerSettings>,
         }
     }
 
+    /// The HTTP/2 settings the server sent in ALPS, on their way into the
+    /// first frame it sends over the connection, which is its `SETTINGS`.
+    ///
+    /// hyper has no way to be given settings up front, so they are applied
+    /// as if the server had sent them there, before its own.
+    enum PeerSettings {
+        /// The settings, and what was read of the frame so far.
+        Reading(Vec<(u16, u32)>, Vec<u8>),
+        /// The frame with the settings added, and how much of it was read.
+        Merged(Vec<u8>, usize),
+    }
+
+    impl<T> BoringTlsConn<T> {
+        pub(super) fn new(inner: SslStream<T>, peer_settings: Vec<(u16, u32)>) -> BoringTlsConn<T> {
+            let in_early_data = unsafe { boring_sys::SSL_in_early_data(inner.ssl().as_ptr()) };
+            BoringTlsConn {
+                inner,
+                early_data: if in_early_data == 1 {
+                    Some(Vec::new())
+                } else {
+                    None
+                },
+                replay: None,
+                peer_settings: if peer_settings.is_empty() {
+                    None
+                } else {
+                    Some(PeerSettings::Reading(peer_settings, Vec::new()))
+                },
+            }
+        }
+
+        pub(super) fn get_ref(&self) -> &T {
+            self.inner.get_ref()
+        }
+    }
+
+    impl<T: AsyncRead + AsyncWrite + Unpin> BoringTlsConn<T> {
+        /// Keeps a copy of what was just written while it is early data.
+        fn record_early_data(&mut self, written: &[u8]) {
+            if let Some(ref mut early_data) = self.early_data {
+                let ssl = self.inner.ssl().as_ptr();
+                if unsafe { boring_sys::SSL_in_early_data(ssl) } == 1 {
+                    early_data.extend_from_slice(written);
+                } else {
+                    self.early_data = None;
+                }
+            }
+        }
+
+        /// Recovers from the server rejecting the early data, by completing
+        /// the handshake and writing the early data again.
+        fn recover(&mut self, err: io::Error) -> io::Result<()> {
+            if let Some(early_data) = self.early_data.take() {
+                if is_early_data_rejected(&err) {
+                    log::debug!("TLS early data rejected, sending it again");
+                    let ssl = self.inner.ssl().as_ptr();
+                    unsafe { boring_sys::SSL_reset_early_data_reject(ssl) };
+                    self.replay = Some((early_data, 0));
+                    return Ok(());
+                }
+            }
+            Err(err)
+        }
+
+        /// Reads from the connection, merging the server's ALPS settings
+        /// into its first frame.
+        fn poll_read_merged(
+            &mut self,
+            cx: &mut Context,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            loop {
+                match self.peer_settings.take() {
+                    Some(PeerSettings::Reading(settings, mut read)) => {
+                        let mut chunk = [0; 1024];
+                        let mut chunk = ReadBuf::new(&mut chunk);
+                        let poll = Pin::new(&mut self.inner).poll_read(cx, &mut chunk);
+                        if let Poll::Ready(Ok(())) = poll {
+                            let eof = chunk.filled().is_empty();
+                            read.extend_from_slice(chunk.filled());
+                            let state = match alps::merge_server_h2(&settings, &read) {
+                                Some(merged) => PeerSettings::Merged(merged, 0),
+                                // The connection closed in the middle of the
+                                // frame, which is left as it was.
+                                None if eof => PeerSettings::Merged(read, 0),
+                                None => PeerSettings::Reading(settings, read),
+                            };
+                            self.peer_settings = Some(state);
+                            continue;
+                        }
+                        self.peer_settings = Some(PeerSettings::Reading(settings, read));
+                        return poll;
+                    }
+                    Some(PeerSettings::Merged(merged, pos)) => {
+                        let n = buf.remaining().min(merged.len() - pos);
+                        buf.put_slice(&merged[pos..pos + n]);
+                        if pos + n < merged.len() {
+                            self.peer_settings = Some(PeerSettings::Merged(merged, pos + n));
+                        }
+                        return Poll::Ready(Ok(()));
+                    }
+                    None => return Pin::new(&mut self.inner).poll_read(cx, buf),
+                }
+            }
+        }
+
+        fn poll_replay(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+            if let Some((buf, mut pos)) = self.replay.take() {
+                while pos < buf.len() {
+                    match Pin::new(&mut self.inner).poll_write(cx, &buf[pos..]) {
+                        Poll::Ready(Ok(n)) => pos += n,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                        Poll::Pending => {
+                            self.replay = Some((buf, pos));
+                            return Poll::Pending;
+                        }
+                    }
+                }
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Whether `err`, returned by a read or write, is BoringSSL reporting
+    /// that the server rejected the early data.
+    fn is_early_data_rejected(err: &io::Error) -> bool {
+        let rejected = ErrorCode::from_raw(boring_sys::SSL_ERROR_EARLY_DATA_REJECTED as c_int);
+        err.get_ref()
+            .and_then(|err| err.downcast_ref::<ssl::Error>())
+            .map_or(false, |err| err.code() == rejected)
+    }
+
     impl<T: Connection + AsyncRead + AsyncWrite + Unpin> Connection for BoringTlsConn<T> {
         fn connected(&self) -> Connected {
             if self.inner.ssl().selected_alpn_protocol() == Some(b"h2") {
@@ -482,8 +887,16 @@ mod boring_tls_conn {
             cx: &mut Context,
             buf: &mut ReadBuf<'_>,
         ) -> Poll<tokio::io::Result<()>> {
-            let this = self.project();
-            AsyncRead::poll_read(this.inner, cx, buf)
+            let this = self.get_mut();
+            loop {
+                if let Err(e) = futures_util::ready!(this.poll_replay(cx)) {
+                    return Poll::Ready(Err(e));
+                }
+                match this.poll_read_merged(cx, buf) {
+                    Poll::Ready(Err(e)) => this.recover(e)?,
+                    poll => return poll,
+                }
+            }
         }
     }
 
@@ -493,8 +906,20 @@ mod boring_tls_conn {
             cx: &mut Context,
             buf: &[u8],
         ) -> Poll<Result<usize, tokio::io::Error>> {
-            let this = self.project();
-            AsyncWrite::poll_write(this.inner, cx, buf)
+            let this = self.get_mut();
+            loop {
+                if let Err(e) = futures_util::ready!(this.poll_replay(cx)) {
+                    return Poll::Ready(Err(e));
+                }
+                match Pin::new(&mut this.inner).poll_write(cx, buf) {
+                    Poll::Ready(Ok(n)) => {
+                        this.record_early_data(&buf[..n]);
+                        return Poll::Ready(Ok(n));
+                    }
+                    Poll::Ready(Err(e)) => this.recover(e)?,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
         }
 
         fn poll_write_vectored(
@@ -502,6 +927,13 @@ mod boring_tls_conn {
             cx: &mut Context<'_>,
             bufs: &[IoSlice<'_>],
         ) -> Poll<Result<usize, io::Error>> {
+            if self.early_data.is_some() {
+                let buf = bufs
+                    .iter()
+                    .find(|b| !b.is_empty())
+                    .map_or(&[][..], |b| &**b);
+                return self.poll_write(cx, buf);
+            }
             let this = self.project();
             AsyncWrite::poll_write_vectored(this.inner, cx, bufs)
         }
@@ -514,8 +946,16 @@ mod boring_tls_conn {
             self: Pin<&mut Self>,
             cx: &mut Context,
         ) -> Poll<Result<(), tokio::io::Error>> {
-            let this = self.project();
-            AsyncWrite::poll_flush(this.inner, cx)
+            let this = self.get_mut();
+            loop {
+                if let Err(e) = futures_util::ready!(this.poll_replay(cx)) {
+                    return Poll::Ready(Err(e));
+                }
+                match Pin::new(&mut this.inner).poll_flush(cx) {
+                    Poll::Ready(Err(e)) => this.recover(e)?,
+                    poll => return poll,
+                }
+            }
         }
 
         fn poll_shutdown(
@@ -528,6 +968,239 @@ mod boring_tls_conn {
     }
 }
 
+mod ech {
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::os::raw::c_int;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use hyper::client::connect::dns::Name;
+
+    use crate::dns::Resolve;
+    use crate::error::BoxError;
+
+    // How long what a host sent when it rejected ECH is used for, unless it
+    // rejects that too, before its published configurations are tried again.
+    const RETRY_TTL: Duration = Duration::from_secs(60 * 60);
+
+    /// Where the ECH configurations of hosts come from.
+    pub(crate) struct EchConfigs {
+        manual: HashMap<String, Vec<u8>>,
+        resolver: Arc<dyn Resolve>,
+        // What to use for connections to a host which rejected ECH, until
+        // the given time: its retry configurations, or no ECH at all.
+        retry: Mutex<HashMap<String, (Option<Vec<u8>>, Instant)>>,
+    }
+
+    /// The server rejected ECH; the connection should be made again.
+    #[derive(Debug)]
+    pub(crate) struct EchRejected;
+
+    impl EchConfigs {
+        pub(crate) fn new(manual: HashMap<String, Vec<u8>>, resolver: Arc<dyn Resolve>) -> Self {
+            EchConfigs {
+                manual,
+                resolver,
+                retry: Mutex::new(HashMap::new()),
+            }
+        }
+
+        /// Returns the `ECHConfigList` to connect to `host` with, if any.
+        pub(crate) async fn lookup(&self, host: &str) -> Result<Option<Vec<u8>>, BoxError> {
+            {
+                let mut retry = self.retry.lock().unwrap();
+                match retry.get(host) {
+                    Some((configs, expires)) if *expires > Instant::now() => {
+                        return Ok(configs.clone());
+                    }
+                    Some(_) => {
+                        retry.remove(host);
+                    }
+                    None => {}
+                }
+            }
+            if let Some(manual) = self.manual.get(host) {
+                return Ok(Some(manual.clone()));
+            }
+            match host.parse::<Name>() {
+                Ok(name) => self.resolver.resolve_ech(name).await,
+                // IP addresses have no DNS records.
+                Err(_) => Ok(None),
+            }
+        }
+
+        /// Records that `host` rejected ECH, along with the retry
+        /// configurations it sent, which replace those it rejected.
+        pub(crate) fn rejected(&self, host: &str, retry_configs: Option<Vec<u8>>) {
+            self.retry
+                .lock()
+                .unwrap()
+                .insert(host.to_owned(), (retry_configs, Instant::now() + RETRY_TTL));
+        }
+    }
+
+    pub(crate) fn is_rejected<S>(err: &tokio_boring::HandshakeError<S>) -> bool {
+        let rejected = boring_sys::SSL_R_ECH_REJECTED as c_int;
+        err.as_ssl_error_stack().map_or(false, |stack| {
+            stack
+                .errors()
+                .iter()
+                .any(|e| boring_sys::ERR_GET_REASON(e.code()) == rejected)
+        })
+    }
+
+    impl fmt::Display for EchRejected {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("the server rejected Encrypted Client Hello")
+        }
+    }
+
+    impl std::error::Error for EchRejected {}
+}
+
+mod session_cache {
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Mutex};
+
+    use boring::error::ErrorStack;
+    use boring::ex_data::Index;
+    use boring::ssl::{
+        ConnectConfiguration, Ssl, SslConnectorBuilder, SslSession, SslSessionCacheMode,
+    };
+    use foreign_types::ForeignTypeRef;
+    use once_cell::sync::OnceCell;
+
+    // Bounds the memory used by a client talking to many hosts.
+    const MAX_KEYS: usize = 256;
+    // TLS 1.3 servers usually issue two tickets per connection.
+    const MAX_SESSIONS_PER_KEY: usize = 2;
+
+    /// Where a TLS session may be resumed: the host and port connected to,
+    /// and the proxy the connection went through, if any.
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    pub(crate) struct SessionKey {
+        host: String,
+        port: u16,
+        via: Option<String>,
+    }
+
+    impl SessionKey {
+        pub(crate) fn new(host: &str, port: u16, via: Option<String>) -> SessionKey {
+            SessionKey {
+                host: host.to_owned(),
+                port,
+                via,
+            }
+        }
+    }
+
+    /// The TLS sessions of a `Client`, to resume them on new connections.
+    ///
+    /// Every client has its own cache, so sessions are never shared between
+    /// clients impersonating different browsers. A cache is installed in a
+    /// single `SslContext`, the only one its sessions may be resumed with.
+    #[derive(Default)]
+    pub(crate) struct SessionCache {
+        inner: Mutex<Sessions>,
+    }
+
+    #[derive(Default)]
+    struct Sessions {
+        by_key: HashMap<SessionKey, VecDeque<SslSession>>,
+        // Keys from least to most recently stored to.
+        lru: VecDeque<SessionKey>,
+    }
+
+    impl SessionCache {
+        /// Makes connections built from `builder` store their sessions
+        /// under the key `resume` gave them.
+        ///
+        /// The connections resuming sessions from this cache must all come
+        /// from the connector `builder` builds.
+        pub(crate) fn install(self: &Arc<Self>, builder: &mut SslConnectorBuilder) {
+            let cache = Arc::downgrade(self);
+            builder.set_session_cache_mode(SslSessionCacheMode::CLIENT);
+            builder.set_new_session_callback(move |ssl, session| {
+                let key = key_index().ok().and_then(|index| ssl.ex_data(index));
+                if let (Some(cache), Some(key)) = (cache.upgrade(), key) {
+                    cache.insert(key.clone(), session);
+                }
+            });
+        }
+
+        /// Resumes the most recent session stored under `key` on the
+        /// connection `conf` configures, sending early data if `early_data`
+        /// is set, and stores its new sessions under `key`.
+        ///
+        /// `conf` must come from the connector this cache is installed in.
+        pub(crate) fn resume(
+            &self,
+            conf: &mut ConnectConfiguration,
+            key: SessionKey,
+            early_data: bool,
+        ) -> Result<(), ErrorStack> {
+            let session = self.take(&key);
+            conf.set_ex_data(key_index()?, key);
+
+            if let Some(session) = session {
+                // Safety: the session was established by a connection from
+                // the one context this cache is installed in, which `conf`
+                // comes from too.
+                unsafe { conf.set_session(&session)? };
+                if early_data {
+                    unsafe { boring_sys::SSL_set_early_data_enabled(conf.as_ptr(), 1) };
+                }
+            }
+            Ok(())
+        }
+
+        fn insert(&self, key: SessionKey, session: SslSession) {
+            let mut sessions = self.inner.lock().unwrap();
+            let Sessions { by_key, lru } = &mut *sessions;
+
+            if let Some(pos) = lru.iter().position(|k| *k == key) {
+                lru.remove(pos);
+            } else if lru.len() >= MAX_KEYS {
+                if let Some(oldest) = lru.pop_front() {
+                    by_key.remove(&oldest);
+                }
+            }
+            lru.push_back(key.clone());
+
+            let entry = by_key.entry(key).or_default();
+            if entry.len() >= MAX_SESSIONS_PER_KEY {
+                entry.pop_front();
+            }
+            entry.push_back(session);
+        }
+
+        /// Returns the most recent session stored under `key`.
+        ///
+        /// TLS 1.3 sessions are removed, as reusing a ticket lets observers
+        /// link the connections; others stay for later connections.
+        fn take(&self, key: &SessionKey) -> Option<SslSession> {
+            let mut sessions = self.inner.lock().unwrap();
+            let entry = sessions.by_key.get_mut(key)?;
+            let session = entry.back()?;
+            let single_use =
+                unsafe { boring_sys::SSL_SESSION_should_be_single_use(session.as_ptr()) } == 1;
+
+            if single_use {
+                entry.pop_back()
+            } else {
+                Some(session.to_owned())
+            }
+        }
+    }
+
+    /// The index under which a connection keeps its `SessionKey`.
+    fn key_index() -> Result<Index<Ssl, SessionKey>, ErrorStack> {
+        static INDEX: OnceCell<Index<Ssl, SessionKey>> = OnceCell::new();
+        INDEX.get_or_try_init(Ssl::new_ex_index).map(|index| *index)
+    }
+}
+
 #[cfg(feature = "socks")]
 mod socks {
     use std::io;
@@ -723,7 +1396,7 @@ mod verbose {
 
 #[cfg(test)]
 mod tests {
-    use super::tunnel;
+    use super::{tunnel, EchConfigs};
     use crate::proxy;
     use std::io::{Read, Write};
     use std::net::TcpListener;
@@ -884,4 +1557,25 @@ mod tests {
 
         rt.block_on(f).unwrap();
     }
+
+    #[test]
+    fn test_ech_retry_configs_kept() {
+        let ech = EchConfigs::new(
+            Default::default(),
+            std::sync::Arc::new(crate::dns::gai::GaiResolver::new()),
+        );
+        ech.rejected("example.com", Some(vec![1]));
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("new rt");
+        for _ in 0..2 {
+            let configs = rt.block_on(ech.lookup("example.com")).unwrap();
+            assert_eq!(configs, Some(vec![1]));
+        }
+
+        ech.rejected("example.com", None);
+        assert_eq!(rt.block_on(ech.lookup("example.com")).unwrap(), None);
+    }
 }
@@ -2,11 +2,14 @@
 
 use std::convert::TryInto;
 use std::fmt;
+use std::fmt::Write as _;
+use std::io::{self, BufRead};
 use std::sync::RwLock;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::header::{HeaderValue, SET_COOKIE};
 use bytes::Bytes;
+use serde::{Deserialize, Serialize};
 
 /// Actions for a persistent cookie store providing session support.
 pub trait CookieStore: Send + Sync {
@@ -25,12 +28,29 @@ pub struct Cookie<'a>(cookie_crate::Cookie<'a>);
 /// This type is exposed to allow creating one and filling it with some
 /// existing cookies more easily, before creating a `Client`.
 ///
-/// For more advanced scenarios, such as needing to serialize the store or
-/// manipulate it between requests, you may refer to the
+/// A `Jar` can be saved and loaded again, as JSON or in the Netscape
+/// `cookies.txt` format used by curl and browser extensions. For more advanced
+/// scenarios, such as needing to manipulate the store between requests, you
+/// may refer to the
 /// [reqwest_cookie_store crate](https://crates.io/crates/reqwest_cookie_store).
 #[derive(Debug, Default)]
 pub struct Jar(RwLock<cookie_store::CookieStore>);
 
+/// A cookie as saved by a `Jar`, along with the origin it applies to.
+#[derive(Debug, Deserialize, Serialize)]
+struct SavedCookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<String>,
+    /// In seconds since the Unix epoch, or `None` for a session cookie.
+    expires: Option<u64>,
+}
+
 // ===== impl Cookie =====
 
 impl<'a> Cookie<'a> {
@@ -99,6 +119,22 @@ impl<'a> Cookie<'a> {
     }
 }
 
+impl Cookie<'static> {
+    /// Copies a cookie of a `Jar`, with the domain, path and expiry time the
+    /// jar applies it with, whether or not it was set with these attributes.
+    fn from_stored(cookie: &cookie_store::Cookie<'static>) -> Option<Cookie<'static>> {
+        let (domain, _) = stored_domain(cookie)?;
+        let mut raw = (**cookie).clone();
+        raw.set_domain(domain.to_owned());
+        raw.set_path(String::from(&cookie.path));
+        if let cookie_store::CookieExpiration::AtUtc(at) = cookie.expires {
+            raw.set_max_age(None);
+            raw.set_expires(at);
+        }
+        Some(Cookie(raw))
+    }
+}
+
 impl<'a> fmt::Debug for Cookie<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.0.fmt(f)
@@ -162,6 +198,284 @@ impl Jar {
             .into_iter();
         self.0.write().unwrap().store_response_cookies(cookies, url);
     }
+
+    /// Returns all unexpired cookies in this jar.
+    ///
+    /// The domain, path and expiry time of each cookie are the ones the jar
+    /// applies it with, even if it was set without these attributes. A cookie
+    /// without an expiry time is a session cookie.
+    pub fn iter(&self) -> impl Iterator<Item = Cookie<'static>> {
+        let store = self.0.read().unwrap();
+        store
+            .iter_unexpired()
+            .filter_map(Cookie::from_stored)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Returns the cookies this jar sends with a request to `url`.
+    pub fn matching(&self, url: &url::Url) -> impl Iterator<Item = Cookie<'static>> {
+        let store = self.0.read().unwrap();
+        store
+            .matches(url)
+            .into_iter()
+            .filter_map(Cookie::from_stored)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Remove the cookie named `name` from this jar, using the domain and
+    /// path [`Jar::iter`] returns for it.
+    ///
+    /// Returns the removed cookie, if there was one.
+    pub fn remove(&self, domain: &str, path: &str, name: &str) -> Option<Cookie<'static>> {
+        let removed = self.0.write().unwrap().remove(domain, path, name)?;
+        Cookie::from_stored(&removed)
+    }
+
+    /// Remove the cookies of `domain` and of its subdomains from this jar.
+    pub fn clear_domain(&self, domain: &str) {
+        let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+        let suffix = format!(".{}", domain);
+        self.retain(|cookie| match stored_domain(cookie) {
+            Some((cookie_domain, _)) => {
+                cookie_domain != domain && !cookie_domain.ends_with(&suffix)
+            }
+            None => true,
+        });
+    }
+
+    /// Remove the session cookies from this jar, as a browser does when it
+    /// is restarted.
+    pub fn clear_session_cookies(&self) {
+        self.retain(|cookie| !matches!(cookie.expires, cookie_store::CookieExpiration::SessionEnd));
+    }
+
+    /// Remove all cookies from this jar.
+    pub fn clear(&self) {
+        *self.0.write().unwrap() = cookie_store::CookieStore::default();
+    }
+
+    fn retain<F>(&self, mut keep: F)
+    where
+        F: FnMut(&cookie_store::Cookie<'static>) -> bool,
+    {
+        let mut store = self.0.write().unwrap();
+        let removed = store
+            .iter_any()
+            .filter(|cookie| !keep(cookie))
+            .filter_map(|cookie| {
+                let (domain, _) = stored_domain(cookie)?;
+                Some((
+                    domain.to_owned(),
+                    String::from(&cookie.path),
+                    cookie.name().to_owned(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        for (domain, path, name) in removed {
+            store.remove(&domain, &path, &name);
+        }
+    }
+
+    /// Save the cookies of this jar as JSON.
+    ///
+    /// Unlike browsers, this includes session cookies, so a session can be
+    /// continued with [`Jar::load_json`] after a restart.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if writing to `writer` fails.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `json` feature to be enabled.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn save_json<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, &self.saved_cookies())?;
+        Ok(())
+    }
+
+    /// Add the cookies saved by [`Jar::save_json`] to this jar.
+    ///
+    /// Cookies which expired in the meantime are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if reading from `reader` fails, or if it does not
+    /// contain cookies saved as JSON.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `json` feature to be enabled.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn load_json<R: io::Read>(&self, reader: R) -> io::Result<()> {
+        let cookies: Vec<SavedCookie> = serde_json::from_reader(reader)?;
+        self.load_cookies(cookies);
+        Ok(())
+    }
+
+    /// Save the cookies of this jar in the Netscape `cookies.txt` format.
+    ///
+    /// Session cookies are saved with an expiry time of `0`, as curl does.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if writing to `writer` fails.
+    pub fn save_netscape<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "# Netscape HTTP Cookie File")?;
+        for cookie in self.saved_cookies() {
+            let mut domain = String::new();
+            if cookie.http_only {
+                domain.push_str("#HttpOnly_");
+            }
+            if !cookie.host_only {
+                domain.push('.');
+            }
+            domain.push_str(&cookie.domain);
+
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                domain,
+                if cookie.host_only { "FALSE" } else { "TRUE" },
+                cookie.path,
+                if cookie.secure { "TRUE" } else { "FALSE" },
+                cookie.expires.unwrap_or(0),
+                cookie.name,
+                cookie.value,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Add the cookies of a Netscape `cookies.txt` file to this jar, such as
+    /// the ones exported from a browser or saved by curl.
+    ///
+    /// Malformed lines, and cookies which already expired, are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if reading from `reader` fails.
+    pub fn load_netscape<R: BufRead>(&self, reader: R) -> io::Result<()> {
+        let mut cookies = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => (line, true),
+                None => (line.as_str(), false),
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.trim_end_matches('\r').split('\t').collect::<Vec<_>>();
+            let (domain, subdomains, path, secure, expires, name, value) = match fields[..] {
+                [domain, subdomains, path, secure, expires, name, value] => {
+                    (domain, subdomains, path, secure, expires, name, value)
+                }
+                _ => continue,
+            };
+            let expires = match expires.parse::<u64>() {
+                Ok(0) => None,
+                Ok(expires) => Some(expires),
+                Err(_) => continue,
+            };
+            cookies.push(SavedCookie {
+                name: name.to_owned(),
+                value: value.to_owned(),
+                domain: domain.trim_start_matches('.').to_owned(),
+                host_only: !subdomains.eq_ignore_ascii_case("TRUE"),
+                path: path.to_owned(),
+                secure: secure.eq_ignore_ascii_case("TRUE"),
+                http_only,
+                same_site: None,
+                expires,
+            });
+        }
+        self.load_cookies(cookies);
+        Ok(())
+    }
+
+    fn saved_cookies(&self) -> Vec<SavedCookie> {
+        let store = self.0.read().unwrap();
+        store
+            .iter_unexpired()
+            .filter_map(|cookie| {
+                let (domain, host_only) = stored_domain(cookie)?;
+                let expires = match cookie.expires {
+                    cookie_store::CookieExpiration::AtUtc(at) => {
+                        Some(u64::try_from(at.unix_timestamp()).unwrap_or(0))
+                    }
+                    cookie_store::CookieExpiration::SessionEnd => None,
+                };
+                Some(SavedCookie {
+                    name: cookie.name().to_owned(),
+                    value: cookie.value().to_owned(),
+                    domain: domain.to_owned(),
+                    host_only,
+                    path: String::from(&cookie.path),
+                    secure: cookie.secure().unwrap_or(false),
+                    http_only: cookie.http_only().unwrap_or(false),
+                    same_site: cookie.same_site().map(|same_site| same_site.to_string()),
+                    expires,
+                })
+            })
+            .collect()
+    }
+
+    fn load_cookies(&self, cookies: Vec<SavedCookie>) {
+        let now = SystemTime::now();
+        let mut store = self.0.write().unwrap();
+        for cookie in cookies {
+            // Loaded as if the origin had just set them, with a `Max-Age`
+            // counting down to their original expiry time.
+            let mut set_cookie = format!("{}={}; Path={}", cookie.name, cookie.value, cookie.path);
+            if !cookie.host_only {
+                let _ = write!(set_cookie, "; Domain={}", cookie.domain);
+            }
+            if cookie.secure {
+                set_cookie.push_str("; Secure");
+            }
+            if cookie.http_only {
+                set_cookie.push_str("; HttpOnly");
+            }
+            if let Some(ref same_site) = cookie.same_site {
+                let _ = write!(set_cookie, "; SameSite={}", same_site);
+            }
+            if let Some(expires) = cookie.expires {
+                let max_age = (UNIX_EPOCH + Duration::from_secs(expires))
+                    .duration_since(now)
+                    .map(|left| left.as_secs())
+                    .unwrap_or(0);
+                if max_age == 0 {
+                    continue;
+                }
+                let _ = write!(set_cookie, "; Max-Age={}", max_age);
+            }
+
+            let url = match url::Url::parse(&format!("https://{}{}", cookie.domain, cookie.path)) {
+                Ok(url) => url,
+                Err(_) => continue,
+            };
+            let set_cookie = match cookie_crate::Cookie::parse(set_cookie) {
+                Ok(set_cookie) => set_cookie,
+                Err(_) => continue,
+            };
+            store.store_response_cookies(std::iter::once(set_cookie), &url);
+        }
+    }
+}
+
+/// The domain a cookie of a `Jar` applies to, and whether it is host-only.
+fn stored_domain<'a>(cookie: &'a cookie_store::Cookie<'static>) -> Option<(&'a str, bool)> {
+    match cookie.domain {
+        cookie_store::CookieDomain::HostOnly(ref domain) => Some((domain, true)),
+        cookie_store::CookieDomain::Suffix(ref domain) => Some((domain, false)),
+        cookie_store::CookieDomain::NotPresent | cookie_store::CookieDomain::Empty => None,
+    }
 }
 
 impl CookieStore for Jar {
@@ -16,6 +16,9 @@ pub type Addrs = Box<dyn Iterator<Item = SocketAddr> + Send>;
 /// Alias for the `Future` type returned by a DNS resolver.
 pub type Resolving = Pin<Box<dyn Future<Output = Result<Addrs, BoxError>> + Send>>;
 
+/// Alias for the `Future` type returned by an ECH configuration lookup.
+pub type ResolvingEch = Pin<Box<dyn Future<Output = Result<Option<Vec<u8>>, BoxError>> + Send>>;
+
 /// Trait for customizing DNS resolution in reqwest.
 pub trait Resolve: Send + Sync {
     /// Performs DNS resolution on a `Name`.
@@ -27,6 +30,15 @@ pub trait Resolve: Send + Sync {
     ///  * Since trait objects cannot make use of associated types, it requires
     ///    wrapping the returned `Future` and its contained `Iterator` with `Box`.
     fn resolve(&self, name: Name) -> Resolving;
+
+    /// Looks up the `ECHConfigList` a host publishes, usually in the `ech`
+    /// parameter of its DNS `HTTPS` record.
+    ///
+    /// When one is found, the TLS handshake with the host uses Encrypted
+    /// Client Hello, as Chrome does. The default implementation finds none.
+    fn resolve_ech(&self, _name: Name) -> ResolvingEch {
+        Box::pin(futures_util::future::ready(Ok(None)))
+    }
 }
 
 #[derive(Clone)]
@@ -81,4 +93,8 @@ impl Resolve for DnsResolverWithOverrides {
             None => self.dns_resolver.resolve(name),
         }
     }
+
+    fn resolve_ech(&self, name: Name) -> ResolvingEch {
+        self.dns_resolver.resolve_ech(name)
+    }
 }
@@ -12,14 +12,16 @@
 //! It handles many of the things that most people just expect an HTTP client
 //! to do for them.
 //!
-//! - Async Client
+//! - Async and [blocking] Clients
 //! - Plain bodies, [JSON](#json), [urlencoded](#forms), [multipart](multipart)
 //! - Customizable [redirect policy](#redirect-policies)
 //! - HTTP [Proxies](#proxies)
 //! - Uses system-native [TLS](#tls)
 //! - Cookies
 //!
-//! The [`reqwest_impersonate::Client`][client] is asynchronous.
+//! The [`reqwest_impersonate::Client`][client] is asynchronous. For applications wishing
+//! to only make a few HTTP requests, the [`reqwest_impersonate::blocking`](blocking) API
+//! may be more convenient.
 //!
 //! Additional learning resources include:
 //!
@@ -122,6 +124,13 @@
 //! maximum redirect chain of 10 hops. To customize this behavior, a
 //! [`redirect::Policy`][redirect] can be used with a `ClientBuilder`.
 //!
+//! ## Retries
+//!
+//! By default, a `Client` only sends a request again when an HTTP/2 server
+//! gracefully closed the connection before processing it. Retrying on
+//! connection resets, timeouts or statuses such as `503 Service Unavailable`,
+//! with exponential backoff, is configured with a [`retry::Policy`][retry].
+//!
 //! ## Cookies
 //!
 //! The automatic storing and sending of session cookies can be enabled with
@@ -177,6 +186,8 @@
 //!   while using root certificates from the `webpki-roots` crate.
 //! - **rustls-tls-native-roots**: Enables TLS functionality provided by `rustls`,
 //!   while using root certificates from the `rustls-native-certs` crate.
+//! - **blocking**: Provides the [blocking][] client API.
+//! - **cache**: Provides an HTTP [cache](crate::cache) for responses.
 //! - **cookies**: Provides cookie session support.
 //! - **gzip**: Provides response body gzip decompression.
 //! - **brotli**: Provides response body brotli decompression.
@@ -186,15 +197,21 @@
 //! - **stream**: Adds support for `futures::Stream`.
 //! - **socks**: Provides SOCKS5 proxy support.
 //!   threadpool using `getaddrinfo`.
+//! - **toml**: Allows loading impersonation profiles from TOML.
+//! - **test-util**: Provides a local server recording the TLS and HTTP/2
+//!   fingerprint of clients.
+//! - **http3**: Sends requests for `Version::HTTP_3` over QUIC, using quiche.
 //!
 //!
 //! [hyper]: http://hyper.rs
+//! [blocking]: ./blocking/index.html
 //! [client]: ./struct.Client.html
 //! [response]: ./struct.Response.html
 //! [get]: ./fn.get.html
 //! [builder]: ./struct.RequestBuilder.html
 //! [serde]: http://serde.rs
 //! [redirect]: crate::redirect
+//! [retry]: crate::retry
 //! [Proxy]: ./struct.Proxy.html
 //! [cargo-features]: https://doc.rust-lang.org/stable/cargo/reference/manifest.html#the-features-section
 
@@ -277,24 +294,34 @@ if_hyper! {
     #[macro_use]
     extern crate doc_comment;
 
-    #[cfg(test)]
+    #[cfg(all(test, feature = "blocking"))]
     doctest!("../README.md");
 
     pub use self::async_impl::{
         Body, Client, ClientBuilder, Request, RequestBuilder, Response, Upgraded,
     };
     pub use self::proxy::{Proxy,NoProxy};
+    pub use self::tls::{Certificate, Identity};
     #[cfg(feature = "multipart")]
     pub use self::async_impl::multipart;
 
 
+    pub mod alt_svc;
     mod async_impl;
+    #[cfg(feature = "blocking")]
+    pub mod blocking;
+    #[cfg(feature = "cache")]
+    pub mod cache;
     mod connect;
     #[cfg(feature = "cookies")]
     pub mod cookie;
     pub mod dns;
+    pub mod middleware;
     mod proxy;
     pub mod redirect;
+    pub mod retry;
+    #[cfg(feature = "test-util")]
+    pub mod test_util;
     pub mod tls;
     mod util;
 }
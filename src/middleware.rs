@@ -0,0 +1,116 @@
+//! Middleware
+//!
+//! Middleware added with `ClientBuilder::middleware` wraps every request a
+//! `Client` sends: the initial request, each redirected hop and each retry.
+//! It runs after the client added its default headers and cookies, so it
+//! sees the request as it is about to be sent, and can change it, send it
+//! more than once or answer it without sending it at all.
+//!
+//! Middleware runs in the order it was added, the first one seeing the
+//! request first and the response last.
+//!
+//! # Example
+//!
+//! ```rust
+//! use reqwest_impersonate::middleware::{BoxFuture, Middleware, Next};
+//! use reqwest_impersonate::Body;
+//!
+//! struct Logger;
+//!
+//! impl Middleware for Logger {
+//!     fn handle<'a>(
+//!         &'a self,
+//!         req: http::Request<Body>,
+//!         next: Next,
+//!     ) -> BoxFuture<'a, reqwest_impersonate::Result<http::Response<Body>>> {
+//!         Box::pin(async move {
+//!             let (method, uri) = (req.method().clone(), req.uri().clone());
+//!             let res = next.run(req).await;
+//!             match res {
+//!                 Ok(ref res) => println!("{} {} -> {}", method, uri, res.status()),
+//!                 Err(ref err) => println!("{} {} failed: {}", method, uri, err),
+//!             }
+//!             res
+//!         })
+//!     }
+//! }
+//!
+//! # fn run() -> Result<(), reqwest_impersonate::Error> {
+//! let client = reqwest_impersonate::Client::builder()
+//!     .middleware(Logger)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::async_impl::client::ClientRef;
+use crate::Body;
+
+/// An owned, boxed future, as returned by [`Middleware::handle`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A step wrapping the requests sent by a `Client`.
+///
+/// See the [module documentation](self) for details.
+pub trait Middleware: Send + Sync + 'static {
+    /// Handles a request, usually by passing it on with [`Next::run`].
+    ///
+    /// The request body can be read with [`Body::as_bytes`], unless it is a
+    /// stream. Only requests with such a buffered body can be passed to a
+    /// clone of `next` more than once.
+    fn handle<'a>(
+        &'a self,
+        req: http::Request<Body>,
+        next: Next,
+    ) -> BoxFuture<'a, crate::Result<http::Response<Body>>>;
+}
+
+/// The rest of the middleware stack, ending with the client sending the
+/// request.
+#[derive(Clone)]
+pub struct Next {
+    client: Arc<ClientRef>,
+    index: usize,
+    alt_svc: bool,
+}
+
+impl Next {
+    pub(crate) fn new(client: Arc<ClientRef>, alt_svc: bool) -> Next {
+        Next {
+            client,
+            index: 0,
+            alt_svc,
+        }
+    }
+
+    /// Passes the request to the next middleware, or sends it if there is
+    /// none left.
+    pub fn run(
+        self,
+        req: http::Request<Body>,
+    ) -> BoxFuture<'static, crate::Result<http::Response<Body>>> {
+        match self.client.middleware.get(self.index).cloned() {
+            Some(middleware) => {
+                let next = Next {
+                    index: self.index + 1,
+                    ..self
+                };
+                Box::pin(async move { middleware.handle(req, next).await })
+            }
+            None => self.client.send_direct(req, self.alt_svc),
+        }
+    }
+}
+
+impl fmt::Debug for Next {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Next")
+            .field("remaining", &(self.client.middleware.len() - self.index))
+            .finish()
+    }
+}
@@ -0,0 +1,598 @@
+//! Retry Handling
+//!
+//! By default, a `Client` sends a request again, at most twice, when an
+//! HTTP/2 server gracefully shuts down the connection it was sent on before
+//! processing it. To retry on other failures, such as connection resets,
+//! timeouts or `503 Service Unavailable` responses, a `retry::Policy` can be
+//! used with a `ClientBuilder` or a single `RequestBuilder`.
+//!
+//! Requests whose body is a stream are never retried, since their body
+//! cannot be sent twice.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::header::{HeaderMap, RETRY_AFTER};
+use crate::{Method, StatusCode};
+
+/// A type that controls when and how often failed requests are sent again.
+///
+/// The default value retries a request at most twice, and only when an
+/// HTTP/2 server sent a graceful `GOAWAY` before processing it.
+///
+/// - `limited` retries up to a maximum number of times on connection resets,
+///   timeouts and configurable status codes, with exponential backoff.
+/// - `none` disables all retries.
+///
+/// Whatever the policy, the total `timeout` of a request also bounds its
+/// retries and the time spent backing off between them.
+///
+/// # Example
+///
+/// ```rust
+/// # use reqwest_impersonate::{Error, retry, StatusCode};
+/// # use std::time::Duration;
+/// #
+/// # fn run() -> Result<(), Error> {
+/// let policy = retry::Policy::limited(3)
+///     .statuses([
+///         StatusCode::TOO_MANY_REQUESTS,
+///         StatusCode::BAD_GATEWAY,
+///         StatusCode::SERVICE_UNAVAILABLE,
+///     ])
+///     .backoff(Duration::from_millis(200), Duration::from_secs(5));
+/// let client = reqwest_impersonate::Client::builder()
+///     .retry(policy)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    max_retries: usize,
+    connection_reset: bool,
+    timeout: bool,
+    statuses: Vec<StatusCode>,
+    non_idempotent: bool,
+    retry_after: bool,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    budget: Option<Budget>,
+}
+
+/// A budget limiting retries to a share of the requests sent, shared by
+/// all policies it is set on.
+///
+/// A budget keeps a failing server from receiving several times the usual
+/// load from retries. Over each window (10 seconds by default), retries
+/// may add `ratio` times the number of requests sent, plus a minimum of
+/// `min_retries` (10 by default) so that clients sending few requests can
+/// still retry.
+///
+/// # Example
+///
+/// ```rust
+/// # use reqwest_impersonate::retry;
+/// // Retries may add at most 20% to the requests sent.
+/// let budget = retry::Budget::new(0.2);
+/// let policy = retry::Policy::limited(3).budget(budget);
+/// ```
+#[derive(Clone)]
+pub struct Budget {
+    ratio: f32,
+    min_retries: u32,
+    window: Duration,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+impl Policy {
+    /// Create a `Policy` sending a request again at most `max` times.
+    ///
+    /// It retries on connection resets and timeouts, waiting between
+    /// attempts with exponential backoff starting at 100 milliseconds and
+    /// capped at 10 seconds, with jitter. No response status is retried
+    /// until set with [`Policy::statuses`].
+    pub fn limited(max: usize) -> Self {
+        Self {
+            max_retries: max,
+            connection_reset: true,
+            timeout: true,
+            statuses: Vec::new(),
+            non_idempotent: false,
+            retry_after: true,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            budget: None,
+        }
+    }
+
+    /// Create a `Policy` that never retries a request.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Policy::default()
+        }
+    }
+
+    /// Set whether requests are retried when their connection is reset or
+    /// closed before a response is received.
+    ///
+    /// This usually happens when a pooled connection was closed by the
+    /// server while idle.
+    pub fn connection_reset(mut self, enable: bool) -> Self {
+        self.connection_reset = enable;
+        self
+    }
+
+    /// Set whether requests are retried when connecting times out.
+    ///
+    /// See [`ClientBuilder::connect_timeout`](crate::ClientBuilder::connect_timeout).
+    pub fn timeout(mut self, enable: bool) -> Self {
+        self.timeout = enable;
+        self
+    }
+
+    /// Set the response statuses a request is retried on, such as
+    /// `429 Too Many Requests` or `503 Service Unavailable`.
+    ///
+    /// The response of the last attempt is returned when no retry is left.
+    pub fn statuses<I>(mut self, statuses: I) -> Self
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        self.statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Set whether requests with a non-idempotent method, such as `POST`,
+    /// are retried when the server may already have processed them.
+    ///
+    /// Requests the server never processed, because connecting failed or an
+    /// HTTP/2 server refused them with a graceful `GOAWAY`, are retried
+    /// whatever their method.
+    ///
+    /// Defaults to `false`.
+    pub fn non_idempotent(mut self, enable: bool) -> Self {
+        self.non_idempotent = enable;
+        self
+    }
+
+    /// Set whether the `Retry-After` header of a retried response is used as
+    /// the delay before the next attempt.
+    ///
+    /// When the server asks to wait longer than the maximum backoff, its
+    /// response is returned instead of being retried.
+    ///
+    /// Defaults to `true`.
+    pub fn retry_after(mut self, enable: bool) -> Self {
+        self.retry_after = enable;
+        self
+    }
+
+    /// Set the exponential backoff between attempts.
+    ///
+    /// The delay before the `n`th retry is `base * 2^(n - 1)`, capped at
+    /// `max`.
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_delay = base;
+        self.max_delay = max;
+        self
+    }
+
+    /// Set whether the backoff delay is randomized, picking a delay between
+    /// zero and the exponential one.
+    ///
+    /// This spreads out the retries of clients that failed at the same time.
+    ///
+    /// Defaults to `true`.
+    pub fn jitter(mut self, enable: bool) -> Self {
+        self.jitter = enable;
+        self
+    }
+
+    /// Set a `Budget` limiting the retries of all requests using it.
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Policy::default()
+    }
+
+    /// Called once for every request sent, before any retry of it.
+    pub(crate) fn on_request(&self) {
+        if let Some(ref budget) = self.budget {
+            budget.deposit();
+        }
+    }
+
+    /// Returns the delay to wait before sending the request again after it
+    /// failed with `err`, or `None` if it must not be retried.
+    pub(crate) fn check_error(
+        &self,
+        method: &Method,
+        retries: usize,
+        err: &(dyn StdError + 'static),
+    ) -> Option<Duration> {
+        let delay = match classify(err)? {
+            Failure::GoAway => Duration::ZERO,
+            Failure::Timeout { sent } if self.timeout && self.may_resend(method, sent) => {
+                self.backoff_delay(retries)
+            }
+            Failure::Reset if self.connection_reset && self.may_resend(method, true) => {
+                self.backoff_delay(retries)
+            }
+            _ => return None,
+        };
+        self.withdraw(retries).then_some(delay)
+    }
+
+    /// Returns the delay to wait before sending the request again after it
+    /// was answered with `status`, or `None` if the response must be kept.
+    pub(crate) fn check_status(
+        &self,
+        method: &Method,
+        retries: usize,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> Option<Duration> {
+        if !self.statuses.contains(&status) || !self.may_resend(method, true) {
+            return None;
+        }
+
+        let delay = match self.retry_after_delay(headers) {
+            Some(delay) if delay > self.max_delay => {
+                log::debug!("Retry-After of {:?} is over the maximum backoff", delay);
+                return None;
+            }
+            Some(delay) => delay,
+            None => self.backoff_delay(retries),
+        };
+        self.withdraw(retries).then_some(delay)
+    }
+
+    fn may_resend(&self, method: &Method, sent: bool) -> bool {
+        !sent || self.non_idempotent || method.is_idempotent()
+    }
+
+    fn withdraw(&self, retries: usize) -> bool {
+        if retries >= self.max_retries {
+            log::trace!("retry count too high");
+            return false;
+        }
+        match self.budget {
+            Some(ref budget) if !budget.withdraw() => {
+                log::debug!("retry budget exhausted");
+                false
+            }
+            _ => true,
+        }
+    }
+
+    fn backoff_delay(&self, retries: usize) -> Duration {
+        let factor = 1u32.checked_shl(retries as u32).unwrap_or(u32::MAX);
+        let delay = self
+            .base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+
+        if self.jitter && !delay.is_zero() {
+            let nanos = delay.as_nanos().min(u64::MAX as u128) as u64;
+            Duration::from_nanos(crate::util::fast_random() % (nanos + 1))
+        } else {
+            delay
+        }
+    }
+
+    fn retry_after_delay(&self, headers: &HeaderMap) -> Option<Duration> {
+        if !self.retry_after {
+            return None;
+        }
+        let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+        parse_retry_after(value, SystemTime::now())
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy::limited(2)
+            .connection_reset(false)
+            .timeout(false)
+            .backoff(Duration::ZERO, Duration::ZERO)
+    }
+}
+
+impl Budget {
+    /// Create a `Budget` allowing retries to add `ratio` times the number of
+    /// requests sent.
+    pub fn new(ratio: f32) -> Self {
+        Self {
+            ratio: ratio.max(0.0),
+            min_retries: 10,
+            window: Duration::from_secs(10),
+            state: Arc::new(Mutex::new(BudgetState::new(Instant::now()))),
+        }
+    }
+
+    /// Set the number of retries allowed in each window, whatever the
+    /// number of requests sent.
+    ///
+    /// Defaults to 10.
+    pub fn min_retries(mut self, min: u32) -> Self {
+        self.min_retries = min;
+        self
+    }
+
+    /// Set the window over which requests and retries are counted.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    fn deposit(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.roll(self.window, Instant::now());
+        state.requests = state.requests.saturating_add(1);
+    }
+
+    fn withdraw(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.roll(self.window, Instant::now());
+        let allowed = self.min_retries as f64 + state.requests as f64 * self.ratio as f64;
+        if (state.retries as f64) < allowed {
+            state.retries += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Budgets are equal when they share the same counts.
+impl PartialEq for Budget {
+    fn eq(&self, other: &Budget) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+            && self.ratio == other.ratio
+            && self.min_retries == other.min_retries
+            && self.window == other.window
+    }
+}
+
+impl fmt::Debug for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Budget")
+            .field("ratio", &self.ratio)
+            .field("min_retries", &self.min_retries)
+            .field("window", &self.window)
+            .finish()
+    }
+}
+
+struct BudgetState {
+    started: Instant,
+    requests: u32,
+    retries: u32,
+}
+
+impl BudgetState {
+    fn new(now: Instant) -> Self {
+        BudgetState {
+            started: now,
+            requests: 0,
+            retries: 0,
+        }
+    }
+
+    fn roll(&mut self, window: Duration, now: Instant) {
+        if now.duration_since(self.started) >= window {
+            *self = BudgetState::new(now);
+        }
+    }
+}
+
+// pub(crate)
+
+#[derive(Debug, PartialEq)]
+enum Failure {
+    /// An HTTP/2 server refused the request with a graceful `GOAWAY`.
+    GoAway,
+    /// Connecting or waiting on the connection timed out.
+    Timeout { sent: bool },
+    /// The connection was reset or closed before a response was received.
+    Reset,
+}
+
+fn classify(err: &(dyn StdError + 'static)) -> Option<Failure> {
+    // Middleware may wrap the hyper error in a `crate::Error`.
+    let connecting = std::iter::successors(Some(err), |err| err.source())
+        .filter_map(|err| err.downcast_ref::<hyper::Error>())
+        .any(|err| err.is_connect());
+
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<h2::Error>() {
+            // They sent us a graceful shutdown, try with a new connection!
+            if err.is_go_away() && err.is_remote() && err.reason() == Some(h2::Reason::NO_ERROR) {
+                return Some(Failure::GoAway);
+            }
+        }
+        if err.is::<crate::error::TimedOut>() {
+            return Some(Failure::Timeout { sent: !connecting });
+        }
+        if let Some(err) = err.downcast_ref::<hyper::Error>() {
+            if err.is_incomplete_message() || err.is_canceled() {
+                return Some(Failure::Reset);
+            }
+        }
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            match err.kind() {
+                io::ErrorKind::TimedOut => return Some(Failure::Timeout { sent: !connecting }),
+                io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+                    if !connecting =>
+                {
+                    return Some(Failure::Reset)
+                }
+                _ => (),
+            }
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// Parses a `Retry-After` value, either a number of seconds or an HTTP
+/// date such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+#[test]
+fn test_retry_policy_default() {
+    let policy = Policy::default();
+    assert!(policy.is_default());
+    assert!(!Policy::default().jitter(false).is_default());
+    assert!(!Policy::default()
+        .backoff(Duration::from_millis(1), Duration::ZERO)
+        .is_default());
+
+    let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+    assert_eq!(classify(&reset), Some(Failure::Reset));
+    assert_eq!(policy.check_error(&Method::GET, 0, &reset), None);
+    assert_eq!(
+        policy.check_status(
+            &Method::GET,
+            0,
+            StatusCode::SERVICE_UNAVAILABLE,
+            &HeaderMap::new()
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_retry_policy_limited() {
+    let policy = Policy::limited(2).jitter(false);
+    let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+
+    assert_eq!(
+        policy.check_error(&Method::GET, 0, &reset),
+        Some(Duration::from_millis(100))
+    );
+    assert_eq!(
+        policy.check_error(&Method::GET, 1, &reset),
+        Some(Duration::from_millis(200))
+    );
+    assert_eq!(policy.check_error(&Method::GET, 2, &reset), None);
+
+    // the server may have processed it
+    assert_eq!(policy.check_error(&Method::POST, 0, &reset), None);
+    let policy = policy.non_idempotent(true);
+    assert!(policy.check_error(&Method::POST, 0, &reset).is_some());
+}
+
+#[test]
+fn test_retry_policy_backoff_capped() {
+    let policy = Policy::limited(100)
+        .jitter(false)
+        .backoff(Duration::from_secs(1), Duration::from_secs(30));
+
+    assert_eq!(policy.backoff_delay(3), Duration::from_secs(8));
+    assert_eq!(policy.backoff_delay(5), Duration::from_secs(30));
+    assert_eq!(policy.backoff_delay(64), Duration::from_secs(30));
+
+    let policy = policy.jitter(true);
+    for retries in 0..8 {
+        assert!(policy.backoff_delay(retries) <= Duration::from_secs(30));
+    }
+}
+
+#[test]
+fn test_retry_policy_statuses() {
+    let policy = Policy::limited(1)
+        .jitter(false)
+        .statuses([StatusCode::SERVICE_UNAVAILABLE]);
+    let mut headers = HeaderMap::new();
+
+    assert_eq!(
+        policy.check_status(&Method::GET, 0, StatusCode::BAD_GATEWAY, &headers),
+        None
+    );
+    assert_eq!(
+        policy.check_status(&Method::GET, 0, StatusCode::SERVICE_UNAVAILABLE, &headers),
+        Some(Duration::from_millis(100))
+    );
+
+    headers.insert(RETRY_AFTER, "3".parse().unwrap());
+    assert_eq!(
+        policy.check_status(&Method::GET, 0, StatusCode::SERVICE_UNAVAILABLE, &headers),
+        Some(Duration::from_secs(3))
+    );
+
+    headers.insert(RETRY_AFTER, "120".parse().unwrap());
+    assert_eq!(
+        policy.check_status(&Method::GET, 0, StatusCode::SERVICE_UNAVAILABLE, &headers),
+        None
+    );
+}
+
+#[test]
+fn test_parse_retry_after() {
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(784_111_770);
+
+    assert_eq!(
+        parse_retry_after("120", now),
+        Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", now),
+        Some(Duration::from_secs(7))
+    );
+    assert_eq!(
+        parse_retry_after("Sun, 06 Nov 1994 08:49:00 GMT", now),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(
+        parse_retry_after("Sunday, 06-Nov-94 08:49:37 GMT", now),
+        Some(Duration::from_secs(7))
+    );
+    assert_eq!(
+        parse_retry_after("Sun Nov  6 08:49:37 1994", now),
+        Some(Duration::from_secs(7))
+    );
+    assert_eq!(
+        parse_retry_after("Thu, 31 Feb 1994 08:49:37 GMT", now),
+        None
+    );
+    assert_eq!(parse_retry_after("soon", now), None);
+}
+
+#[test]
+fn test_retry_budget() {
+    let budget = Budget::new(0.5).min_retries(1);
+    let policy = Policy::limited(10).budget(budget);
+    let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+
+    policy.on_request();
+    policy.on_request();
+    // 1 + 2 * 0.5
+    assert!(policy.check_error(&Method::GET, 0, &reset).is_some());
+    assert!(policy.check_error(&Method::GET, 1, &reset).is_some());
+    assert!(policy.check_error(&Method::GET, 2, &reset).is_none());
+}
@@ -0,0 +1,139 @@
+//! Utilities for testing what a client sends on the wire
+//!
+//! [`EchoServer`] is a TLS server on the loopback interface, built on
+//! BoringSSL like the client's connector. It records the raw ClientHello,
+//! the negotiated ALPN protocol, the HTTP/2 SETTINGS, WINDOW_UPDATE and
+//! PRIORITY frames, and the pseudo-header and header order of each
+//! connection, so impersonation can be checked without an external echo
+//! site:
+//!
+//! ```no_run
+//! use reqwest_impersonate::browser::ChromeVersion;
+//! use reqwest_impersonate::test_util::EchoServer;
+//! use reqwest_impersonate::Client;
+//!
+//! # async fn run() -> Result<(), reqwest_impersonate::Error> {
+//! let mut server = EchoServer::start().await?;
+//! let client = server
+//!     .configure(Client::builder().impersonate(ChromeVersion::V120.profile()))
+//!     .build()?;
+//!
+//! client.get(server.url()).send().await?;
+//!
+//! let capture = server.next_capture().await?;
+//! assert_eq!(capture.alpn(), Some(&b"h2"[..]));
+//! assert_eq!(capture.http2().unwrap().window_update(), Some(15663105));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This module requires the `test-util` feature.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use url::Url;
+
+pub use crate::browser::fingerprint::capture::Capture;
+pub use crate::browser::fingerprint::http2::Http2Capture;
+use crate::browser::fingerprint::capture::{with_timeout, Listener};
+use crate::error::BoxError;
+use crate::ClientBuilder;
+
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A loopback TLS and HTTP/2 server recording what clients send.
+///
+/// The server answers the first request on each connection with an empty
+/// `200 OK` and then closes the connection. Connections are handled one at
+/// a time, in the order they are accepted.
+///
+/// The server stops when it is dropped.
+#[derive(Debug)]
+pub struct EchoServer {
+    addr: SocketAddr,
+    captures: mpsc::UnboundedReceiver<Result<Capture, BoxError>>,
+    task: JoinHandle<()>,
+}
+
+impl EchoServer {
+    /// Starts a server on a random port of `localhost`.
+    ///
+    /// This must be called from within a Tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if no port can be bound or the self-signed
+    /// certificate cannot be generated.
+    pub async fn start() -> crate::Result<EchoServer> {
+        let listener = Listener::bind().await.map_err(crate::error::builder)?;
+        let addr = listener.local_addr().map_err(crate::error::builder)?;
+
+        let (tx, captures) = mpsc::unbounded_channel();
+        let task = tokio::spawn(async move {
+            loop {
+                if tx.send(listener.capture().await).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(EchoServer {
+            addr,
+            captures,
+            task,
+        })
+    }
+
+    /// The IPv4 address the server listens on.
+    ///
+    /// The server also listens on the same port of `::1` when possible.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The URL of the server, `https://localhost:{port}/`.
+    pub fn url(&self) -> Url {
+        let url = format!("https://localhost:{}/", self.addr.port());
+        Url::parse(&url).expect("valid URL")
+    }
+
+    /// Configures a `ClientBuilder` to reach this server.
+    ///
+    /// `localhost` is resolved to the server's address, proxies are disabled
+    /// and the server's self-signed certificate is accepted. None of these
+    /// change what the client sends.
+    pub fn configure(&self, builder: ClientBuilder) -> ClientBuilder {
+        builder
+            .danger_accept_invalid_certs(true)
+            .resolve("localhost", self.addr)
+            .no_proxy()
+    }
+
+    /// Waits for the next connection to be recorded.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if no connection is recorded within 10 seconds, or
+    /// the client didn't send a valid ClientHello.
+    pub async fn next_capture(&mut self) -> crate::Result<Capture> {
+        let captures = &mut self.captures;
+        let next = async move {
+            match captures.recv().await {
+                Some(capture) => capture,
+                None => Err("echo server stopped".into()),
+            }
+        };
+        with_timeout(CAPTURE_TIMEOUT, next)
+            .await
+            .map_err(crate::error::request)
+    }
+}
+
+impl Drop for EchoServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -1,8 +1,7 @@
 //! TLS configuration
 //!
-//! By default, a `Client` will make use of system-native transport layer
-//! security to connect to HTTPS destinations. This means schannel on Windows,
-//! Security-Framework on macOS, and OpenSSL on Linux.
+//! A `Client` uses BoringSSL to connect to HTTPS destinations, configured
+//! to look like the impersonated browser.
 //!
 //! - Additional X509 certificates can be configured on a `ClientBuilder` with the
 //!   [`Certificate`](Certificate) type.
@@ -13,6 +12,14 @@
 use std::fmt;
 use std::sync::Arc;
 
+use boring::error::ErrorStack;
+use boring::pkcs12::Pkcs12;
+use boring::pkey::{PKey, Private};
+use boring::ssl::SslContextBuilder;
+use boring::x509::store::X509StoreBuilderRef;
+use boring::x509::X509;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
 /// A TLS protocol version.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Version(InnerVersion);
@@ -37,6 +44,267 @@ impl Version {
     pub const TLS_1_2: Version = Version(InnerVersion::Tls1_2);
     /// Version 1.3 of the TLS protocol.
     pub const TLS_1_3: Version = Version(InnerVersion::Tls1_3);
+
+    pub(crate) fn to_boring(self) -> boring::ssl::SslVersion {
+        use boring::ssl::SslVersion;
+
+        match self.0 {
+            InnerVersion::Tls1_0 => SslVersion::TLS1,
+            InnerVersion::Tls1_1 => SslVersion::TLS1_1,
+            InnerVersion::Tls1_2 => SslVersion::TLS1_2,
+            InnerVersion::Tls1_3 => SslVersion::TLS1_3,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self.0 {
+            InnerVersion::Tls1_0 => "1.0",
+            InnerVersion::Tls1_1 => "1.1",
+            InnerVersion::Tls1_2 => "1.2",
+            InnerVersion::Tls1_3 => "1.3",
+        }
+    }
+}
+
+/// Serialized as `"1.0"`, `"1.1"`, `"1.2"` or `"1.3"`.
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Version, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        match s.as_str() {
+            "1.0" => Ok(Version::TLS_1_0),
+            "1.1" => Ok(Version::TLS_1_1),
+            "1.2" => Ok(Version::TLS_1_2),
+            "1.3" => Ok(Version::TLS_1_3),
+            _ => Err(de::Error::invalid_value(
+                de::Unexpected::Str(&s),
+                &"one of \"1.0\", \"1.1\", \"1.2\" or \"1.3\"",
+            )),
+        }
+    }
+}
+
+/// Represents a server X509 certificate.
+#[derive(Clone)]
+pub struct Certificate {
+    inner: X509,
+}
+
+/// Represents a private key and X509 cert as a client certificate.
+#[derive(Clone)]
+pub struct Identity {
+    key: PKey<Private>,
+    cert: X509,
+    chain: Vec<X509>,
+}
+
+impl Certificate {
+    /// Create a `Certificate` from a binary DER encoded certificate
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::Read;
+    /// # fn cert() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut buf = Vec::new();
+    /// File::open("my_cert.der")?
+    ///     .read_to_end(&mut buf)?;
+    /// let cert = reqwest_impersonate::Certificate::from_der(&buf)?;
+    /// # drop(cert);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_der(der: &[u8]) -> crate::Result<Certificate> {
+        Ok(Certificate {
+            inner: X509::from_der(der).map_err(crate::error::builder)?,
+        })
+    }
+
+    /// Create a `Certificate` from a PEM encoded certificate
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::Read;
+    /// # fn cert() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut buf = Vec::new();
+    /// File::open("my_cert.pem")?
+    ///     .read_to_end(&mut buf)?;
+    /// let cert = reqwest_impersonate::Certificate::from_pem(&buf)?;
+    /// # drop(cert);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_pem(pem: &[u8]) -> crate::Result<Certificate> {
+        Ok(Certificate {
+            inner: X509::from_pem(pem).map_err(crate::error::builder)?,
+        })
+    }
+
+    /// Create a collection of `Certificate`s from a PEM encoded certificate bundle.
+    /// Example byte sources may be `.crt`, `.cer` or `.pem` files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::Read;
+    /// # fn cert() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut buf = Vec::new();
+    /// File::open("ca-bundle.crt")?
+    ///     .read_to_end(&mut buf)?;
+    /// let certs = reqwest_impersonate::Certificate::from_pem_bundle(&buf)?;
+    /// # drop(certs);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_pem_bundle(pem_bundle: &[u8]) -> crate::Result<Vec<Certificate>> {
+        Ok(X509::stack_from_pem(pem_bundle)
+            .map_err(crate::error::builder)?
+            .into_iter()
+            .map(|inner| Certificate { inner })
+            .collect())
+    }
+
+    pub(crate) fn add_to_store(&self, store: &mut X509StoreBuilderRef) -> Result<(), ErrorStack> {
+        store.add_cert(self.inner.clone())
+    }
+}
+
+impl Identity {
+    /// Parses a DER-formatted PKCS #12 archive, using the specified password to decrypt the key.
+    ///
+    /// The archive should contain a leaf certificate and its private key, as well any intermediate
+    /// certificates that allow clients to build a chain to a trusted root.
+    /// The chain certificates should be in order from the leaf certificate towards the root.
+    ///
+    /// PKCS #12 archives typically have either the `.p12` or `.pfx` extension,
+    /// and can be created with the OpenSSL `pkcs12` tool:
+    ///
+    /// ```bash
+    /// openssl pkcs12 -export -out identity.pfx -inkey key.pem -in cert.pem -certfile chain_certs.pem
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::Read;
+    /// # fn pkcs12() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut buf = Vec::new();
+    /// File::open("my-ident.pfx")?
+    ///     .read_to_end(&mut buf)?;
+    /// let pkcs12 = reqwest_impersonate::Identity::from_pkcs12_der(&buf, "my-privkey-password")?;
+    /// # drop(pkcs12);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_pkcs12_der(der: &[u8], password: &str) -> crate::Result<Identity> {
+        let parsed = Pkcs12::from_der(der)
+            .and_then(|pkcs12| pkcs12.parse(password))
+            .map_err(crate::error::builder)?;
+
+        Ok(Identity {
+            key: parsed.pkey,
+            cert: parsed.cert,
+            chain: parsed
+                .chain
+                .map(|chain| chain.into_iter().collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Parses a chain of PEM encoded X509 certificates, with the leaf certificate first.
+    /// `key` is a PEM encoded PKCS #8 formatted private key for the leaf certificate.
+    ///
+    /// The certificate chain should contain any intermediate cerficates that should be sent to
+    /// clients to allow them to build a chain to a trusted root.
+    ///
+    /// A certificate chain here means a series of PEM encoded certificates concatenated together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs;
+    /// # fn pkcs8() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cert = fs::read("client.pem")?;
+    /// let key = fs::read("key.pem")?;
+    /// let pkcs8 = reqwest_impersonate::Identity::from_pkcs8_pem(&cert, &key)?;
+    /// # drop(pkcs8);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_pkcs8_pem(pem: &[u8], key: &[u8]) -> crate::Result<Identity> {
+        let key = PKey::private_key_from_pem(key).map_err(crate::error::builder)?;
+        Identity::from_key_and_certs(key, pem)
+    }
+
+    /// Parses PEM encoded private key and certificate.
+    ///
+    /// The input should contain a PEM encoded private key
+    /// and at least one PEM encoded certificate, the leaf certificate first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::Read;
+    /// # fn pem() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut buf = Vec::new();
+    /// File::open("my-ident.pem")?
+    ///     .read_to_end(&mut buf)?;
+    /// let id = reqwest_impersonate::Identity::from_pem(&buf)?;
+    /// # drop(id);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_pem(buf: &[u8]) -> crate::Result<Identity> {
+        let key = PKey::private_key_from_pem(buf).map_err(crate::error::builder)?;
+        Identity::from_key_and_certs(key, buf)
+    }
+
+    fn from_key_and_certs(key: PKey<Private>, pem: &[u8]) -> crate::Result<Identity> {
+        let mut certs = X509::stack_from_pem(pem)
+            .map_err(crate::error::builder)?
+            .into_iter();
+        let cert = certs
+            .next()
+            .ok_or_else(|| crate::error::builder("no certificate found in PEM"))?;
+
+        Ok(Identity {
+            key,
+            cert,
+            chain: certs.collect(),
+        })
+    }
+
+    pub(crate) fn add_to_builder(&self, builder: &mut SslContextBuilder) -> Result<(), ErrorStack> {
+        builder.set_private_key(&self.key)?;
+        builder.set_certificate(&self.cert)?;
+        for cert in &self.chain {
+            builder.add_extra_chain_cert(cert.clone())?;
+        }
+        builder.check_private_key()
+    }
+}
+
+impl fmt::Debug for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Certificate").finish()
+    }
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Identity").finish()
+    }
 }
 
 pub(crate) enum TlsBackend {
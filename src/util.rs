@@ -1,4 +1,4 @@
-use crate::header::{Entry, HeaderMap, OccupiedEntry};
+use crate::header::{Entry, HeaderMap, HeaderName, OccupiedEntry};
 
 // xor-shift
 pub(crate) fn fast_random() -> u64 {
@@ -64,3 +64,25 @@ pub(crate) fn replace_headers(dst: &mut HeaderMap, src: HeaderMap) {
         }
     }
 }
+
+/// Rearranges `headers` so that the names listed in `order` come first, in
+/// that order, followed by every other header in its current position.
+pub(crate) fn sort_headers(headers: &mut HeaderMap, order: &[HeaderName]) {
+    if order.is_empty() {
+        return;
+    }
+
+    let mut sorted = HeaderMap::with_capacity(headers.keys_len());
+    for name in order {
+        for value in headers.get_all(name) {
+            sorted.append(name.clone(), value.clone());
+        }
+    }
+    for (name, value) in headers.iter() {
+        if !order.contains(name) {
+            sorted.append(name.clone(), value.clone());
+        }
+    }
+
+    *headers = sorted;
+}
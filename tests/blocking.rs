@@ -0,0 +1,175 @@
+mod support;
+
+use std::io::Read;
+use std::time::Duration;
+
+use http::header::CONTENT_LENGTH;
+use reqwest_impersonate::browser::ChromeVersion;
+use support::*;
+
+#[test]
+fn test_response_text() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let url = format!("http://{}/text", server.addr());
+    let res = reqwest_impersonate::blocking::get(&url).unwrap();
+    assert_eq!(res.url().as_str(), &url);
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+    assert_eq!(res.content_length(), Some(5));
+
+    let body = res.text().unwrap();
+    assert_eq!(b"Hello", body.as_bytes());
+}
+
+#[test]
+fn test_response_read() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let url = format!("http://{}/read", server.addr());
+    let mut res = reqwest_impersonate::blocking::get(&url).unwrap();
+
+    let mut body = String::new();
+    res.read_to_string(&mut body).unwrap();
+    assert_eq!(body, "Hello");
+}
+
+#[test]
+fn test_chrome_builder() {
+    let server = server::http(move |req| async move {
+        assert!(req.headers()["user-agent"]
+            .to_str()
+            .unwrap()
+            .contains("Chrome/131"));
+        assert!(req.headers()["sec-ch-ua"]
+            .to_str()
+            .unwrap()
+            .contains("\"Chromium\";v=\"131\""));
+        http::Response::default()
+    });
+
+    let url = format!("http://{}/chrome", server.addr());
+    let res = reqwest_impersonate::blocking::Client::builder()
+        .chrome_builder(ChromeVersion::V131)
+        .no_proxy()
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[test]
+fn test_post() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.method(), "POST");
+        assert_eq!(req.headers()[CONTENT_LENGTH], "5");
+
+        let data = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        assert_eq!(&*data, b"Hello");
+
+        http::Response::default()
+    });
+
+    let url = format!("http://{}/2", server.addr());
+    let res = reqwest_impersonate::blocking::Client::new()
+        .post(&url)
+        .body("Hello")
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[test]
+fn test_post_reader_body() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.method(), "POST");
+        assert_eq!(req.headers()["transfer-encoding"], "chunked");
+
+        let data = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        assert_eq!(&*data, b"streamed body");
+
+        http::Response::default()
+    });
+
+    let url = format!("http://{}/stream", server.addr());
+    let body = reqwest_impersonate::blocking::Body::new(&b"streamed body"[..]);
+    let res = reqwest_impersonate::blocking::Client::new()
+        .post(&url)
+        .body(body)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[test]
+fn test_error_for_status_4xx() {
+    let server = server::http(move |_req| async {
+        http::Response::builder()
+            .status(400)
+            .body(Default::default())
+            .unwrap()
+    });
+
+    let url = format!("http://{}/1", server.addr());
+    let res = reqwest_impersonate::blocking::get(&url).unwrap();
+
+    let err = res.error_for_status().unwrap_err();
+    assert!(err.is_status());
+    assert_eq!(
+        err.status(),
+        Some(reqwest_impersonate::StatusCode::BAD_REQUEST)
+    );
+}
+
+#[test]
+fn test_timeout() {
+    let server = server::http(move |_req| async {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        http::Response::default()
+    });
+
+    let url = format!("http://{}/slow", server.addr());
+    let err = reqwest_impersonate::blocking::Client::builder()
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap()
+        .get(&url)
+        .send()
+        .unwrap_err();
+
+    assert!(err.is_timeout());
+    assert_eq!(err.url().map(|u| u.as_str()), Some(url.as_str()));
+}
+
+#[test]
+#[should_panic]
+fn test_blocking_inside_a_runtime() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let url = format!("http://{}/text", server.addr());
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("new rt");
+
+    rt.block_on(async move {
+        let _should_panic = reqwest_impersonate::blocking::get(&url);
+    });
+}
+
+#[test]
+fn test_response_outlives_client() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let url = format!("http://{}/text", server.addr());
+    let client = reqwest_impersonate::blocking::Client::new();
+    let res = client.get(&url).send().unwrap();
+    drop(client);
+
+    // the response keeps the runtime thread alive after the client is gone
+    assert_eq!(res.text().unwrap(), "Hello");
+}
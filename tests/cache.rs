@@ -0,0 +1,198 @@
+mod support;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use reqwest_impersonate::cache::{DiskStore, MemoryStore};
+use reqwest_impersonate::StatusCode;
+use support::*;
+
+/// Serves numbered bodies with `headers`, answering `If-None-Match: "v1"`
+/// with `304 Not Modified`.
+fn counting(
+    headers: &'static [(&'static str, &'static str)],
+    hits: Arc<AtomicUsize>,
+) -> server::Server {
+    server::http(move |req| {
+        let hits = hits.clone();
+        async move {
+            let hit = hits.fetch_add(1, Ordering::SeqCst) + 1;
+            let mut res = http::Response::builder();
+            for (name, value) in headers {
+                res = res.header(*name, *value);
+            }
+            if req
+                .headers()
+                .get("if-none-match")
+                .map_or(false, |v| v == "\"v1\"")
+            {
+                res.status(304).body(Default::default()).unwrap()
+            } else {
+                res.body(format!("hit {}", hit).into()).unwrap()
+            }
+        }
+    })
+}
+
+#[tokio::test]
+async fn fresh_response_is_reused() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = counting(&[("cache-control", "max-age=60")], hits.clone());
+
+    let client = reqwest_impersonate::Client::builder()
+        .cache(MemoryStore::new(16))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/fresh", server.addr());
+    for _ in 0..3 {
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "hit 1");
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    let res = client
+        .get(&url)
+        .header("cache-control", "no-cache")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "hit 2");
+}
+
+#[tokio::test]
+async fn stale_response_is_revalidated() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = counting(
+        &[("cache-control", "no-cache"), ("etag", "\"v1\"")],
+        hits.clone(),
+    );
+
+    let client = reqwest_impersonate::Client::builder()
+        .cache(MemoryStore::new(16))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/etag", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "hit 1");
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["etag"], "\"v1\"");
+    assert_eq!(res.text().await.unwrap(), "hit 1");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn no_store_is_not_cached() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = counting(&[("cache-control", "no-store, max-age=60")], hits.clone());
+
+    let client = reqwest_impersonate::Client::builder()
+        .cache(MemoryStore::new(16))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/no-store", server.addr());
+    client.get(&url).send().await.unwrap();
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "hit 2");
+}
+
+#[tokio::test]
+async fn vary_selects_response() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = counting(
+        &[("cache-control", "max-age=60"), ("vary", "accept-language")],
+        hits.clone(),
+    );
+
+    let client = reqwest_impersonate::Client::builder()
+        .cache(MemoryStore::new(16))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/vary", server.addr());
+    let get = |lang: &'static str| client.get(&url).header("accept-language", lang).send();
+    assert_eq!(get("en").await.unwrap().text().await.unwrap(), "hit 1");
+    assert_eq!(get("en").await.unwrap().text().await.unwrap(), "hit 1");
+    assert_eq!(get("fr").await.unwrap().text().await.unwrap(), "hit 2");
+}
+
+#[tokio::test]
+async fn unsafe_request_invalidates() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = counting(&[("cache-control", "max-age=60")], hits.clone());
+
+    let client = reqwest_impersonate::Client::builder()
+        .cache(MemoryStore::new(16))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/resource", server.addr());
+    client.get(&url).send().await.unwrap();
+    client.post(&url).body("update").send().await.unwrap();
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "hit 3");
+}
+
+#[tokio::test]
+async fn disk_store_outlives_client() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = counting(&[("cache-control", "max-age=60")], hits.clone());
+
+    let dir = std::env::temp_dir().join(format!("reqwest-cache-test-{}", std::process::id()));
+    let url = format!("http://{}/disk", server.addr());
+    for _ in 0..2 {
+        let client = reqwest_impersonate::Client::builder()
+            .cache(DiskStore::new(&dir).unwrap())
+            .build()
+            .unwrap();
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "hit 1");
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn large_response_is_not_cached() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = counting(&[("cache-control", "max-age=60")], hits.clone());
+
+    let client = reqwest_impersonate::Client::builder()
+        .cache(MemoryStore::new(16).with_max_entry_size(4))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/large", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "hit 1");
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "hit 2");
+}
+
+#[tokio::test]
+async fn set_cookie_is_not_replayed() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = counting(
+        &[("cache-control", "max-age=60"), ("set-cookie", "session=1")],
+        hits.clone(),
+    );
+
+    let client = reqwest_impersonate::Client::builder()
+        .cache(MemoryStore::new(16))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/cookie", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.headers()["set-cookie"], "session=1");
+    assert_eq!(res.text().await.unwrap(), "hit 1");
+
+    let res = client.get(&url).send().await.unwrap();
+    assert!(res.headers().get("set-cookie").is_none());
+    assert_eq!(res.text().await.unwrap(), "hit 1");
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
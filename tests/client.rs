@@ -225,3 +225,376 @@ async fn overridden_dns_resolution_with_gai_multiple() {
     let text = res.text().await.expect("Failed to get text");
     assert_eq!("Hello", text);
 }
+
+#[tokio::test]
+async fn firefox_default_headers() {
+    let server = server::http(move |req| async move {
+        assert_eq!(
+            req.headers()["user-agent"],
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:120.0) Gecko/20100101 Firefox/120.0"
+        );
+        assert_eq!(req.headers()["accept-language"], "en-US,en;q=0.5");
+        assert_eq!(req.headers()["te"], "trailers");
+        assert_eq!(req.headers().get("sec-ch-ua"), None);
+        http::Response::default()
+    });
+
+    let url = format!("http://{}/firefox", server.addr());
+    let res = reqwest_impersonate::Client::builder()
+        .firefox_builder(reqwest_impersonate::browser::FirefoxVersion::V120)
+        .no_proxy()
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn safari_ios_default_headers() {
+    let server = server::http(move |req| async move {
+        assert!(req.headers()["user-agent"]
+            .to_str()
+            .unwrap()
+            .contains("iPhone OS 17_0"));
+        assert_eq!(req.headers()["sec-fetch-mode"], "navigate");
+        assert_eq!(req.headers().get("upgrade-insecure-requests"), None);
+        http::Response::default()
+    });
+
+    let url = format!("http://{}/safari", server.addr());
+    let res = reqwest_impersonate::Client::builder()
+        .safari_builder(reqwest_impersonate::browser::SafariVersion::Ios17)
+        .no_proxy()
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn chrome_modern_default_headers() {
+    let server = server::http(move |req| async move {
+        assert!(req.headers()["sec-ch-ua"]
+            .to_str()
+            .unwrap()
+            .contains("\"Chromium\";v=\"131\""));
+        assert_eq!(req.headers()["priority"], "u=0, i");
+        http::Response::default()
+    });
+
+    let url = format!("http://{}/chrome", server.addr());
+    let res = reqwest_impersonate::Client::builder()
+        .chrome_builder(reqwest_impersonate::browser::ChromeVersion::V131)
+        .no_proxy()
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn impersonate_tweaked_profile() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()["user-agent"], "tweaked");
+        assert_eq!(req.headers()["x-extra"], "1");
+        http::Response::default()
+    });
+
+    let profile = reqwest_impersonate::browser::ChromeVersion::V131
+        .profile()
+        .to_builder()
+        .user_agent("tweaked")
+        .header("x-extra", "1")
+        .build()
+        .expect("profile builder");
+
+    let url = format!("http://{}/impersonate", server.addr());
+    let res = reqwest_impersonate::Client::builder()
+        .impersonate(profile)
+        .no_proxy()
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[test]
+fn impersonate_profile_rejects_unknown_cipher() {
+    let err = reqwest_impersonate::browser::ImpersonateProfile::builder()
+        .cipher_list(["NOT_A_CIPHER"])
+        .build()
+        .unwrap_err();
+
+    assert!(err.is_builder());
+}
+
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn impersonate_profile_from_json() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()["user-agent"], "from-json");
+        assert_eq!(req.headers()["accept"], "*/*");
+        http::Response::default()
+    });
+
+    let profile = reqwest_impersonate::browser::ImpersonateProfile::from_json(
+        r#"{
+            "tls": {
+                "cipher_list": ["TLS_AES_128_GCM_SHA256", "TLS_CHACHA20_POLY1305_SHA256"],
+                "curves": ["X25519", "P-256"],
+                "alpn_protos": ["h2", "http/1.1"],
+                "cert_compression_algorithms": ["brotli"],
+                "grease_enabled": true,
+                "min_tls_version": "1.2",
+                "max_tls_version": "1.3"
+            },
+            "http2": { "initial_stream_window_size": 6291456 },
+            "headers": [["user-agent", "from-json"], ["accept", "*/*"]]
+        }"#,
+    )
+    .expect("profile from json");
+
+    let url = format!("http://{}/json", server.addr());
+    let res = reqwest_impersonate::Client::builder()
+        .impersonate(profile)
+        .no_proxy()
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn impersonate_profile_json_roundtrip() {
+    let profile = reqwest_impersonate::browser::ChromeVersion::V131.profile();
+    let json = serde_json::to_string(&profile).expect("serialize");
+    let loaded =
+        reqwest_impersonate::browser::ImpersonateProfile::from_json(&json).expect("deserialize");
+
+    assert_eq!(loaded.cipher_list(), profile.cipher_list());
+    assert_eq!(loaded.curves(), profile.curves());
+    assert_eq!(loaded.headers(), profile.headers());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn impersonate_profile_from_json_rejects_bad_version() {
+    let err = reqwest_impersonate::browser::ImpersonateProfile::from_json(
+        r#"{ "tls": { "min_tls_version": "2.0" } }"#,
+    )
+    .unwrap_err();
+
+    assert!(err.is_builder());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn impersonate_profile_json_rejects_non_utf8_header() {
+    let profile = reqwest_impersonate::browser::ImpersonateProfile::builder()
+        .header("x-latin-1", &b"caf\xe9"[..])
+        .build()
+        .expect("profile");
+
+    assert!(serde_json::to_string(&profile).is_err());
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn impersonate_profile_from_toml() {
+    let profile = reqwest_impersonate::browser::ImpersonateProfile::from_toml(
+        r#"
+            headers = [["user-agent", "from-toml"], ["accept", "*/*"]]
+            gzip = true
+
+            [tls]
+            cipher_list = ["TLS_AES_128_GCM_SHA256", "TLS_CHACHA20_POLY1305_SHA256"]
+            curves = ["X25519", "P-256"]
+            min_tls_version = "1.2"
+
+            [http2]
+            initial_stream_window_size = 6291456
+        "#,
+    )
+    .expect("profile from toml");
+
+    assert_eq!(
+        profile.cipher_list(),
+        ["TLS_AES_128_GCM_SHA256", "TLS_CHACHA20_POLY1305_SHA256"]
+    );
+    assert_eq!(profile.curves(), ["X25519", "P-256"]);
+    assert_eq!(profile.headers()["user-agent"], "from-toml");
+
+    let err = reqwest_impersonate::browser::ImpersonateProfile::from_toml(
+        r#"
+            [tls]
+            min_tls_version = "2.0"
+        "#,
+    )
+    .unwrap_err();
+    assert!(err.is_builder());
+}
+
+#[tokio::test]
+async fn header_order_client_and_request() {
+    let server = server::http(move |req| async move {
+        let names: Vec<_> = req.headers().keys().map(|k| k.as_str()).collect();
+        let pos = |name| names.iter().position(|n| *n == name).unwrap();
+        if req.uri().path() == "/client" {
+            assert!(pos("x-b") < pos("x-a"));
+            assert!(pos("x-a") < pos("x-c"));
+        } else {
+            assert!(pos("x-c") < pos("x-a"));
+            assert!(pos("x-a") < pos("x-b"));
+        }
+        http::Response::default()
+    });
+
+    let client = reqwest_impersonate::Client::builder()
+        .header_order([
+            reqwest_impersonate::header::HeaderName::from_static("x-b"),
+            reqwest_impersonate::header::HeaderName::from_static("x-a"),
+        ])
+        .no_proxy()
+        .build()
+        .expect("client builder");
+
+    let res = client
+        .get(&format!("http://{}/client", server.addr()))
+        .header("x-a", "a")
+        .header("x-c", "c")
+        .header("x-b", "b")
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+
+    let res = client
+        .get(&format!("http://{}/request", server.addr()))
+        .header("x-a", "a")
+        .header("x-b", "b")
+        .header("x-c", "c")
+        .header_order([
+            reqwest_impersonate::header::HeaderName::from_static("x-c"),
+            reqwest_impersonate::header::HeaderName::from_static("x-a"),
+            reqwest_impersonate::header::HeaderName::from_static("x-b"),
+        ])
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[test]
+fn impersonate_profile_rejects_repeated_pseudo_header() {
+    use reqwest_impersonate::browser::{ImpersonateProfile, PseudoOrder};
+
+    let err = ImpersonateProfile::builder()
+        .http2_headers_pseudo_order([
+            PseudoOrder::Method,
+            PseudoOrder::Path,
+            PseudoOrder::Path,
+            PseudoOrder::Scheme,
+        ])
+        .build()
+        .unwrap_err();
+
+    assert!(err.is_builder());
+}
+
+#[test]
+fn impersonate_profile_rejects_invalid_http2_options() {
+    use reqwest_impersonate::browser::{ImpersonateProfile, Priority, SettingId};
+
+    let err = ImpersonateProfile::builder()
+        .http2_headers_priority(Priority {
+            stream_dependency: 0,
+            weight: 0,
+            exclusive: true,
+        })
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+
+    let err = ImpersonateProfile::builder()
+        .http2_settings_order([SettingId::EnablePush, SettingId::EnablePush])
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+
+    let err = ImpersonateProfile::builder()
+        .http2_extra_settings([(0x4, 1)])
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+}
+
+#[test]
+fn impersonate_profile_rejects_alps_without_protocols() {
+    use reqwest_impersonate::browser::{ApplicationSettings, ImpersonateProfile};
+
+    let err = ImpersonateProfile::builder()
+        .alps(ApplicationSettings::new(Vec::<String>::new()))
+        .build()
+        .unwrap_err();
+
+    assert!(err.is_builder());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn impersonate_profile_alps_from_json() {
+    use reqwest_impersonate::browser::{
+        AlpsCodepoint, AlpsPayload, ApplicationSettings, ImpersonateProfile,
+    };
+
+    let legacy =
+        ImpersonateProfile::from_json(r#"{ "tls": { "application_settings": true } }"#).unwrap();
+    assert_eq!(
+        legacy.application_settings(),
+        Some(&ApplicationSettings::new(["h2"]))
+    );
+
+    let profile = ImpersonateProfile::from_json(
+        r#"{
+            "tls": {
+                "application_settings": {
+                    "protocols": ["h2"],
+                    "payload": "http2_settings",
+                    "codepoint": "new"
+                }
+            }
+        }"#,
+    )
+    .unwrap();
+    let alps = profile.application_settings().unwrap();
+    assert_eq!(alps.payload(), &AlpsPayload::Http2Settings);
+    assert_eq!(alps.codepoint(), AlpsCodepoint::New);
+
+    let json = serde_json::to_string(&profile).unwrap();
+    let loaded = ImpersonateProfile::from_json(&json).unwrap();
+    assert_eq!(
+        loaded.application_settings(),
+        profile.application_settings()
+    );
+}
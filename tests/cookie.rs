@@ -1,4 +1,6 @@
 mod support;
+use reqwest_impersonate::cookie::{CookieStore, Jar};
+use reqwest_impersonate::Url;
 use support::*;
 
 #[tokio::test]
@@ -201,3 +203,179 @@ async fn cookie_store_path() {
     let url = format!("http://{}/subpath", server.addr());
     client.get(&url).send().await.unwrap();
 }
+
+const COOKIES_TXT: &str = "# Netscape HTTP Cookie File\n\
+    .example.com\tTRUE\t/\tFALSE\t0\tsession\t1\n\
+    #HttpOnly_www.example.com\tFALSE\t/app\tTRUE\t4102444800\tlogin\t2\n\
+    example.com\tFALSE\t/\tFALSE\t1\texpired\t3\n\
+    \n\
+    malformed line\n";
+
+fn request_cookies(jar: &Jar, url: &str) -> Vec<String> {
+    let mut cookies = jar
+        .cookies(&url.parse::<Url>().unwrap())
+        .map(|value| {
+            value
+                .to_str()
+                .unwrap()
+                .split("; ")
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    cookies.sort();
+    cookies
+}
+
+#[test]
+fn cookie_jar_load_netscape() {
+    let jar = Jar::default();
+    jar.load_netscape(COOKIES_TXT.as_bytes()).unwrap();
+
+    assert_eq!(
+        request_cookies(&jar, "http://sub.example.com/"),
+        ["session=1"]
+    );
+    assert_eq!(
+        request_cookies(&jar, "https://www.example.com/app"),
+        ["login=2", "session=1"]
+    );
+    assert_eq!(
+        request_cookies(&jar, "http://www.example.com/app"),
+        ["session=1"]
+    );
+}
+
+#[test]
+fn cookie_jar_save_netscape() {
+    let jar = Jar::default();
+    jar.load_netscape(COOKIES_TXT.as_bytes()).unwrap();
+
+    let mut saved = Vec::new();
+    jar.save_netscape(&mut saved).unwrap();
+    let saved = String::from_utf8(saved).unwrap();
+    assert!(saved.starts_with("# Netscape HTTP Cookie File\n"));
+    assert!(saved.contains("\n.example.com\tTRUE\t/\tFALSE\t0\tsession\t1\n"));
+    assert!(
+        saved.contains("\n#HttpOnly_www.example.com\tFALSE\t/app\tTRUE\t4102444800\tlogin\t2\n")
+    );
+    assert!(!saved.contains("expired"));
+
+    let loaded = Jar::default();
+    loaded.load_netscape(saved.as_bytes()).unwrap();
+    assert_eq!(
+        request_cookies(&loaded, "https://www.example.com/app"),
+        ["login=2", "session=1"]
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn cookie_jar_json_round_trip() {
+    let url = "https://example.com/".parse::<Url>().unwrap();
+    let jar = Jar::default();
+    jar.add_cookie_str("session=1; HttpOnly; SameSite=Strict", &url);
+    jar.add_cookie_str("persistent=2; Max-Age=3600; Domain=example.com", &url);
+    jar.add_cookie_str("expired=3; Max-Age=0", &url);
+
+    let mut saved = Vec::new();
+    jar.save_json(&mut saved).unwrap();
+
+    let loaded = Jar::default();
+    loaded.load_json(&saved[..]).unwrap();
+    assert_eq!(
+        request_cookies(&loaded, "https://example.com/"),
+        ["persistent=2", "session=1"]
+    );
+    assert_eq!(
+        request_cookies(&loaded, "https://sub.example.com/"),
+        ["persistent=2"]
+    );
+
+    assert!(loaded.load_json(&b"not json"[..]).is_err());
+}
+
+fn names(
+    cookies: impl Iterator<Item = reqwest_impersonate::cookie::Cookie<'static>>,
+) -> Vec<String> {
+    let mut names = cookies
+        .map(|cookie| cookie.name().to_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[test]
+fn cookie_jar_iter() {
+    let url = "https://www.example.com/app/login".parse::<Url>().unwrap();
+    let jar = Jar::default();
+    jar.add_cookie_str("host=1; Secure; HttpOnly; SameSite=Lax", &url);
+    jar.add_cookie_str("site=2; Domain=example.com; Path=/; Max-Age=3600", &url);
+
+    let mut cookies = jar.iter().collect::<Vec<_>>();
+    cookies.sort_by(|a, b| a.name().cmp(b.name()));
+    assert_eq!(cookies.len(), 2);
+
+    assert_eq!(cookies[0].name(), "host");
+    assert_eq!(cookies[0].domain(), Some("www.example.com"));
+    assert_eq!(cookies[0].path(), Some("/app"));
+    assert!(cookies[0].secure());
+    assert!(cookies[0].http_only());
+    assert!(cookies[0].same_site_lax());
+    assert_eq!(cookies[0].expires(), None);
+
+    assert_eq!(cookies[1].name(), "site");
+    assert_eq!(cookies[1].domain(), Some("example.com"));
+    assert_eq!(cookies[1].path(), Some("/"));
+    assert_eq!(cookies[1].max_age(), None);
+    let expires = cookies[1].expires().unwrap();
+    assert!(expires > std::time::SystemTime::now());
+
+    let matching = |url: &str| names(jar.matching(&url.parse::<Url>().unwrap()));
+    assert_eq!(matching("https://www.example.com/app"), ["host", "site"]);
+    assert_eq!(matching("https://api.example.com/app"), ["site"]);
+    assert!(matching("https://example.org/").is_empty());
+}
+
+#[test]
+fn cookie_jar_remove() {
+    let url = "https://www.example.com/".parse::<Url>().unwrap();
+    let jar = Jar::default();
+    jar.add_cookie_str("a=1", &url);
+    jar.add_cookie_str("b=2; Domain=example.com", &url);
+
+    assert!(jar.remove("example.com", "/", "a").is_none());
+    let removed = jar.remove("www.example.com", "/", "a").unwrap();
+    assert_eq!(removed.value(), "1");
+    assert_eq!(names(jar.iter()), ["b"]);
+
+    jar.clear();
+    assert_eq!(jar.iter().count(), 0);
+}
+
+#[test]
+fn cookie_jar_clear_domain() {
+    let jar = Jar::default();
+    for url in [
+        "https://example.com/",
+        "https://www.example.com/",
+        "https://example.org/",
+    ] {
+        let name = url.trim_start_matches("https://").trim_end_matches('/');
+        jar.add_cookie_str(&format!("{}=1", name), &url.parse::<Url>().unwrap());
+    }
+
+    jar.clear_domain("Example.com");
+    assert_eq!(names(jar.iter()), ["example.org"]);
+}
+
+#[test]
+fn cookie_jar_clear_session_cookies() {
+    let url = "https://example.com/".parse::<Url>().unwrap();
+    let jar = Jar::default();
+    jar.add_cookie_str("session=1", &url);
+    jar.add_cookie_str("persistent=2; Max-Age=3600", &url);
+
+    jar.clear_session_cookies();
+    assert_eq!(names(jar.iter()), ["persistent"]);
+}
@@ -0,0 +1,206 @@
+use reqwest_impersonate::browser::fingerprint::GeneratedProfile;
+use reqwest_impersonate::browser::{
+    CertCompressionAlgorithm, ChromeVersion, FirefoxVersion, ImpersonateProfile, Priority,
+    PseudoOrder, SafariVersion,
+};
+use reqwest_impersonate::header::{HeaderName, ACCEPT, USER_AGENT};
+use reqwest_impersonate::test_util::EchoServer;
+use reqwest_impersonate::Client;
+
+#[tokio::test]
+async fn echo_chrome_on_the_wire() {
+    let mut server = EchoServer::start().await.unwrap();
+    let client = server
+        .configure(Client::builder().impersonate(ChromeVersion::V120.profile()))
+        .build()
+        .unwrap();
+
+    let res = client.get(server.url()).send().await.unwrap();
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+
+    let capture = server.next_capture().await.unwrap();
+    assert_eq!(capture.server_name().as_deref(), Some("localhost"));
+    assert_eq!(capture.alpn(), Some(&b"h2"[..]));
+
+    let h2 = capture.http2().unwrap();
+    assert_eq!(
+        h2.settings(),
+        &[(1, 65536), (2, 0), (4, 6291456), (6, 262144)]
+    );
+    assert_eq!(h2.window_update(), Some(15663105));
+    assert!(h2.priority_frames().is_empty());
+    assert_eq!(
+        h2.headers_priority(),
+        Some(Priority {
+            stream_dependency: 0,
+            weight: 256,
+            exclusive: true,
+        })
+    );
+    assert_eq!(
+        h2.pseudo_order(),
+        &[
+            PseudoOrder::Method,
+            PseudoOrder::Authority,
+            PseudoOrder::Scheme,
+            PseudoOrder::Path,
+        ]
+    );
+
+    let order: Vec<&str> = capture.header_order().iter().map(|h| h.as_str()).collect();
+    assert_eq!(
+        order,
+        [
+            "sec-ch-ua",
+            "sec-ch-ua-mobile",
+            "sec-ch-ua-platform",
+            "upgrade-insecure-requests",
+            "user-agent",
+            "accept",
+            "sec-fetch-site",
+            "sec-fetch-mode",
+            "sec-fetch-user",
+            "sec-fetch-dest",
+            "accept-encoding",
+            "accept-language",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn echo_firefox_priority_frames() {
+    let mut server = EchoServer::start().await.unwrap();
+    let client = server
+        .configure(Client::builder().impersonate(FirefoxVersion::V120.profile()))
+        .build()
+        .unwrap();
+
+    client.get(server.url()).send().await.unwrap();
+
+    let capture = server.next_capture().await.unwrap();
+    let h2 = capture.http2().unwrap();
+    let frames: Vec<(u32, u32, u16)> = h2
+        .priority_frames()
+        .iter()
+        .map(|f| (f.stream_id, f.priority.stream_dependency, f.priority.weight))
+        .collect();
+    assert_eq!(
+        frames,
+        [
+            (3, 0, 201),
+            (5, 0, 101),
+            (7, 0, 1),
+            (9, 7, 1),
+            (11, 3, 1),
+            (13, 0, 241),
+        ]
+    );
+    assert_eq!(h2.headers_priority().unwrap().stream_dependency, 13);
+    assert_eq!(
+        h2.pseudo_order(),
+        &[
+            PseudoOrder::Method,
+            PseudoOrder::Path,
+            PseudoOrder::Authority,
+            PseudoOrder::Scheme,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn echo_safari_settings_order() {
+    let mut server = EchoServer::start().await.unwrap();
+    let client = server
+        .configure(Client::builder().impersonate(SafariVersion::V17.profile()))
+        .build()
+        .unwrap();
+
+    client.get(server.url()).send().await.unwrap();
+
+    let capture = server.next_capture().await.unwrap();
+    let h2 = capture.http2().unwrap();
+    let ids: Vec<u16> = h2.settings().iter().map(|(id, _)| *id).collect();
+    let position = |id| ids.iter().position(|i| *i == id).unwrap();
+    assert!(position(4) < position(3));
+    assert_eq!(h2.window_update(), Some(10485760));
+}
+
+#[tokio::test]
+async fn echo_http1_header_order() {
+    let mut server = EchoServer::start().await.unwrap();
+    let client = server
+        .configure(Client::builder())
+        .header_order([USER_AGENT, HeaderName::from_static("x-custom"), ACCEPT])
+        .user_agent("echo-test")
+        .build()
+        .unwrap();
+
+    client
+        .get(server.url())
+        .header("accept", "*/*")
+        .header("x-custom", "1")
+        .send()
+        .await
+        .unwrap();
+
+    let capture = server.next_capture().await.unwrap();
+    assert_eq!(capture.alpn(), None);
+    assert!(capture.http2().is_none());
+
+    let order: Vec<&str> = capture
+        .header_order()
+        .iter()
+        .map(|h| h.as_str())
+        .filter(|h| ["user-agent", "x-custom", "accept"].contains(h))
+        .collect();
+    assert_eq!(order, ["user-agent", "x-custom", "accept"]);
+}
+
+#[tokio::test]
+async fn echo_records_each_connection() {
+    let mut server = EchoServer::start().await.unwrap();
+
+    for version in [ChromeVersion::V120, ChromeVersion::V131] {
+        let client = server
+            .configure(Client::builder().impersonate(version.profile()))
+            .build()
+            .unwrap();
+        client.get(server.url()).send().await.unwrap();
+    }
+
+    let first = server.next_capture().await.unwrap().fingerprint().unwrap();
+    let second = server.next_capture().await.unwrap().fingerprint().unwrap();
+    assert!(first.ja4().starts_with("t13d"));
+    assert!(second.ja4().starts_with("t13d"));
+}
+
+#[tokio::test]
+async fn echo_cert_compression_order() {
+    let mut server = EchoServer::start().await.unwrap();
+
+    let profile = ImpersonateProfile::builder()
+        .cert_compression_algorithms([
+            CertCompressionAlgorithm::Zstd,
+            CertCompressionAlgorithm::Zlib,
+        ])
+        .build()
+        .unwrap();
+    let client = server
+        .configure(Client::builder().impersonate(profile))
+        .build()
+        .unwrap();
+    client.get(server.url()).send().await.unwrap();
+
+    let capture = server.next_capture().await.unwrap();
+    let generated = GeneratedProfile::from_client_hello(capture.client_hello())
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(
+        generated.cert_compression_algorithms(),
+        &[
+            CertCompressionAlgorithm::Zstd,
+            CertCompressionAlgorithm::Zlib
+        ]
+    );
+}
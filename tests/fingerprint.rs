@@ -0,0 +1,274 @@
+use reqwest_impersonate::browser::fingerprint::{Fingerprint, GeneratedProfile, Unsupported};
+use reqwest_impersonate::browser::{
+    AlpsCodepoint, AlpsPayload, ApplicationSettings, ChromeVersion, FirefoxVersion,
+};
+use reqwest_impersonate::Client;
+
+#[tokio::test]
+async fn fingerprint_chrome() {
+    let fingerprint = Fingerprint::of_profile(ChromeVersion::V120.profile())
+        .await
+        .unwrap();
+
+    assert!(fingerprint.ja3().starts_with("771,4865-4866-4867-"));
+    assert!(fingerprint.ja4().starts_with("t13d"));
+    assert!(fingerprint.ja4().contains("h2_"));
+    assert_eq!(fingerprint.ja3_hash().len(), 32);
+    assert_eq!(
+        fingerprint.akamai(),
+        Some("1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p")
+    );
+}
+
+#[tokio::test]
+async fn fingerprint_alps_codepoint() {
+    let chrome = Fingerprint::of_profile(ChromeVersion::V120.profile())
+        .await
+        .unwrap();
+    let extensions = |ja3: &str| ja3.split(',').nth(2).unwrap().to_owned();
+    assert!(extensions(chrome.ja3()).split('-').any(|e| e == "17513"));
+
+    let profile = ChromeVersion::V120
+        .profile()
+        .to_builder()
+        .alps(
+            ApplicationSettings::new(["h2"])
+                .with_payload(AlpsPayload::Http2Settings)
+                .with_codepoint(AlpsCodepoint::New),
+        )
+        .build()
+        .unwrap();
+    let fingerprint = Fingerprint::of_profile(profile).await.unwrap();
+    let extensions = extensions(fingerprint.ja3());
+    assert!(extensions.split('-').any(|e| e == "17613"));
+    assert!(!extensions.split('-').any(|e| e == "17513"));
+
+    let generated = GeneratedProfile::from_ja3(fingerprint.ja3()).unwrap();
+    assert!(!generated
+        .unsupported()
+        .contains(&Unsupported::Extension(17613)));
+    let alps = generated.build().unwrap();
+    assert_eq!(
+        alps.application_settings().map(|a| a.codepoint()),
+        Some(AlpsCodepoint::New)
+    );
+}
+
+#[tokio::test]
+async fn fingerprint_ja3n_is_stable_under_permutation() {
+    let first = Fingerprint::of_profile(ChromeVersion::V120.profile())
+        .await
+        .unwrap();
+    let second = Fingerprint::of_profile(ChromeVersion::V120.profile())
+        .await
+        .unwrap();
+
+    assert_eq!(first.ja3n_hash(), second.ja3n_hash());
+    assert_eq!(first.ja4(), second.ja4());
+}
+
+#[tokio::test]
+async fn fingerprint_firefox_priority_frames() {
+    let fingerprint = Fingerprint::of_profile(FirefoxVersion::V120.profile())
+        .await
+        .unwrap();
+
+    let akamai = fingerprint.akamai().unwrap();
+    assert!(akamai.contains("|3:0:0:201,5:0:0:101,7:0:0:1,9:0:7:1,11:0:3:1,13:0:0:241|"));
+    assert!(akamai.ends_with("|m,p,a,s"));
+}
+
+#[tokio::test]
+async fn fingerprint_firefox_versions_differ() {
+    let extensions = |ja3: &str| -> Vec<String> {
+        ja3.split(',')
+            .nth(2)
+            .unwrap()
+            .split('-')
+            .map(str::to_owned)
+            .collect()
+    };
+    let v109 = Fingerprint::of_profile(FirefoxVersion::V109.profile())
+        .await
+        .unwrap();
+    let v120 = Fingerprint::of_profile(FirefoxVersion::V120.profile())
+        .await
+        .unwrap();
+
+    let (v109, v120) = (extensions(v109.ja3()), extensions(v120.ja3()));
+    // compress_certificate and encrypted_client_hello
+    for ext in ["27", "65037"] {
+        assert!(!v109.iter().any(|e| e == ext));
+        assert!(v120.iter().any(|e| e == ext));
+    }
+}
+
+#[tokio::test]
+async fn fingerprint_of_verifying_client_has_no_akamai() {
+    let client = Client::builder()
+        .impersonate(ChromeVersion::V120.profile())
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let fingerprint = Fingerprint::of_client(&client).await.unwrap();
+
+    assert!(fingerprint.ja4().starts_with("t13d"));
+    assert_eq!(fingerprint.akamai(), None);
+}
+
+#[tokio::test]
+async fn generated_profile_from_ja3_reproduces_it() {
+    let chrome = Fingerprint::of_profile(ChromeVersion::V120.profile())
+        .await
+        .unwrap();
+
+    let generated = GeneratedProfile::from_ja3(chrome.ja3())
+        .unwrap()
+        .with_akamai(chrome.akamai().unwrap())
+        .unwrap();
+    let profile = generated.to_builder().grease_enabled(true).build().unwrap();
+    let reproduced = Fingerprint::of_profile(profile).await.unwrap();
+
+    // Without the extension contents, only the cipher suites, groups and
+    // HTTP/2 settings are carried over exactly.
+    let fields = |ja3: &str| {
+        let fields: Vec<String> = ja3.split(',').map(str::to_owned).collect();
+        (fields[1].clone(), fields[3].clone())
+    };
+    assert_eq!(fields(reproduced.ja3()), fields(chrome.ja3()));
+    assert_eq!(reproduced.akamai(), chrome.akamai());
+}
+
+#[test]
+fn generated_profile_reports_unsupported() {
+    let firefox_ja3 = "771,4865-4867-4866-49195-49199-52393-52392-49196-49200-49162-49161-49171-49172-156-157-47-53,0-23-65281-10-11-16-5-34-51-43-13-45-28-27-65037,29-23-24-25-256-257,0";
+    let generated = GeneratedProfile::from_ja3(firefox_ja3).unwrap();
+
+    assert_eq!(
+        generated.unsupported(),
+        &[
+            Unsupported::Group(256),
+            Unsupported::Group(257),
+            Unsupported::Extension(34),
+            Unsupported::Extension(28),
+        ]
+    );
+    assert_eq!(generated.extension_order()[..3], [0, 23, 65281]);
+
+    let profile = generated.build().unwrap();
+    assert_eq!(profile.curves(), &["X25519", "P-256", "P-384", "P-521"]);
+    assert_eq!(profile.cipher_list()[0], "TLS_AES_128_GCM_SHA256");
+}
+
+#[test]
+fn generated_profile_rejects_invalid_input() {
+    assert!(GeneratedProfile::from_ja3("771,4865").is_err());
+    assert!(GeneratedProfile::from_client_hello(&[22, 3, 1, 0, 10, 1]).is_err());
+    assert!(GeneratedProfile::from_pcap(b"not a capture").is_err());
+
+    let generated = GeneratedProfile::from_ja3("771,4865,0,29,0").unwrap();
+    assert!(generated.clone().with_akamai("1:65536|00|0").is_err());
+    assert!(generated.with_akamai("1:65536|00|0|m,a,s,x").is_err());
+}
+
+#[test]
+fn generated_profile_from_pcap() {
+    // sni, supported_groups (X25519, P-256), signature_algorithms,
+    // alpn (h2), compress_certificate (brotli) and record_size_limit
+    let mut extensions = Vec::new();
+    extensions.extend_from_slice(&[0, 0, 0, 14, 0, 12, 0, 0, 9]);
+    extensions.extend_from_slice(b"localhost");
+    extensions.extend_from_slice(&[0, 10, 0, 6, 0, 4, 0, 0x1d, 0, 0x17]);
+    extensions.extend_from_slice(&[0, 13, 0, 6, 0, 4, 4, 3, 8, 4]);
+    extensions.extend_from_slice(&[0, 16, 0, 5, 0, 3, 2, b'h', b'2']);
+    extensions.extend_from_slice(&[0, 27, 0, 3, 2, 0, 2]);
+    extensions.extend_from_slice(&[0, 28, 0, 2, 0x40, 0x01]);
+
+    let mut body = vec![3, 3];
+    body.extend_from_slice(&[0; 32]);
+    body.extend_from_slice(&[0, 0, 4, 0x13, 0x01, 0xc0, 0x2b, 1, 0]);
+    body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    body.extend_from_slice(&extensions);
+
+    let mut hello = vec![1, 0];
+    hello.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    hello.extend_from_slice(&body);
+    let mut record = vec![22, 3, 1];
+    record.extend_from_slice(&(hello.len() as u16).to_be_bytes());
+    record.extend_from_slice(&hello);
+
+    // Split the record over two TCP segments, with a retransmission of the
+    // first in between.
+    let (first, second) = record.split_at(40);
+    let mut pcap = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    pcap.extend_from_slice(&[0xff, 0xff, 0, 0, 1, 0, 0, 0]);
+    for (seq, payload) in [(1000, first), (1000, first), (1040, second)] {
+        let frame = ethernet_tcp_frame(seq, payload);
+        pcap.extend_from_slice(&[0; 8]);
+        pcap.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        pcap.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        pcap.extend_from_slice(&frame);
+    }
+
+    let generated = GeneratedProfile::from_pcap(&pcap).unwrap();
+    assert_eq!(generated.unsupported(), &[Unsupported::Extension(28)]);
+    assert_eq!(generated.extension_order(), &[0, 10, 13, 16, 27, 28]);
+
+    let from_records = GeneratedProfile::from_client_hello(&record).unwrap();
+    let from_message = GeneratedProfile::from_client_hello(&hello).unwrap();
+    assert_eq!(
+        from_records.extension_order(),
+        from_message.extension_order()
+    );
+
+    let profile = generated.build().unwrap();
+    assert_eq!(
+        profile.cipher_list(),
+        &[
+            "TLS_AES_128_GCM_SHA256",
+            "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"
+        ]
+    );
+    assert_eq!(profile.curves(), &["X25519", "P-256"]);
+    assert_eq!(
+        profile.sigalgs_list(),
+        &["ecdsa_secp256r1_sha256", "rsa_pss_rsae_sha256"]
+    );
+    assert_eq!(profile.alpn_protos(), &["h2"]);
+}
+
+fn ethernet_tcp_frame(seq: u32, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0; 12];
+    frame.extend_from_slice(&[0x08, 0x00]);
+
+    let total_len = (20 + 20 + payload.len()) as u16;
+    frame.extend_from_slice(&[0x45, 0]);
+    frame.extend_from_slice(&total_len.to_be_bytes());
+    frame.extend_from_slice(&[0, 0, 0x40, 0, 64, 6, 0, 0]);
+    frame.extend_from_slice(&[127, 0, 0, 1, 127, 0, 0, 1]);
+
+    frame.extend_from_slice(&[0xc0, 0x00, 0x01, 0xbb]);
+    frame.extend_from_slice(&seq.to_be_bytes());
+    frame.extend_from_slice(&[0, 0, 0, 0, 0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+#[tokio::test]
+async fn fingerprint_chrome_sends_ech_grease() {
+    for (version, ech_grease) in [
+        (ChromeVersion::V116, false),
+        (ChromeVersion::V120, true),
+        (ChromeVersion::V131, true),
+    ] {
+        let fingerprint = Fingerprint::of_profile(version.profile()).await.unwrap();
+        let extensions = fingerprint.ja3().split(',').nth(2).unwrap().to_owned();
+        assert_eq!(
+            extensions.split('-').any(|e| e == "65037"),
+            ech_grease,
+            "{:?}",
+            version
+        );
+    }
+}
@@ -0,0 +1,293 @@
+mod support;
+use support::*;
+
+use std::sync::{Arc, Mutex};
+
+use reqwest_impersonate::alt_svc;
+use reqwest_impersonate::browser::ChromeVersion;
+use reqwest_impersonate::{Client, StatusCode, Url, Version};
+
+fn client_for(server: &server::Server) -> reqwest_impersonate::ClientBuilder {
+    Client::builder()
+        .danger_accept_invalid_certs(true)
+        .resolve("localhost", server.addr())
+        .no_proxy()
+}
+
+/// An `h3` server answering every request with `body`, which records the
+/// requests it got.
+fn recording_server(body: &'static str) -> (server::Server, Arc<Mutex<Vec<server::H3Request>>>) {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+    let server = server::h3(move |req| {
+        recorded.lock().unwrap().push(req);
+        http::Response::builder()
+            .header("x-served-by", "quiche")
+            .body(body.as_bytes().to_vec())
+            .unwrap()
+    });
+    (server, requests)
+}
+
+fn header<'a>(req: &'a server::H3Request, name: &str) -> Option<&'a str> {
+    req.headers
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+#[tokio::test]
+async fn http3_get() {
+    let (server, requests) = recording_server("Hello");
+
+    let url = format!("https://localhost:{}/h3?q=1", server.addr().port());
+    let res = client_for(&server)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .version(Version::HTTP_3)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.version(), Version::HTTP_3);
+    assert_eq!(res.headers()["x-served-by"], "quiche");
+    assert_eq!(res.text().await.expect("body"), "Hello");
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(header(&requests[0], ":method"), Some("GET"));
+    assert_eq!(header(&requests[0], ":path"), Some("/h3?q=1"));
+    assert_eq!(
+        header(&requests[0], ":authority"),
+        Some(&*format!("localhost:{}", server.addr().port()))
+    );
+}
+
+#[tokio::test]
+async fn http3_post_body() {
+    let (server, requests) = recording_server("");
+
+    let url = format!("https://localhost:{}/upload", server.addr().port());
+    let res = client_for(&server)
+        .build()
+        .expect("client builder")
+        .post(&url)
+        .version(Version::HTTP_3)
+        .body("a request body")
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let requests = requests.lock().unwrap();
+    assert_eq!(header(&requests[0], ":method"), Some("POST"));
+    assert_eq!(requests[0].body, b"a request body");
+}
+
+#[tokio::test]
+async fn http3_connection_reused() {
+    let (server, requests) = recording_server("Hello");
+
+    let client = client_for(&server).build().expect("client builder");
+    let url = format!("https://localhost:{}/", server.addr().port());
+    for _ in 0..2 {
+        let res = client
+            .get(&url)
+            .version(Version::HTTP_3)
+            .send()
+            .await
+            .expect("request");
+        assert_eq!(res.text().await.expect("body"), "Hello");
+    }
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].peer, requests[1].peer);
+}
+
+#[tokio::test]
+async fn http3_chrome_fingerprint() {
+    let (server, requests) = recording_server("Hello");
+
+    let url = format!("https://localhost:{}/", server.addr().port());
+    client_for(&server)
+        .chrome_builder(ChromeVersion::V131)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .version(Version::HTTP_3)
+        .send()
+        .await
+        .expect("request");
+
+    let requests = requests.lock().unwrap();
+    let params = &requests[0].transport_params;
+    assert_eq!(params.max_idle_timeout, 30_000);
+    assert_eq!(params.max_udp_payload_size, 1472);
+    assert_eq!(params.initial_max_data, 15_728_640);
+    assert_eq!(params.initial_max_stream_data_bidi_local, 6_291_456);
+    assert_eq!(params.initial_max_stream_data_bidi_remote, 6_291_456);
+    assert_eq!(params.initial_max_stream_data_uni, 6_291_456);
+    assert_eq!(params.initial_max_streams_bidi, 100);
+    assert_eq!(params.initial_max_streams_uni, 103);
+    // ALPS is offered for `h3` under the profile's codepoint, next to an
+    // ECH GREASE extension.
+    assert_eq!(requests[0].tls_extensions, [0, 16, 57, 17513, 0xfe0d]);
+
+    let pseudo = requests[0]
+        .headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| name.starts_with(':'))
+        .collect::<Vec<_>>();
+    assert_eq!(pseudo, [":method", ":authority", ":scheme", ":path"]);
+    assert!(header(&requests[0], "sec-ch-ua").is_some());
+}
+
+#[tokio::test]
+async fn http3_requires_https() {
+    let res = Client::new()
+        .get("http://localhost:1/")
+        .version(Version::HTTP_3)
+        .send()
+        .await;
+
+    assert!(res.unwrap_err().is_request());
+}
+
+#[tokio::test]
+async fn http3_refuses_proxy() {
+    let (server, requests) = recording_server("Hello");
+
+    let url = format!("https://localhost:{}/", server.addr().port());
+    let res = client_for(&server)
+        .proxy(reqwest_impersonate::Proxy::https("http://127.0.0.1:1").unwrap())
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .version(Version::HTTP_3)
+        .send()
+        .await;
+
+    assert!(res.unwrap_err().is_request());
+    assert!(requests.lock().unwrap().is_empty());
+}
+
+/// An HTTP/1 server over TLS, advertising `alt_svc` in every response.
+fn alt_svc_origin(alt_svc: String) -> server::Server {
+    server::https(move |_, _req| {
+        let alt_svc = alt_svc.clone();
+        async move {
+            http::Response::builder()
+                .header("alt-svc", alt_svc)
+                .body("tcp".into())
+                .unwrap()
+        }
+    })
+}
+
+#[tokio::test]
+async fn alt_svc_upgrades_to_http3() {
+    let (h3, requests) = recording_server("quic");
+    let origin = alt_svc_origin(format!("h3=\":{}\"; ma=60", h3.addr().port()));
+
+    let client = client_for(&origin)
+        .alt_svc(true)
+        .build()
+        .expect("client builder");
+    let url = format!("https://localhost:{}/", origin.addr().port());
+
+    let res = client.get(&url).send().await.expect("first request");
+    assert_eq!(res.version(), Version::HTTP_11);
+    assert_eq!(res.text().await.expect("body"), "tcp");
+
+    let res = client.get(&url).send().await.expect("second request");
+    assert_eq!(res.version(), Version::HTTP_3);
+    assert_eq!(res.text().await.expect("body"), "quic");
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        header(&requests[0], ":authority"),
+        Some(&*format!("localhost:{}", origin.addr().port()))
+    );
+}
+
+#[tokio::test]
+async fn alt_svc_clear_stops_upgrades() {
+    let h3 = server::h3(|_| {
+        http::Response::builder()
+            .header("alt-svc", "clear")
+            .body(b"quic".to_vec())
+            .unwrap()
+    });
+    let origin = alt_svc_origin("clear".into());
+
+    let url = format!("https://localhost:{}/", origin.addr().port());
+    let cache = Arc::new(alt_svc::Cache::new());
+    cache.add_alt_svc_str(
+        &format!("h3=\":{}\"", h3.addr().port()),
+        &Url::parse(&url).unwrap(),
+    );
+    let client = client_for(&origin)
+        .alt_svc_cache(cache.clone())
+        .build()
+        .expect("client builder");
+
+    let res = client.get(&url).send().await.expect("first request");
+    assert_eq!(res.version(), Version::HTTP_3);
+    assert!(cache.alternatives(&Url::parse(&url).unwrap()).is_empty());
+
+    let res = client.get(&url).send().await.expect("second request");
+    assert_eq!(res.version(), Version::HTTP_11);
+}
+
+#[tokio::test]
+async fn alt_svc_falls_back_to_tcp() {
+    // Nothing answers on this socket, so the QUIC handshake times out.
+    let unreachable = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let origin = alt_svc_origin("clear".into());
+
+    let url = format!("https://localhost:{}/", origin.addr().port());
+    let cache = Arc::new(alt_svc::Cache::new());
+    cache.add_alt_svc_str(
+        &format!("h3=\":{}\"", unreachable.local_addr().unwrap().port()),
+        &Url::parse(&url).unwrap(),
+    );
+    let client = client_for(&origin)
+        .alt_svc_cache(cache)
+        .connect_timeout(std::time::Duration::from_millis(500))
+        .build()
+        .expect("client builder");
+
+    let res = client.post(&url).body("hi").send().await.expect("request");
+    assert_eq!(res.version(), Version::HTTP_11);
+    assert_eq!(res.text().await.expect("body"), "tcp");
+}
+
+#[tokio::test]
+async fn alt_svc_skipped_behind_proxy() {
+    let (h3, requests) = recording_server("quic");
+    let origin = alt_svc_origin("clear".into());
+
+    let url = format!("https://localhost:{}/", origin.addr().port());
+    let cache = Arc::new(alt_svc::Cache::new());
+    cache.add_alt_svc_str(
+        &format!("h3=\":{}\"", h3.addr().port()),
+        &Url::parse(&url).unwrap(),
+    );
+    // Nothing listens on the proxy, so only an upgrade could succeed.
+    let res = client_for(&origin)
+        .proxy(reqwest_impersonate::Proxy::https("http://127.0.0.1:1").unwrap())
+        .alt_svc_cache(cache)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await;
+
+    assert!(res.unwrap_err().is_connect());
+    assert!(requests.lock().unwrap().is_empty());
+}
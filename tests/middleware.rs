@@ -0,0 +1,159 @@
+mod support;
+use std::sync::{Arc, Mutex};
+
+use reqwest_impersonate::middleware::{BoxFuture, Middleware, Next};
+use reqwest_impersonate::{Body, StatusCode};
+use support::*;
+
+/// Signs every request and records the path of each hop.
+struct Signer {
+    seen: Arc<Mutex<Vec<String>>>,
+}
+
+impl Middleware for Signer {
+    fn handle<'a>(
+        &'a self,
+        mut req: http::Request<Body>,
+        next: Next,
+    ) -> BoxFuture<'a, reqwest_impersonate::Result<http::Response<Body>>> {
+        self.seen.lock().unwrap().push(req.uri().path().to_owned());
+        req.headers_mut()
+            .insert("x-signature", http::HeaderValue::from_static("signed"));
+        next.run(req)
+    }
+}
+
+/// Answers requests to `/cached` without sending them.
+struct Cache;
+
+impl Middleware for Cache {
+    fn handle<'a>(
+        &'a self,
+        req: http::Request<Body>,
+        next: Next,
+    ) -> BoxFuture<'a, reqwest_impersonate::Result<http::Response<Body>>> {
+        if req.uri().path() == "/cached" {
+            Box::pin(async { Ok(http::Response::new(Body::from("from cache"))) })
+        } else {
+            next.run(req)
+        }
+    }
+}
+
+/// Sends a request again with a token when it is challenged.
+struct Auth;
+
+impl Middleware for Auth {
+    fn handle<'a>(
+        &'a self,
+        req: http::Request<Body>,
+        next: Next,
+    ) -> BoxFuture<'a, reqwest_impersonate::Result<http::Response<Body>>> {
+        Box::pin(async move {
+            let mut retry = http::Request::new(Body::from(req.body().as_bytes().unwrap().to_vec()));
+            *retry.method_mut() = req.method().clone();
+            *retry.uri_mut() = req.uri().clone();
+            *retry.headers_mut() = req.headers().clone();
+
+            let res = next.clone().run(req).await?;
+            if res.status() != StatusCode::UNAUTHORIZED {
+                return Ok(res);
+            }
+            retry.headers_mut().insert(
+                http::header::AUTHORIZATION,
+                http::HeaderValue::from_static("Bearer fresh"),
+            );
+            next.run(retry).await
+        })
+    }
+}
+
+#[tokio::test]
+async fn middleware_sees_every_redirect_hop() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()["x-signature"], "signed");
+        if req.uri().path() == "/start" {
+            http::Response::builder()
+                .status(302)
+                .header("location", "/dst")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            http::Response::default()
+        }
+    });
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let client = reqwest_impersonate::Client::builder()
+        .middleware(Signer { seen: seen.clone() })
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/start", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.url().path(), "/dst");
+    assert_eq!(*seen.lock().unwrap(), ["/start", "/dst"]);
+}
+
+#[tokio::test]
+async fn middleware_can_answer_requests() {
+    let server = server::http(move |_req| async move { http::Response::new("from server".into()) });
+
+    let client = reqwest_impersonate::Client::builder()
+        .middleware(Cache)
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/cached", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "from cache");
+
+    let url = format!("http://{}/other", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "from server");
+}
+
+#[tokio::test]
+async fn middleware_can_resend_on_challenge() {
+    let server = server::http(move |req| async move {
+        match req.headers().get(http::header::AUTHORIZATION) {
+            Some(token) => {
+                assert_eq!(token, "Bearer fresh");
+                let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                assert_eq!(&*body, b"payload");
+                http::Response::default()
+            }
+            None => http::Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(Default::default())
+                .unwrap(),
+        }
+    });
+
+    let client = reqwest_impersonate::Client::builder()
+        .middleware(Auth)
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/protected", server.addr());
+    let res = client.post(&url).body("payload").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn middleware_runs_in_order() {
+    let server = server::http(move |_req| async move { http::Response::new("from server".into()) });
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let client = reqwest_impersonate::Client::builder()
+        .middleware(Cache)
+        .middleware(Signer { seen: seen.clone() })
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/cached", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "from cache");
+    assert!(seen.lock().unwrap().is_empty());
+}
@@ -0,0 +1,169 @@
+mod support;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest_impersonate::{retry, StatusCode};
+use support::*;
+
+fn unavailable_until(ok_at: usize, hits: Arc<AtomicUsize>) -> server::Server {
+    server::http(move |_req| {
+        let hits = hits.clone();
+        async move {
+            let status = if hits.fetch_add(1, Ordering::SeqCst) + 1 < ok_at {
+                StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                StatusCode::OK
+            };
+            http::Response::builder()
+                .status(status)
+                .header("retry-after", "0")
+                .body(Default::default())
+                .unwrap()
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_retry_status() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = unavailable_until(3, hits.clone());
+
+    let client = reqwest_impersonate::Client::builder()
+        .retry(retry::Policy::limited(3).statuses([StatusCode::SERVICE_UNAVAILABLE]))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.attempts(), 3);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retry_status_exhausted() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = unavailable_until(10, hits.clone());
+
+    let client = reqwest_impersonate::Client::builder()
+        .retry(
+            retry::Policy::limited(2)
+                .statuses([StatusCode::SERVICE_UNAVAILABLE])
+                .backoff(Duration::from_millis(1), Duration::from_millis(10)),
+        )
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.attempts(), 3);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retry_status_skips_non_idempotent() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = unavailable_until(3, hits.clone());
+
+    let client = reqwest_impersonate::Client::builder()
+        .retry(retry::Policy::limited(3).statuses([StatusCode::SERVICE_UNAVAILABLE]))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client.post(&url).body("hello").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.attempts(), 1);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_retry_per_request_policy() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = unavailable_until(2, hits.clone());
+
+    let client = reqwest_impersonate::Client::new();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.attempts(), 1);
+
+    let res = client
+        .get(&url)
+        .retry(retry::Policy::limited(1).statuses([StatusCode::SERVICE_UNAVAILABLE]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.attempts(), 2);
+}
+
+#[tokio::test]
+async fn test_retry_attempts_across_redirect() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let hits = hits.clone();
+        move |req| {
+            let first = req.uri().path() == "/retry" && hits.fetch_add(1, Ordering::SeqCst) == 0;
+            async move {
+                let res = http::Response::builder().header("retry-after", "0");
+                let res = if first {
+                    res.status(StatusCode::SERVICE_UNAVAILABLE)
+                } else if req.uri().path() == "/retry" {
+                    res.status(StatusCode::FOUND).header("location", "/dst")
+                } else {
+                    res.status(StatusCode::OK)
+                };
+                res.body(Default::default()).unwrap()
+            }
+        }
+    });
+
+    let client = reqwest_impersonate::Client::builder()
+        .retry(retry::Policy::limited(3).statuses([StatusCode::SERVICE_UNAVAILABLE]))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.url().path(), "/dst");
+    // 503, then 302, then 200
+    assert_eq!(res.attempts(), 3);
+}
+
+#[tokio::test]
+async fn test_retry_timeout_bounds_backoff() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let hits = hits.clone();
+        move |_req| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            async {
+                http::Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .header("retry-after", "5")
+                    .body(Default::default())
+                    .unwrap()
+            }
+        }
+    });
+
+    let client = reqwest_impersonate::Client::builder()
+        .retry(
+            retry::Policy::limited(3)
+                .statuses([StatusCode::SERVICE_UNAVAILABLE])
+                .backoff(Duration::from_millis(100), Duration::from_secs(10)),
+        )
+        .timeout(Duration::from_millis(300))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
@@ -5,6 +5,7 @@ use std::sync::mpsc as std_mpsc;
 use std::thread;
 use std::time::Duration;
 
+use foreign_types::ForeignTypeRef;
 use tokio::sync::oneshot;
 
 use tokio::runtime;
@@ -88,3 +89,376 @@ where
     .join()
     .unwrap()
 }
+
+/// What the TLS handshake of an `https` server connection negotiated.
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct Handshake {
+    pub version: &'static str,
+    pub server_name: Option<String>,
+    pub client_certificate: bool,
+    pub session_reused: bool,
+    pub early_data_accepted: bool,
+    pub ech_accepted: bool,
+}
+
+/// Like `http`, but serving HTTP/1 over BoringSSL with the self-signed
+/// `server.cert` for `localhost`.
+#[allow(unused)]
+pub fn https<F, Fut>(func: F) -> Server
+where
+    F: Fn(Handshake, http::Request<hyper::Body>) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = http::Response<hyper::Body>> + Send + 'static,
+{
+    https_with(tls_acceptor_builder().build(), func)
+}
+
+/// Like `https`, but with a TLS acceptor configured by the test, e.g. from
+/// `tls_acceptor_builder`.
+#[allow(unused)]
+pub fn https_with<F, Fut>(acceptor: boring::ssl::SslAcceptor, func: F) -> Server
+where
+    F: Fn(Handshake, http::Request<hyper::Body>) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = http::Response<hyper::Body>> + Send + 'static,
+{
+    //Spawn new runtime in thread to prevent reactor execution context conflict
+    thread::spawn(move || {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("new rt");
+        let listener = rt
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .expect("bind");
+        let addr = listener.local_addr().expect("local_addr");
+        let acceptor = std::sync::Arc::new(acceptor);
+
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
+        let (panic_tx, panic_rx) = std_mpsc::channel();
+        let tname = format!(
+            "test({})-support-server",
+            thread::current().name().unwrap_or("<unknown>")
+        );
+        thread::Builder::new()
+            .name(tname)
+            .spawn(move || {
+                rt.block_on(async move {
+                    loop {
+                        let tcp = tokio::select! {
+                            _ = &mut shutdown_rx => break,
+                            accepted = listener.accept() => accepted.expect("accept").0,
+                        };
+                        let acceptor = acceptor.clone();
+                        let func = func.clone();
+                        tokio::spawn(async move {
+                            let stream = match tokio_boring::accept(&acceptor, tcp).await {
+                                Ok(stream) => stream,
+                                Err(_) => return,
+                            };
+                            let handshake = Handshake {
+                                version: stream.ssl().version_str(),
+                                server_name: stream
+                                    .ssl()
+                                    .servername(boring::ssl::NameType::HOST_NAME)
+                                    .map(str::to_owned),
+                                client_certificate: stream.ssl().peer_certificate().is_some(),
+                                session_reused: stream.ssl().session_reused(),
+                                early_data_accepted: unsafe {
+                                    boring_sys::SSL_early_data_accepted(stream.ssl().as_ptr()) == 1
+                                },
+                                ech_accepted: unsafe {
+                                    boring_sys::SSL_ech_accepted(stream.ssl().as_ptr()) == 1
+                                },
+                            };
+                            let svc = hyper::service::service_fn(move |req| {
+                                let fut = func(handshake.clone(), req);
+                                async move { Ok::<_, Infallible>(fut.await) }
+                            });
+                            let _ = hyper::server::conn::Http::new()
+                                .serve_connection(stream, svc)
+                                .await;
+                        });
+                    }
+                });
+                let _ = panic_tx.send(());
+            })
+            .expect("thread spawn");
+
+        Server {
+            addr,
+            panic_rx,
+            shutdown_tx: Some(shutdown_tx),
+        }
+    })
+    .join()
+    .unwrap()
+}
+
+/// The TLS acceptor `https` uses, serving `server.cert`.
+pub fn tls_acceptor_builder() -> boring::ssl::SslAcceptorBuilder {
+    use boring::pkey::PKey;
+    use boring::ssl::{SslAcceptor, SslMethod, SslVerifyMode};
+    use boring::x509::X509;
+
+    let key = PKey::private_key_from_pem(include_bytes!("server.key")).unwrap();
+    let cert = X509::from_pem(include_bytes!("server.cert")).unwrap();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    // Ask for, but don't require or verify, a client certificate.
+    acceptor.set_verify_callback(SslVerifyMode::PEER, |_, _| true);
+    acceptor
+}
+
+/// Enables Encrypted Client Hello on `acceptor` with a new key, and returns
+/// the `ECHConfigList` clients should use.
+#[allow(unused)]
+pub fn enable_ech(acceptor: &mut boring::ssl::SslAcceptorBuilder, config_id: u8) -> Vec<u8> {
+    use boring_sys as ffi;
+
+    unsafe {
+        let key = ffi::EVP_HPKE_KEY_new();
+        assert_eq!(
+            ffi::EVP_HPKE_KEY_generate(key, ffi::EVP_hpke_x25519_hkdf_sha256()),
+            1
+        );
+
+        let mut config = std::ptr::null_mut();
+        let mut config_len = 0;
+        let public_name = b"localhost\0";
+        assert_eq!(
+            ffi::SSL_marshal_ech_config(
+                &mut config,
+                &mut config_len,
+                config_id,
+                key,
+                public_name.as_ptr().cast(),
+                16,
+            ),
+            1
+        );
+
+        let keys = ffi::SSL_ECH_KEYS_new();
+        assert_eq!(ffi::SSL_ECH_KEYS_add(keys, 1, config, config_len, key), 1);
+
+        let mut list = std::ptr::null_mut();
+        let mut list_len = 0;
+        assert_eq!(
+            ffi::SSL_ECH_KEYS_marshal_retry_configs(keys, &mut list, &mut list_len),
+            1
+        );
+        let config_list = std::slice::from_raw_parts(list, list_len).to_vec();
+
+        assert_eq!(ffi::SSL_CTX_set1_ech_keys(acceptor.as_ptr(), keys), 1);
+
+        ffi::OPENSSL_free(list.cast());
+        ffi::OPENSSL_free(config.cast());
+        ffi::SSL_ECH_KEYS_free(keys);
+        ffi::EVP_HPKE_KEY_free(key);
+
+        config_list
+    }
+}
+
+/// A request received by an `h3` server, along with what the QUIC
+/// connection it came on negotiated.
+#[cfg(feature = "http3")]
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct H3Request {
+    /// The header fields in the order they were sent, pseudo-headers
+    /// included.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub peer: net::SocketAddr,
+    pub transport_params: quiche::TransportParams,
+    /// Which of `H3_TLS_EXTENSIONS` the ClientHello had, in that order.
+    pub tls_extensions: Vec<u16>,
+}
+
+/// The ClientHello extensions an `h3` server looks for: `server_name`,
+/// ALPN, QUIC transport parameters, both ALPS codepoints, and ECH.
+#[cfg(feature = "http3")]
+pub const H3_TLS_EXTENSIONS: [u16; 6] = [0, 16, 57, 17513, 17613, 0xfe0d];
+
+/// Like `https`, but serving HTTP/3 over QUIC with quiche.
+#[cfg(feature = "http3")]
+#[allow(unused)]
+pub fn h3<F>(func: F) -> Server
+where
+    F: Fn(H3Request) -> http::Response<Vec<u8>> + Send + 'static,
+{
+    use quiche::h3::NameValue;
+    use std::collections::HashMap;
+
+    struct Conn {
+        quic: quiche::Connection,
+        h3: Option<quiche::h3::Connection>,
+        requests: HashMap<u64, (Vec<(String, String)>, Vec<u8>)>,
+    }
+
+    use boring::pkey::PKey;
+    use boring::ssl::{ExtensionType, Ssl, SslContextBuilder, SslMethod};
+    use boring::x509::X509;
+
+    let extensions = Ssl::new_ex_index::<Vec<u16>>().unwrap();
+    let mut ctx = SslContextBuilder::new(SslMethod::tls()).unwrap();
+    ctx.set_private_key(&PKey::private_key_from_pem(include_bytes!("server.key")).unwrap())
+        .unwrap();
+    ctx.set_certificate(&X509::from_pem(include_bytes!("server.cert")).unwrap())
+        .unwrap();
+    ctx.set_select_certificate_callback(move |mut hello| {
+        let found = H3_TLS_EXTENSIONS
+            .iter()
+            .copied()
+            .filter(|&ext| hello.get_extension(ExtensionType::from(ext)).is_some())
+            .collect();
+        hello.ssl_mut().set_ex_data(extensions, found);
+        Ok(())
+    });
+    let mut config =
+        quiche::Config::with_boring_ssl_ctx_builder(quiche::PROTOCOL_VERSION, ctx).unwrap();
+    config.set_application_protos(&[b"h3"]).unwrap();
+    config.set_max_idle_timeout(5_000);
+    config.set_initial_max_data(10_000_000);
+    config.set_initial_max_stream_data_bidi_local(1_000_000);
+    config.set_initial_max_stream_data_bidi_remote(1_000_000);
+    config.set_initial_max_stream_data_uni(1_000_000);
+    config.set_initial_max_streams_bidi(100);
+    config.set_initial_max_streams_uni(100);
+    let h3_config = quiche::h3::Config::new().unwrap();
+
+    let socket = net::UdpSocket::bind("127.0.0.1:0").expect("bind");
+    let addr = socket.local_addr().expect("local_addr");
+
+    let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
+    let (panic_tx, panic_rx) = std_mpsc::channel();
+    let tname = format!(
+        "test({})-support-server",
+        thread::current().name().unwrap_or("<unknown>")
+    );
+    thread::Builder::new()
+        .name(tname)
+        .spawn(move || {
+            let mut conns: HashMap<net::SocketAddr, Conn> = HashMap::new();
+            let mut buf = [0; 65535];
+            let mut out = [0; 1350];
+
+            while shutdown_rx.try_recv().is_err() {
+                let timeout = conns
+                    .values()
+                    .filter_map(|c| c.quic.timeout())
+                    .min()
+                    .unwrap_or(Duration::from_millis(50))
+                    .clamp(Duration::from_millis(1), Duration::from_millis(50));
+                socket.set_read_timeout(Some(timeout)).unwrap();
+
+                match socket.recv_from(&mut buf) {
+                    Ok((len, from)) => {
+                        let conn = conns.entry(from).or_insert_with(|| {
+                            let mut scid = [0; quiche::MAX_CONN_ID_LEN];
+                            boring::rand::rand_bytes(&mut scid).unwrap();
+                            let scid = quiche::ConnectionId::from_vec(scid.to_vec());
+                            Conn {
+                                quic: quiche::accept(&scid, None, addr, from, &mut config).unwrap(),
+                                h3: None,
+                                requests: HashMap::new(),
+                            }
+                        });
+                        let info = quiche::RecvInfo { from, to: addr };
+                        let _ = conn.quic.recv(&mut buf[..len], info);
+                    }
+                    Err(_) => conns.values_mut().for_each(|c| c.quic.on_timeout()),
+                }
+
+                for (peer, conn) in conns.iter_mut() {
+                    if conn.h3.is_none() && conn.quic.is_established() {
+                        conn.h3 = Some(
+                            quiche::h3::Connection::with_transport(&mut conn.quic, &h3_config)
+                                .unwrap(),
+                        );
+                    }
+                    if let Some(h3) = conn.h3.as_mut() {
+                        loop {
+                            let (id, event) = match h3.poll(&mut conn.quic) {
+                                Ok(event) => event,
+                                Err(_) => break,
+                            };
+                            match event {
+                                quiche::h3::Event::Headers { list, .. } => {
+                                    let headers = list
+                                        .iter()
+                                        .map(|h| {
+                                            (
+                                                String::from_utf8_lossy(h.name()).into_owned(),
+                                                String::from_utf8_lossy(h.value()).into_owned(),
+                                            )
+                                        })
+                                        .collect();
+                                    conn.requests.insert(id, (headers, Vec::new()));
+                                }
+                                quiche::h3::Event::Data => {
+                                    while let Ok(n) = h3.recv_body(&mut conn.quic, id, &mut buf) {
+                                        if let Some(req) = conn.requests.get_mut(&id) {
+                                            req.1.extend_from_slice(&buf[..n]);
+                                        }
+                                    }
+                                }
+                                quiche::h3::Event::Finished => {
+                                    let (headers, body) = match conn.requests.remove(&id) {
+                                        Some(req) => req,
+                                        None => continue,
+                                    };
+                                    let res = func(H3Request {
+                                        headers,
+                                        body,
+                                        peer: *peer,
+                                        transport_params: conn
+                                            .quic
+                                            .peer_transport_params()
+                                            .cloned()
+                                            .unwrap(),
+                                        tls_extensions: AsMut::<boring::ssl::SslRef>::as_mut(
+                                            &mut conn.quic,
+                                        )
+                                        .ex_data(extensions)
+                                        .cloned()
+                                        .unwrap_or_default(),
+                                    });
+                                    let mut headers = vec![quiche::h3::Header::new(
+                                        b":status",
+                                        res.status().as_str().as_bytes(),
+                                    )];
+                                    for (name, value) in res.headers() {
+                                        headers.push(quiche::h3::Header::new(
+                                            name.as_str().as_bytes(),
+                                            value.as_bytes(),
+                                        ));
+                                    }
+                                    h3.send_response(&mut conn.quic, id, &headers, false)
+                                        .unwrap();
+                                    h3.send_body(&mut conn.quic, id, res.body(), true).unwrap();
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+
+                    while let Ok((len, info)) = conn.quic.send(&mut out) {
+                        let _ = socket.send_to(&out[..len], info.to);
+                    }
+                }
+
+                conns.retain(|_, conn| !conn.quic.is_closed());
+            }
+            let _ = panic_tx.send(());
+        })
+        .expect("thread spawn");
+
+    Server {
+        addr,
+        panic_rx,
+        shutdown_tx: Some(shutdown_tx),
+    }
+}
@@ -0,0 +1,577 @@
+mod support;
+use support::*;
+
+use std::sync::{Arc, Mutex};
+
+use boring::ssl::{CertificateCompressionAlgorithm, CertificateCompressor};
+use foreign_types::ForeignTypeRef;
+use reqwest_impersonate::browser::{
+    BrotliCompressor, CertCompressionAlgorithm, ImpersonateProfile, ZlibCompressor, ZstdCompressor,
+};
+use reqwest_impersonate::tls::Version;
+use reqwest_impersonate::Client;
+
+fn client_for(server: &server::Server) -> reqwest_impersonate::ClientBuilder {
+    Client::builder()
+        .danger_accept_invalid_certs(true)
+        .resolve("localhost", server.addr())
+        .no_proxy()
+}
+
+#[tokio::test]
+async fn tls_sni_sent_by_default() {
+    let server = server::https(move |handshake, _req| async move {
+        assert_eq!(handshake.server_name.as_deref(), Some("localhost"));
+        http::Response::default()
+    });
+
+    let url = format!("https://localhost:{}/sni", server.addr().port());
+    let res = client_for(&server)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn tls_sni_disabled() {
+    let server = server::https(move |handshake, _req| async move {
+        assert_eq!(handshake.server_name, None);
+        http::Response::default()
+    });
+
+    let url = format!("https://localhost:{}/sni", server.addr().port());
+    let res = client_for(&server)
+        .chrome_builder(reqwest_impersonate::browser::ChromeVersion::V131)
+        .tls_sni(false)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn max_tls_version_overrides_profile() {
+    let server = server::https(move |handshake, _req| async move {
+        assert_eq!(handshake.version, "TLSv1.2");
+        http::Response::default()
+    });
+
+    let url = format!("https://localhost:{}/version", server.addr().port());
+    let res = client_for(&server)
+        .chrome_builder(reqwest_impersonate::browser::ChromeVersion::V131)
+        .max_tls_version(Version::TLS_1_2)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn min_tls_version_is_applied() {
+    let server = server::https(move |_handshake, _req| async move { http::Response::default() });
+
+    // With no version left to offer, the handshake cannot succeed.
+    let url = format!("https://localhost:{}/version", server.addr().port());
+    let err = client_for(&server)
+        .safari_builder(reqwest_impersonate::browser::SafariVersion::V17)
+        .min_tls_version(Version::TLS_1_3)
+        .max_tls_version(Version::TLS_1_2)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_connect());
+}
+
+#[tokio::test]
+async fn add_root_certificate() {
+    let server = server::https(move |_handshake, _req| async move { http::Response::default() });
+
+    let cert = reqwest_impersonate::Certificate::from_pem(include_bytes!("support/server.cert"))
+        .expect("certificate");
+
+    let url = format!("https://localhost:{}/root", server.addr().port());
+    let res = Client::builder()
+        .chrome_builder(reqwest_impersonate::browser::ChromeVersion::V131)
+        .add_root_certificate(cert)
+        .tls_built_in_root_certs(false)
+        .resolve("localhost", server.addr())
+        .no_proxy()
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn untrusted_server_certificate() {
+    let server = server::https(move |_handshake, _req| async move { http::Response::default() });
+
+    let url = format!("https://localhost:{}/root", server.addr().port());
+    let err = Client::builder()
+        .tls_built_in_root_certs(false)
+        .resolve("localhost", server.addr())
+        .no_proxy()
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_connect());
+}
+
+#[tokio::test]
+async fn client_identity() {
+    let server = server::https(move |handshake, _req| async move {
+        assert!(handshake.client_certificate);
+        http::Response::default()
+    });
+
+    let identity = reqwest_impersonate::Identity::from_pem(include_bytes!("support/client.pem"))
+        .expect("identity");
+
+    let url = format!("https://localhost:{}/mtls", server.addr().port());
+    let res = client_for(&server)
+        .firefox_builder(reqwest_impersonate::browser::FirefoxVersion::V120)
+        .identity(identity)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+}
+
+#[test]
+fn identity_requires_certificate() {
+    let err =
+        reqwest_impersonate::Identity::from_pem(include_bytes!("support/server.key")).unwrap_err();
+
+    assert!(err.is_builder());
+}
+
+fn session_reused_server() -> server::Server {
+    server::https(move |handshake, _req| async move {
+        http::Response::builder()
+            .header("x-session-reused", handshake.session_reused.to_string())
+            .body(Default::default())
+            .unwrap()
+    })
+}
+
+async fn sessions_reused(client: &Client, server: &server::Server) -> Vec<String> {
+    let url = format!("https://localhost:{}/session", server.addr().port());
+    let mut reused = Vec::new();
+    for _ in 0..2 {
+        let res = client.get(&url).send().await.expect("request");
+        reused.push(
+            res.headers()["x-session-reused"]
+                .to_str()
+                .unwrap()
+                .to_owned(),
+        );
+        // Read the body, so the session tickets sent after the handshake are
+        // received.
+        res.bytes().await.expect("body");
+    }
+    reused
+}
+
+#[tokio::test]
+async fn tls_session_resumed_on_new_connection() {
+    let server = session_reused_server();
+    let client = client_for(&server)
+        .chrome_builder(reqwest_impersonate::browser::ChromeVersion::V131)
+        .pool_max_idle_per_host(0)
+        .build()
+        .expect("client builder");
+
+    assert_eq!(sessions_reused(&client, &server).await, ["false", "true"]);
+}
+
+#[tokio::test]
+async fn tls_session_cache_disabled() {
+    let server = session_reused_server();
+    let client = client_for(&server)
+        .chrome_builder(reqwest_impersonate::browser::ChromeVersion::V131)
+        .pool_max_idle_per_host(0)
+        .tls_session_cache(false)
+        .build()
+        .expect("client builder");
+
+    assert_eq!(sessions_reused(&client, &server).await, ["false", "false"]);
+}
+
+#[tokio::test]
+async fn tls_sessions_not_shared_between_clients() {
+    let server = session_reused_server();
+    let build = || {
+        client_for(&server)
+            .pool_max_idle_per_host(0)
+            .build()
+            .expect("client builder")
+    };
+
+    let first = build();
+    sessions_reused(&first, &server).await;
+
+    let second = build();
+    assert_eq!(sessions_reused(&second, &server).await, ["false", "true"]);
+}
+
+/// A request an `early_data_server` got: whether its session was resumed
+/// and it came as early data, and its body.
+type EarlyDataRequest = (bool, bool, String);
+
+fn early_data_server(
+    acceptor: boring::ssl::SslAcceptor,
+) -> (server::Server, Arc<Mutex<Vec<EarlyDataRequest>>>) {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+    let server = server::https_with(acceptor, move |handshake, req| {
+        let recorded = recorded.clone();
+        async move {
+            let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+            recorded.lock().unwrap().push((
+                handshake.session_reused,
+                handshake.early_data_accepted,
+                String::from_utf8(body.to_vec()).unwrap(),
+            ));
+            http::Response::default()
+        }
+    });
+    (server, requests)
+}
+
+fn early_data_acceptor() -> boring::ssl::SslAcceptor {
+    let mut acceptor = server::tls_acceptor_builder();
+    unsafe { boring_sys::SSL_CTX_set_early_data_enabled(acceptor.as_ptr(), 1) };
+    acceptor.build()
+}
+
+async fn put(client: &Client, server: &server::Server) {
+    let url = format!("https://localhost:{}/early", server.addr().port());
+    let res = client
+        .put(&url)
+        .body("early")
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+    // Read the body, so the session tickets sent after the handshake are
+    // received.
+    res.bytes().await.expect("body");
+}
+
+#[tokio::test]
+async fn tls_early_data_accepted() {
+    let (server, requests) = early_data_server(early_data_acceptor());
+    let client = client_for(&server)
+        .pool_max_idle_per_host(0)
+        .tls_early_data(true)
+        .build()
+        .expect("client builder");
+
+    put(&client, &server).await;
+    put(&client, &server).await;
+
+    assert_eq!(
+        *requests.lock().unwrap(),
+        [
+            (false, false, "early".to_owned()),
+            (true, true, "early".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn tls_early_data_rejected() {
+    let acceptor = early_data_acceptor();
+    let context = acceptor.clone();
+    let (server, requests) = early_data_server(acceptor);
+    let client = client_for(&server)
+        .pool_max_idle_per_host(0)
+        .tls_early_data(true)
+        .build()
+        .expect("client builder");
+
+    put(&client, &server).await;
+    // The ticket the client got still allows early data, but the server now
+    // rejects it, so the request is sent again after the handshake.
+    unsafe { boring_sys::SSL_CTX_set_early_data_enabled(context.context().as_ptr(), 0) };
+    put(&client, &server).await;
+
+    assert_eq!(
+        *requests.lock().unwrap(),
+        [
+            (false, false, "early".to_owned()),
+            (true, false, "early".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn tls_server_alps_settings_applied() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let mut acceptor = server::tls_acceptor_builder();
+    acceptor.set_alpn_select_callback(|ssl, protos| {
+        // A SETTINGS frame with SETTINGS_MAX_CONCURRENT_STREAMS = 1, which
+        // the server doesn't send over the connection itself.
+        let settings = [0, 0, 6, 4, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 1];
+        let ret = unsafe {
+            boring_sys::SSL_add_application_settings(
+                ssl.as_ptr(),
+                b"h2".as_ptr(),
+                2,
+                settings.as_ptr(),
+                settings.len(),
+            )
+        };
+        assert_eq!(ret, 1);
+        boring::ssl::select_next_proto(b"\x02h2", protos).ok_or(boring::ssl::AlpnError::NOACK)
+    });
+
+    let active = Arc::new(AtomicUsize::new(0));
+    let max_active = Arc::new(AtomicUsize::new(0));
+    let (server_active, server_max_active) = (active.clone(), max_active.clone());
+    let server = server::https_with(acceptor.build(), move |_, _req| {
+        let (active, max_active) = (server_active.clone(), server_max_active.clone());
+        async move {
+            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+            max_active.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            active.fetch_sub(1, Ordering::SeqCst);
+            http::Response::default()
+        }
+    });
+
+    let client = client_for(&server)
+        .chrome_builder(reqwest_impersonate::browser::ChromeVersion::V131)
+        .build()
+        .expect("client builder");
+    let url = format!("https://localhost:{}/alps", server.addr().port());
+    // Open the connection first, so the next requests share it.
+    client.get(&url).send().await.expect("request");
+    let (first, second) = tokio::join!(client.get(&url).send(), client.get(&url).send());
+
+    for res in [first, second] {
+        assert_eq!(
+            res.expect("request").version(),
+            reqwest_impersonate::Version::HTTP_2
+        );
+    }
+    // The requests sharing the connection were sent one after the other.
+    assert_eq!(max_active.load(Ordering::SeqCst), 1);
+}
+
+/// Records the algorithm each time the server compresses its certificate.
+struct Recording<C> {
+    inner: C,
+    algorithm: CertCompressionAlgorithm,
+    used: Arc<Mutex<Vec<CertCompressionAlgorithm>>>,
+}
+
+impl<C: CertificateCompressor> CertificateCompressor for Recording<C> {
+    const ALGORITHM: CertificateCompressionAlgorithm = C::ALGORITHM;
+    const CAN_COMPRESS: bool = true;
+    const CAN_DECOMPRESS: bool = false;
+
+    fn compress<W>(&self, input: &[u8], output: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        self.used.lock().unwrap().push(self.algorithm);
+        self.inner.compress(input, output)
+    }
+}
+
+#[tokio::test]
+async fn cert_compression_round_trip() {
+    for algorithm in [
+        CertCompressionAlgorithm::Zlib,
+        CertCompressionAlgorithm::Brotli,
+        CertCompressionAlgorithm::Zstd,
+    ] {
+        let used = Arc::new(Mutex::new(Vec::new()));
+        let mut acceptor = server::tls_acceptor_builder();
+        acceptor
+            .add_certificate_compression_algorithm(Recording {
+                inner: ZlibCompressor::default(),
+                algorithm: CertCompressionAlgorithm::Zlib,
+                used: used.clone(),
+            })
+            .unwrap();
+        acceptor
+            .add_certificate_compression_algorithm(Recording {
+                inner: BrotliCompressor::default(),
+                algorithm: CertCompressionAlgorithm::Brotli,
+                used: used.clone(),
+            })
+            .unwrap();
+        acceptor
+            .add_certificate_compression_algorithm(Recording {
+                inner: ZstdCompressor::default(),
+                algorithm: CertCompressionAlgorithm::Zstd,
+                used: used.clone(),
+            })
+            .unwrap();
+        let server = server::https_with(acceptor.build(), move |_handshake, _req| async move {
+            http::Response::default()
+        });
+
+        let profile = ImpersonateProfile::builder()
+            .cert_compression_algorithms([algorithm])
+            .build()
+            .expect("profile");
+        let url = format!("https://localhost:{}/compressed", server.addr().port());
+        let res = client_for(&server)
+            .impersonate(profile)
+            .build()
+            .expect("client builder")
+            .get(&url)
+            .send()
+            .await
+            .expect("request");
+
+        assert_eq!(res.status(), reqwest_impersonate::StatusCode::OK);
+        assert_eq!(*used.lock().unwrap(), [algorithm]);
+    }
+}
+
+fn ech_accepted_server(acceptor: boring::ssl::SslAcceptorBuilder) -> server::Server {
+    server::https_with(acceptor.build(), move |handshake, _req| async move {
+        http::Response::builder()
+            .header("x-ech-accepted", handshake.ech_accepted.to_string())
+            .body(Default::default())
+            .unwrap()
+    })
+}
+
+#[tokio::test]
+async fn ech_with_config_list() {
+    let mut acceptor = server::tls_acceptor_builder();
+    let config_list = server::enable_ech(&mut acceptor, 1);
+    let server = ech_accepted_server(acceptor);
+
+    let url = format!("https://localhost:{}/ech", server.addr().port());
+    let res = client_for(&server)
+        .ech_config_list("localhost", &config_list)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.headers()["x-ech-accepted"], "true");
+}
+
+#[tokio::test]
+async fn ech_rejected_uses_retry_configs() {
+    let stale = server::enable_ech(&mut server::tls_acceptor_builder(), 1);
+    let mut acceptor = server::tls_acceptor_builder();
+    server::enable_ech(&mut acceptor, 2);
+    let server = ech_accepted_server(acceptor);
+
+    let url = format!("https://localhost:{}/ech", server.addr().port());
+    let res = client_for(&server)
+        .ech_config_list("localhost", &stale)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.headers()["x-ech-accepted"], "true");
+}
+
+#[tokio::test]
+async fn ech_grease_without_config_list() {
+    let mut acceptor = server::tls_acceptor_builder();
+    server::enable_ech(&mut acceptor, 1);
+    let server = ech_accepted_server(acceptor);
+
+    let url = format!("https://localhost:{}/ech", server.addr().port());
+    let res = client_for(&server)
+        .chrome_builder(reqwest_impersonate::browser::ChromeVersion::V131)
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.headers()["x-ech-accepted"], "false");
+}
+
+/// Resolves every name to `addr`, publishing `config_list` for it.
+struct EchResolver {
+    addr: std::net::SocketAddr,
+    config_list: Vec<u8>,
+}
+
+impl reqwest_impersonate::dns::Resolve for EchResolver {
+    fn resolve(&self, _: hyper::client::connect::dns::Name) -> reqwest_impersonate::dns::Resolving {
+        let addrs: reqwest_impersonate::dns::Addrs = Box::new(std::iter::once(self.addr));
+        Box::pin(futures_util::future::ready(Ok(addrs)))
+    }
+
+    fn resolve_ech(
+        &self,
+        _: hyper::client::connect::dns::Name,
+    ) -> reqwest_impersonate::dns::ResolvingEch {
+        Box::pin(futures_util::future::ready(Ok(Some(
+            self.config_list.clone(),
+        ))))
+    }
+}
+
+#[tokio::test]
+async fn ech_with_resolver_config_list() {
+    let mut acceptor = server::tls_acceptor_builder();
+    let config_list = server::enable_ech(&mut acceptor, 1);
+    let server = ech_accepted_server(acceptor);
+
+    let url = format!("https://localhost:{}/ech", server.addr().port());
+    let res = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .dns_resolver(Arc::new(EchResolver {
+            addr: server.addr(),
+            config_list,
+        }))
+        .no_proxy()
+        .build()
+        .expect("client builder")
+        .get(&url)
+        .send()
+        .await
+        .expect("request");
+
+    assert_eq!(res.headers()["x-ech-accepted"], "true");
+}